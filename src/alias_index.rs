@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+type Trigram = [char; 3];
+
+/// Distinct character trigrams of `text` padded with two spaces on each side. The padding
+/// gives the first character a trigram of its own (`"  к"`), so strings that start with the
+/// same character always share at least one.
+pub(crate) fn trigrams(text: &str) -> Vec<Trigram> {
+    let padded: Vec<char> = "  ".chars().chain(text.chars()).chain("  ".chars()).collect();
+    let mut out: Vec<Trigram> = padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect();
+    out.sort_unstable();
+    out.dedup();
+    out
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    text: String,
+    chars: usize,
+    trigrams: usize,
+}
+
+/// Inverted trigram index for approximate lookups. One edit changes at most three trigrams,
+/// so two strings within Levenshtein distance `d` share all but `3d` of the distinct
+/// trigrams of either one, and differ in length by at most `d`; other entries are never
+/// compared. Entries sharing no trigram at all are not returned either, which is exact as
+/// long as the caller only accepts pairs with the same first character.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TrigramIndex {
+    entries: Vec<Entry>,
+    postings: HashMap<Trigram, Vec<u32>>,
+}
+
+impl TrigramIndex {
+    pub(crate) fn new(texts: impl IntoIterator<Item = String>) -> Self {
+        let mut index = Self::default();
+        for text in texts {
+            let id = index.entries.len() as u32;
+            let grams = trigrams(&text);
+            for gram in &grams {
+                index.postings.entry(*gram).or_default().push(id);
+            }
+            index.entries.push(Entry {
+                chars: text.chars().count(),
+                trigrams: grams.len(),
+                text,
+            });
+        }
+        index
+    }
+
+    /// Entries that may reach `threshold` in `strsim::normalized_levenshtein` against
+    /// `query`; the caller still compares them.
+    pub(crate) fn candidates(&self, query: &str, threshold: f64) -> Vec<&str> {
+        let query_chars = query.chars().count();
+        let query_trigrams = trigrams(query);
+        let mut shared: HashMap<u32, usize> = HashMap::new();
+        for gram in &query_trigrams {
+            for id in self.postings.get(gram).into_iter().flatten() {
+                *shared.entry(*id).or_default() += 1;
+            }
+        }
+        shared
+            .into_iter()
+            .filter_map(|(id, count)| {
+                let entry = &self.entries[id as usize];
+                // similarity = 1 - d / max_len; the epsilon keeps 0.3 * 10 from flooring to 2.
+                let max_distance = ((1.0 - threshold) * entry.chars.max(query_chars) as f64 + 1e-9).floor() as usize;
+                let close = entry.chars.abs_diff(query_chars) <= max_distance
+                    && count + 3 * max_distance >= entry.trigrams.max(query_trigrams.len());
+                close.then_some(entry.text.as_str())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strsim::normalized_levenshtein;
+
+    /// Every string one or two edits away from `word` that keeps its first character.
+    fn neighbours(word: &str) -> Vec<String> {
+        let alphabet = ['а', 'м', 'н', 'ь', ' '];
+        let edits = |word: &str| {
+            let chars: Vec<char> = word.chars().collect();
+            let mut out = Vec::new();
+            for i in 1..=chars.len() {
+                if i < chars.len() {
+                    let mut deleted = chars.clone();
+                    deleted.remove(i);
+                    out.push(deleted);
+                }
+                if i + 1 < chars.len() {
+                    let mut swapped = chars.clone();
+                    swapped.swap(i, i + 1);
+                    out.push(swapped);
+                }
+                for ch in alphabet {
+                    let mut inserted = chars.clone();
+                    inserted.insert(i, ch);
+                    out.push(inserted);
+                    if i < chars.len() {
+                        let mut replaced = chars.clone();
+                        replaced[i] = ch;
+                        out.push(replaced);
+                    }
+                }
+            }
+            out.into_iter().map(|c| c.into_iter().collect::<String>()).collect::<Vec<_>>()
+        };
+        let mut out: Vec<String> = edits(word).iter().flat_map(|w| edits(w)).collect();
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    #[test]
+    fn candidates_never_miss_a_close_entry() {
+        let words = ["камень", "кабель", "мох", "лед бур"];
+        for word in words {
+            let variants = neighbours(word);
+            let index = TrigramIndex::new(variants.iter().cloned());
+            for threshold in [0.6, 0.7, 0.75, 0.8, 0.9] {
+                let candidates = index.candidates(word, threshold);
+                for variant in &variants {
+                    if normalized_levenshtein(word, variant) >= threshold {
+                        assert!(
+                            candidates.contains(&variant.as_str()),
+                            "«{variant}» ~ «{word}» at {threshold}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn candidates_skip_distant_entries() {
+        let index = TrigramIndex::new(["камень", "кабель", "кактус", "камен"].map(str::to_string));
+        let mut candidates = index.candidates("камень", 0.8);
+        candidates.sort_unstable();
+        assert_eq!(candidates, ["камен", "камень"]);
+        assert_eq!(trigrams("ка"), [[' ', ' ', 'к'], [' ', 'к', 'а'], ['а', ' ', ' '], ['к', 'а', ' ']]);
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use strsim::normalized_levenshtein;
+
+use crate::phonetic::{phonetic_key, word_key};
+use crate::stemmer::stem_text;
+use crate::{alias_order, AppConfig, BlockCatalog, MIN_FUZZY_ALIAS_CHARS, MIN_FUZZY_PHONETIC_KEY, MIN_PHONETIC_KEY};
+
+/// Aliases sampled for the generated phrases.
+const GENERATED_ALIASES: usize = 200;
+/// Mismatching phrases printed in full.
+const SHOWN_MISMATCHES: usize = 5;
+
+/// `BlockCatalog` matching as it was before the indexes: every alias is compared with the
+/// text on every call. Kept as the baseline for `blockdeletee bench`.
+struct ScanMatcher<'a> {
+    catalog: &'a BlockCatalog,
+    phonetic_keys: HashMap<&'a str, String>,
+}
+
+impl<'a> ScanMatcher<'a> {
+    fn new(catalog: &'a BlockCatalog) -> Self {
+        let phonetic_keys = catalog
+            .sorted_aliases
+            .iter()
+            .map(|alias| (alias.as_str(), phonetic_key(alias)))
+            .collect();
+        Self { catalog, phonetic_keys }
+    }
+
+    fn match_blocks(&self, text: &str, fuzzy_threshold: f64) -> Vec<String> {
+        let normalized = self.catalog.normalize(text);
+        if normalized.is_empty() {
+            return Vec::new();
+        }
+        let mut matched = self.exact(&normalized);
+        if !self.catalog.stemmers.is_empty() {
+            let stemmed = self.stem(&normalized, &matched);
+            matched.extend(stemmed);
+        }
+        if fuzzy_threshold > 0.0 {
+            let fuzzy = self.fuzzy(&normalized, fuzzy_threshold, &matched);
+            matched.extend(fuzzy);
+        }
+        if self.catalog.phonetic_threshold > 0.0 {
+            let phonetic = self.phonetic(&normalized, &matched);
+            matched.extend(phonetic);
+        }
+        let mut aliases: Vec<String> = matched.into_iter().collect();
+        aliases.sort_by(|a, b| alias_order(a, b));
+        let mut blocks: Vec<String> = Vec::new();
+        for alias in &aliases {
+            for block_id in &self.catalog.alias_to_blocks[alias] {
+                if !blocks.contains(block_id) {
+                    blocks.push(block_id.clone());
+                }
+            }
+        }
+        blocks
+    }
+
+    fn exact(&self, normalized: &str) -> HashSet<String> {
+        let padded = format!(" {normalized} ");
+        self.catalog
+            .sorted_aliases
+            .iter()
+            .filter(|alias| padded.contains(&format!(" {alias} ")))
+            .cloned()
+            .collect()
+    }
+
+    fn stem(&self, normalized: &str, already: &HashSet<String>) -> HashSet<String> {
+        let padded = format!(" {} ", stem_text(normalized, &self.catalog.stemmers));
+        let mut stemmed = HashSet::new();
+        for (stem, aliases) in &self.catalog.stemmed_aliases {
+            if padded.contains(&format!(" {stem} ")) {
+                stemmed.extend(aliases.iter().filter(|a| !already.contains(*a)).cloned());
+            }
+        }
+        stemmed
+    }
+
+    fn fuzzy(&self, normalized: &str, threshold: f64, already: &HashSet<String>) -> HashSet<String> {
+        let words: Vec<&str> = normalized.split_whitespace().collect();
+        let mut fuzzy = HashSet::new();
+        for (word_count, aliases) in &self.catalog.aliases_by_word_count {
+            let candidates = BlockCatalog::ngram_candidates(&words, *word_count);
+            for alias in aliases {
+                if already.contains(alias) || alias.chars().count() < MIN_FUZZY_ALIAS_CHARS {
+                    continue;
+                }
+                let first = alias.chars().next();
+                let matched = candidates.iter().any(|candidate| {
+                    BlockCatalog::is_plausible_length(alias, candidate)
+                        && first == candidate.chars().next()
+                        && normalized_levenshtein(alias, candidate) >= threshold
+                });
+                if matched {
+                    fuzzy.insert(alias.clone());
+                }
+            }
+        }
+        fuzzy
+    }
+
+    fn phonetic(&self, normalized: &str, already: &HashSet<String>) -> HashSet<String> {
+        let word_keys: Vec<String> = normalized
+            .split_whitespace()
+            .map(word_key)
+            .filter(|key| !key.is_empty())
+            .collect();
+        let words: Vec<&str> = word_keys.iter().map(String::as_str).collect();
+        let threshold = self.catalog.phonetic_threshold;
+        let mut phonetic = HashSet::new();
+        for (word_count, aliases) in &self.catalog.aliases_by_word_count {
+            let candidates: Vec<String> = BlockCatalog::ngram_candidates(&words, *word_count)
+                .into_iter()
+                .map(|c| if *word_count == 1 { word_key(&c) } else { c })
+                .collect();
+            for alias in aliases {
+                let key = &self.phonetic_keys[alias.as_str()];
+                let key_len = key.chars().count();
+                if already.contains(alias) || key_len < MIN_PHONETIC_KEY {
+                    continue;
+                }
+                let first = key.chars().next();
+                let matched = candidates.iter().any(|candidate| {
+                    candidate == key
+                        || (key_len >= MIN_FUZZY_PHONETIC_KEY
+                            && first == candidate.chars().next()
+                            && BlockCatalog::is_plausible_length(key, candidate)
+                            && normalized_levenshtein(key, candidate) >= threshold)
+                });
+                if matched {
+                    phonetic.insert(alias.clone());
+                }
+            }
+        }
+        phonetic
+    }
+}
+
+/// Phrases around catalog aliases: plain, with a typo, and inside filler words.
+fn generated_phrases(catalog: &BlockCatalog) -> Vec<String> {
+    let step = (catalog.sorted_aliases.len() / GENERATED_ALIASES).max(1);
+    let mut phrases = Vec::new();
+    for alias in catalog.sorted_aliases.iter().step_by(step) {
+        phrases.push(format!("удали {alias}"));
+        let mut chars: Vec<char> = alias.chars().collect();
+        if chars.len() >= 4 {
+            let middle = chars.len() / 2;
+            chars.swap(middle - 1, middle);
+            phrases.push(format!("убери {}", chars.into_iter().collect::<String>()));
+        }
+        phrases.push(format!("дай мне пожалуйста {alias} и еще немного"));
+    }
+    phrases
+}
+
+fn mean(samples: &[Duration]) -> Duration {
+    samples.iter().sum::<Duration>() / samples.len().max(1) as u32
+}
+
+fn percentile(samples: &mut [Duration], p: f64) -> Duration {
+    samples.sort_unstable();
+    let index = ((samples.len() as f64 - 1.0) * p).round() as usize;
+    samples.get(index).copied().unwrap_or_default()
+}
+
+fn format_duration(d: Duration) -> String {
+    format!("{:.1} мкс", d.as_secs_f64() * 1e6)
+}
+
+/// `blockdeletee bench`: times every phrase against the old linear scans and the indexed
+/// `BlockCatalog::match_blocks`, and checks that both find the same blocks.
+pub(crate) fn run_match_bench(
+    config: &AppConfig,
+    config_dir: &Path,
+    phrases_file: Option<&Path>,
+    iterations: usize,
+) -> Result<(), String> {
+    let started = Instant::now();
+    let catalog = BlockCatalog::from_config(config, config_dir)?;
+    eprintln!(
+        "[bench] каталог: {} алиасов, загружен за {:.0} мс",
+        catalog.alias_count(),
+        started.elapsed().as_secs_f64() * 1000.0
+    );
+    let phrases = match phrases_file {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| format!("Не удалось прочитать `{}`: {e}", path.display()))?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+        None => generated_phrases(&catalog),
+    };
+    if phrases.is_empty() {
+        return Err("bench: нет фраз".to_string());
+    }
+    let iterations = iterations.max(1);
+    let fuzzy_threshold = config.speech.fuzzy_threshold;
+    let scan = ScanMatcher::new(&catalog);
+
+    let mut scan_times = Vec::with_capacity(phrases.len());
+    let mut index_times = Vec::with_capacity(phrases.len());
+    let mut mismatches = Vec::new();
+    for phrase in &phrases {
+        let time = |run: &dyn Fn() -> Vec<String>| {
+            let started = Instant::now();
+            let mut blocks = Vec::new();
+            for _ in 0..iterations {
+                blocks = run();
+            }
+            (started.elapsed() / iterations as u32, blocks)
+        };
+        let (scan_time, scan_blocks) = time(&|| scan.match_blocks(phrase, fuzzy_threshold));
+        let (index_time, index_blocks) = time(&|| catalog.match_block_ids(phrase, fuzzy_threshold));
+        scan_times.push(scan_time);
+        index_times.push(index_time);
+        if scan_blocks != index_blocks {
+            mismatches.push((phrase, scan_blocks, index_blocks));
+        }
+    }
+
+    let (scan_mean, index_mean) = (mean(&scan_times), mean(&index_times));
+    println!("фраз: {}, повторов: {iterations}", phrases.len());
+    println!(
+        "scan:  среднее {}, p95 {}",
+        format_duration(scan_mean),
+        format_duration(percentile(&mut scan_times, 0.95))
+    );
+    println!(
+        "index: среднее {}, p95 {}",
+        format_duration(index_mean),
+        format_duration(percentile(&mut index_times, 0.95))
+    );
+    println!(
+        "ускорение: x{:.1}",
+        scan_mean.as_secs_f64() / index_mean.as_secs_f64().max(f64::EPSILON)
+    );
+    println!("расхождений: {}", mismatches.len());
+    for (phrase, scan_blocks, index_blocks) in mismatches.iter().take(SHOWN_MISMATCHES) {
+        println!("  «{phrase}»: scan {scan_blocks:?}, index {index_blocks:?}");
+    }
+    Ok(())
+}
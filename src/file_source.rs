@@ -0,0 +1,428 @@
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::bounded;
+
+use crate::level_meter::{LevelMeter, WARNING_QUEUE};
+use crate::{
+    to_mono_f32, to_mono_f64, to_mono_i16, to_mono_i32, to_mono_u8, ui_log, ui_set_mic,
+    ChannelSelection, MicrophoneConfig, PcmBlock, RawAudioFormat, UiHandle,
+};
+
+/// Silence appended after the end of input so the recognizer can finalize the last phrase.
+const TRAILING_SILENCE_SECONDS: f64 = 1.0;
+/// How long `stop` waits for the reader thread before leaving it behind.
+const STOP_TIMEOUT: Duration = Duration::from_millis(500);
+/// Pause before offering a block again while the pipeline queue is full.
+const QUEUE_FULL_RETRY: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PcmSampleFormat {
+    U8,
+    I16,
+    I24,
+    I32,
+    F32,
+    F64,
+}
+
+impl PcmSampleFormat {
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::I16 => 2,
+            Self::I24 => 3,
+            Self::I32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PcmStreamFormat {
+    sample_format: PcmSampleFormat,
+    channels: usize,
+    sample_rate: u32,
+}
+
+/// Replays a WAV file, a raw s16le file or stdin as if it were a microphone.
+pub(crate) struct FileSource {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl FileSource {
+    /// `path == None` reads from stdin. `raw_sample_rate` applies to headerless s16le input.
+    /// `on_pcm` must not block: it hands the block back when the pipeline queue is full, and
+    /// the block is offered again until it fits or the source stops.
+    pub(crate) fn start(
+        config: &MicrophoneConfig,
+        path: Option<PathBuf>,
+        raw_sample_rate: u32,
+        ui: UiHandle,
+        on_pcm: impl Fn(PcmBlock) -> Option<PcmBlock> + Send + 'static,
+    ) -> Result<Self, String> {
+        let label = path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "stdin".to_string());
+        let input: Box<dyn Read + Send> = match &path {
+            Some(p) => Box::new(
+                File::open(p).map_err(|e| format!("Не удалось открыть аудио-файл `{label}`: {e}"))?,
+            ),
+            None => Box::new(io::stdin()),
+        };
+        let mut reader = BufReader::new(input);
+
+        let mut magic = Vec::with_capacity(4);
+        (&mut reader)
+            .take(4)
+            .read_to_end(&mut magic)
+            .map_err(|e| format!("Не удалось прочитать `{label}`: {e}"))?;
+        let is_wav = magic == b"RIFF";
+        let (format, mut stream): (PcmStreamFormat, Box<dyn Read + Send>) = match config.input_format {
+            RawAudioFormat::Wav if !is_wav => {
+                return Err(format!("`{label}` не похож на WAV (нет заголовка RIFF)"));
+            }
+            RawAudioFormat::Wav | RawAudioFormat::Auto if is_wav => {
+                let (format, data_len) = read_wav_header(&mut reader)
+                    .map_err(|e| format!("Ошибка WAV заголовка `{label}`: {e}"))?;
+                // Chunks after `data` (LIST, id3, ...) are not audio.
+                match data_len {
+                    Some(len) => (format, Box::new(reader.take(len))),
+                    None => (format, Box::new(reader)),
+                }
+            }
+            _ => {
+                let format = PcmStreamFormat {
+                    sample_format: PcmSampleFormat::I16,
+                    channels: config.input_channels.max(1) as usize,
+                    sample_rate: config.input_samplerate.unwrap_or(raw_sample_rate),
+                };
+                (format, Box::new(Cursor::new(magic).chain(reader)))
+            }
+        };
+
+        if format.sample_rate == 0 {
+            return Err(format!("`{label}`: sample_rate=0"));
+        }
+
+        let selection = config.channel.fit(format.channels).unwrap_or_else(|warning| {
+            ui_log(&ui, format!("[input-file-warning] {warning}"));
+            ChannelSelection::Mix
+        });
+        let speed = config.input_speed;
+        let frames_per_block = config.blocksize.max(1) as usize;
+        let stop = Arc::new(AtomicBool::new(false));
+        ui_set_mic(&ui, true);
+        ui_log(
+            &ui,
+            format!(
+                "[input-file] запущен: {label} | channels={} | channel={selection} | sample_rate={} | format={:?} | speed={}",
+                format.channels,
+                format.sample_rate,
+                format.sample_format,
+                if speed > 0.0 { format!("{speed}x") } else { "max".to_string() }
+            ),
+        );
+
+        let handle = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let bytes_per_frame = format.channels * format.sample_format.bytes_per_sample();
+                let mut buf = vec![0u8; frames_per_block * bytes_per_frame];
+                let started = Instant::now();
+                let mut sent_frames: u64 = 0;
+                // Not a real-time thread, so the reader logs the warnings itself.
+                let (warning_tx, warning_rx) = bounded(WARNING_QUEUE);
+                let mut meter = LevelMeter::new(Arc::clone(&ui), warning_tx);
+
+                let send = |mut block: PcmBlock| {
+                    while let Some(back) = on_pcm(block) {
+                        if stop.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        thread::sleep(QUEUE_FULL_RETRY);
+                        block = back;
+                    }
+                };
+                let pace = |sent_frames: u64| {
+                    if speed <= 0.0 {
+                        return;
+                    }
+                    let due = Duration::from_secs_f64(sent_frames as f64 / format.sample_rate as f64 / speed);
+                    let elapsed = started.elapsed();
+                    if due > elapsed {
+                        sleep_unless_stopped(due - elapsed, &stop);
+                    }
+                };
+
+                while !stop.load(Ordering::Relaxed) {
+                    let filled = match read_full(&mut stream, &mut buf) {
+                        Ok(n) => n,
+                        Err(err) => {
+                            ui_set_mic(&ui, false);
+                            ui_log(&ui, format!("[input-file-error] {label}: {err}"));
+                            return;
+                        }
+                    };
+                    let usable = filled - filled % bytes_per_frame;
+                    if usable > 0 {
+                        let mono = decode_block(&buf[..usable], format, &selection);
+                        meter.feed(&mono);
+                        for line in warning_rx.try_iter() {
+                            ui_log(&ui, line);
+                        }
+                        sent_frames += mono.len() as u64;
+                        send(PcmBlock {
+                            sample_rate: format.sample_rate,
+                            samples: mono,
+                        });
+                        pace(sent_frames);
+                    }
+                    if filled < buf.len() {
+                        break;
+                    }
+                }
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                let mut silence_left = (format.sample_rate as f64 * TRAILING_SILENCE_SECONDS) as usize;
+                while silence_left > 0 && !stop.load(Ordering::Relaxed) {
+                    let n = silence_left.min(frames_per_block);
+                    silence_left -= n;
+                    sent_frames += n as u64;
+                    send(PcmBlock {
+                        sample_rate: format.sample_rate,
+                        samples: vec![0i16; n],
+                    });
+                    pace(sent_frames);
+                }
+                ui_log(
+                    &ui,
+                    format!(
+                        "[input-file] воспроизведение завершено: {label} ({:.1}s аудио)",
+                        sent_frames as f64 / format.sample_rate as f64
+                    ),
+                );
+            })
+        };
+
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// A read from stdin blocks until the writer sends more data or closes the pipe, so the
+    /// thread is joined only if it finishes within `STOP_TIMEOUT`; otherwise it is left to
+    /// exit on its own once the read returns.
+    pub(crate) fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        if handle.is_finished() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn sleep_unless_stopped(total: Duration, stop: &AtomicBool) {
+    let deadline = Instant::now() + total;
+    while !stop.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(50)));
+    }
+}
+
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+fn read_u16_le(reader: &mut impl Read) -> io::Result<u16> {
+    let mut b = [0u8; 2];
+    reader.read_exact(&mut b)?;
+    Ok(u16::from_le_bytes(b))
+}
+
+fn read_u32_le(reader: &mut impl Read) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    reader.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+/// Parses a RIFF/WAVE header up to the start of the `data` chunk. The `RIFF` magic is already consumed.
+/// Also returns the size of the `data` chunk; `None` when the writer left it as 0 or
+/// 0xFFFFFFFF, as streaming encoders do, and the audio runs to the end of input.
+fn read_wav_header(reader: &mut impl Read) -> Result<(PcmStreamFormat, Option<u64>), String> {
+    let _riff_size = read_u32_le(reader).map_err(|e| e.to_string())?;
+    let mut wave = [0u8; 4];
+    reader.read_exact(&mut wave).map_err(|e| e.to_string())?;
+    if &wave != b"WAVE" {
+        return Err("ожидался тип WAVE".to_string());
+    }
+
+    let mut format: Option<PcmStreamFormat> = None;
+    loop {
+        let mut id = [0u8; 4];
+        reader
+            .read_exact(&mut id)
+            .map_err(|_| "не найден chunk `data`".to_string())?;
+        let size = read_u32_le(reader).map_err(|e| e.to_string())?;
+        match &id {
+            b"fmt " => {
+                let mut body = vec![0u8; size as usize];
+                reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+                if size % 2 == 1 {
+                    let _ = read_full(reader, &mut [0u8; 1]);
+                }
+                if body.len() < 16 {
+                    return Err("слишком короткий chunk `fmt `".to_string());
+                }
+                let mut cursor = Cursor::new(&body);
+                let mut tag = read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
+                let channels = read_u16_le(&mut cursor).map_err(|e| e.to_string())?;
+                let sample_rate = read_u32_le(&mut cursor).map_err(|e| e.to_string())?;
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real format tag in the sub-format GUID.
+                if tag == 0xFFFE && body.len() >= 26 {
+                    tag = u16::from_le_bytes([body[24], body[25]]);
+                }
+                let sample_format = match (tag, bits) {
+                    (1, 8) => PcmSampleFormat::U8,
+                    (1, 16) => PcmSampleFormat::I16,
+                    (1, 24) => PcmSampleFormat::I24,
+                    (1, 32) => PcmSampleFormat::I32,
+                    (3, 32) => PcmSampleFormat::F32,
+                    (3, 64) => PcmSampleFormat::F64,
+                    _ => return Err(format!("неподдерживаемый формат WAV: tag={tag}, bits={bits}")),
+                };
+                if channels == 0 {
+                    return Err("channels=0 в WAV заголовке".to_string());
+                }
+                format = Some(PcmStreamFormat {
+                    sample_format,
+                    channels: channels as usize,
+                    sample_rate,
+                });
+            }
+            b"data" => {
+                let format = format.ok_or_else(|| "chunk `data` встретился раньше `fmt `".to_string())?;
+                let data_len = (size != 0 && size != u32::MAX).then_some(size as u64);
+                return Ok((format, data_len));
+            }
+            _ => {
+                let skip = size as u64 + (size % 2) as u64;
+                io::copy(&mut reader.take(skip), &mut io::sink()).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+}
+
+fn decode_block(bytes: &[u8], format: PcmStreamFormat, selection: &ChannelSelection) -> Vec<i16> {
+    let channels = format.channels;
+    match format.sample_format {
+        PcmSampleFormat::U8 => to_mono_u8(bytes, channels, selection),
+        PcmSampleFormat::I16 => {
+            let samples: Vec<i16> = bytes
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                .collect();
+            to_mono_i16(&samples, channels, selection)
+        }
+        PcmSampleFormat::I24 => {
+            let samples: Vec<i32> = bytes
+                .chunks_exact(3)
+                .map(|b| i32::from_le_bytes([0, b[0], b[1], b[2]]))
+                .collect();
+            to_mono_i32(&samples, channels, selection)
+        }
+        PcmSampleFormat::I32 => {
+            let samples: Vec<i32> = bytes
+                .chunks_exact(4)
+                .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            to_mono_i32(&samples, channels, selection)
+        }
+        PcmSampleFormat::F32 => {
+            let samples: Vec<f32> = bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            to_mono_f32(&samples, channels, selection)
+        }
+        PcmSampleFormat::F64 => {
+            let samples: Vec<f64> = bytes
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            to_mono_f64(&samples, channels, selection)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A mono 16 kHz s16le WAV without the `RIFF` magic, as `read_wav_header` sees it.
+    fn wav(data_size: u32, data: &[u8], trailer: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(b"WAVEfmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        for field in [1u16, 1] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        out.extend_from_slice(&16_000u32.to_le_bytes());
+        out.extend_from_slice(&32_000u32.to_le_bytes());
+        for field in [2u16, 16] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&data_size.to_le_bytes());
+        out.extend_from_slice(data);
+        out.extend_from_slice(trailer);
+        out
+    }
+
+    #[test]
+    fn data_chunk_size_bounds_the_audio() {
+        let bytes = wav(4, &[1, 0, 2, 0], b"LIST\x04\x00\x00\x00junk");
+        let mut reader = Cursor::new(bytes);
+        let (format, data_len) = read_wav_header(&mut reader).unwrap();
+        assert_eq!((format.sample_format, format.channels, format.sample_rate), (PcmSampleFormat::I16, 1, 16_000));
+        assert_eq!(data_len, Some(4));
+    }
+
+    #[test]
+    fn streaming_data_size_runs_to_the_end() {
+        for size in [0, u32::MAX] {
+            let mut reader = Cursor::new(wav(size, &[1, 0], &[]));
+            assert_eq!(read_wav_header(&mut reader).unwrap().1, None);
+        }
+    }
+}
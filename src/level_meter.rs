@@ -0,0 +1,176 @@
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+
+use crate::{ui_set_input_level, UiHandle};
+
+/// Lowest level shown; digital silence is clamped here instead of `-inf`.
+pub(crate) const LEVEL_FLOOR_DB: f32 = -90.0;
+/// |sample| at or above this counts as clipped (about -0.1 dBFS).
+const CLIP_SAMPLE: i32 = 32_400;
+/// Below this RMS the input is considered silent (muted mic, wrong device, zero gain).
+const SILENCE_DB: f32 = -60.0;
+const SILENCE_WARN_AFTER: Duration = Duration::from_secs(10);
+const CLIP_WARN_INTERVAL: Duration = Duration::from_secs(5);
+/// The UI keeps showing the clip indicator this long after the last clipped block.
+const CLIP_HOLD: Duration = Duration::from_secs(1);
+const PUBLISH_INTERVAL: Duration = Duration::from_millis(50);
+/// Peak-hold falloff speed for the meter.
+const PEAK_DECAY_DB_PER_SEC: f32 = 20.0;
+/// Warnings waiting to be logged; more are dropped. They come at most every few seconds.
+pub(crate) const WARNING_QUEUE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct InputLevel {
+    /// RMS of the last block, dBFS.
+    pub(crate) rms_db: f32,
+    /// Peak with hold/decay, dBFS.
+    pub(crate) peak_db: f32,
+    pub(crate) clipping: bool,
+}
+
+/// Tracks RMS/peak of incoming PCM blocks, publishes them to the UI and reports
+/// clipping/silence warnings.
+///
+/// `feed` runs inside the audio callback, so it never waits for the UI lock: the level is
+/// published only when the lock is free, and warnings go to a queue that the owner of the
+/// receiving end writes to the log.
+pub(crate) struct LevelMeter {
+    ui: UiHandle,
+    warnings: Sender<String>,
+    peak_db: f32,
+    last_block: Option<Instant>,
+    last_publish: Option<Instant>,
+    last_clip: Option<Instant>,
+    last_clip_warning: Option<Instant>,
+    silent_since: Option<Instant>,
+    silence_warned: bool,
+}
+
+impl LevelMeter {
+    pub(crate) fn new(ui: UiHandle, warnings: Sender<String>) -> Self {
+        Self {
+            ui,
+            warnings,
+            peak_db: LEVEL_FLOOR_DB,
+            last_block: None,
+            last_publish: None,
+            last_clip: None,
+            last_clip_warning: None,
+            silent_since: None,
+            silence_warned: false,
+        }
+    }
+
+    pub(crate) fn feed(&mut self, samples: &[i16]) {
+        if samples.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let energy: f64 = samples.iter().map(|v| (*v as f64) * (*v as f64)).sum();
+        let rms_db = to_dbfs((energy / samples.len() as f64).sqrt());
+        let peak = samples.iter().map(|v| (*v as i32).abs()).max().unwrap_or(0);
+        let clipped = samples.iter().filter(|v| (**v as i32).abs() >= CLIP_SAMPLE).count();
+
+        let elapsed = self
+            .last_block
+            .map(|t| now.duration_since(t).as_secs_f32())
+            .unwrap_or(0.0);
+        self.last_block = Some(now);
+        self.peak_db = (self.peak_db - PEAK_DECAY_DB_PER_SEC * elapsed)
+            .max(to_dbfs(peak as f64))
+            .max(LEVEL_FLOOR_DB);
+
+        if clipped > 0 {
+            self.last_clip = Some(now);
+            if self
+                .last_clip_warning
+                .is_none_or(|t| now.duration_since(t) >= CLIP_WARN_INTERVAL)
+            {
+                self.last_clip_warning = Some(now);
+                self.warn(format!(
+                    "[level-warning] клиппинг: {:.1}% сэмплов на пределе — уменьши усиление микрофона",
+                    clipped as f64 * 100.0 / samples.len() as f64
+                ));
+            }
+        }
+
+        if rms_db < SILENCE_DB {
+            let since = *self.silent_since.get_or_insert(now);
+            if !self.silence_warned && now.duration_since(since) >= SILENCE_WARN_AFTER {
+                self.silence_warned = true;
+                self.warn(format!(
+                    "[level-warning] вход молчит {}s (уровень {rms_db:.0} dBFS) — проверь устройство, mute и громкость",
+                    SILENCE_WARN_AFTER.as_secs()
+                ));
+            }
+        } else {
+            if self.silence_warned {
+                self.warn(format!("[level] сигнал появился: {rms_db:.0} dBFS"));
+            }
+            self.silent_since = None;
+            self.silence_warned = false;
+        }
+
+        if self
+            .last_publish
+            .is_none_or(|t| now.duration_since(t) >= PUBLISH_INTERVAL)
+        {
+            self.last_publish = Some(now);
+            ui_set_input_level(
+                &self.ui,
+                InputLevel {
+                    rms_db,
+                    peak_db: self.peak_db,
+                    clipping: self.last_clip.is_some_and(|t| now.duration_since(t) < CLIP_HOLD),
+                },
+            );
+        }
+    }
+
+    /// Dropped when the queue is full; the log is not worth stalling the audio for.
+    fn warn(&self, line: String) {
+        let _ = self.warnings.try_send(line);
+    }
+}
+
+fn to_dbfs(amplitude: f64) -> f32 {
+    if amplitude <= 0.0 {
+        return LEVEL_FLOOR_DB;
+    }
+    ((20.0 * (amplitude / i16::MAX as f64).log10()) as f32).max(LEVEL_FLOOR_DB)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crossbeam_channel::bounded;
+
+    use super::*;
+    use crate::{UiMode, UiState};
+
+    #[test]
+    fn feed_queues_warnings_while_the_ui_is_locked() {
+        let ui = Arc::new(Mutex::new(UiState::new(
+            "Steve".to_string(),
+            "127.0.0.1".to_string(),
+            25575,
+            String::new(),
+            UiMode::Tui,
+        )));
+        let (warning_tx, warning_rx) = bounded(1);
+        let mut meter = LevelMeter::new(Arc::clone(&ui), warning_tx);
+
+        let guard = ui.lock().unwrap();
+        meter.feed(&[i16::MAX, 0, i16::MIN, 0]);
+        meter.last_clip_warning = None;
+        // The queue is full: the second warning is dropped instead of waiting.
+        meter.feed(&[i16::MAX; 4]);
+        drop(guard);
+
+        let warnings: Vec<String> = warning_rx.try_iter().collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("[level-warning] клиппинг: 50.0%"), "{}", warnings[0]);
+    }
+}
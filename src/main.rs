@@ -1,24 +1,29 @@
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
 use std::io::stdout;
 use std::io::{Read, Write};
 use std::net::{TcpStream, ToSocketAddrs};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{BufferSize, Device, SampleFormat, SampleRate, Stream, StreamConfig, SupportedStreamConfigRange};
-use crossterm::event::{self, Event as CEvent, KeyCode};
+use crossterm::event::{
+    self, Event as CEvent, KeyCode, KeyEventKind, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
 use crossterm::execute;
 use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+    disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+    LeaveAlternateScreen,
 };
-use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, SendTimeoutError, Sender, TrySendError};
 use glob::Pattern;
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
@@ -30,14 +35,63 @@ use regex::Regex;
 use serde::Deserialize;
 use serde_json::Value;
 use strsim::normalized_levenshtein;
-use vosk::{set_log_level, CompleteResult, DecodingState, LogLevel, Model, Recognizer};
 
+use alias_index::TrigramIndex;
+use file_source::FileSource;
+use level_meter::{InputLevel, LevelMeter, LEVEL_FLOOR_DB, WARNING_QUEUE};
+use models::{base_language, discover_models, model_config_path, model_for_locale, ModelInfo, DEFAULT_MODEL_NAME};
+use net_source::{NetworkContext, NetworkServer};
+use phonetic::{phonetic_key, word_key};
+use recorder::SessionRecorder;
+use resample::Resampler;
+use stemmer::{stem_text, Stemmer};
+use speech::{Alternative, Hypothesis, SpeechEngine, SpeechOutput, SpeechStream, WordInfo};
+use vad::VoiceActivityGate;
+use voiceprints::SpeakerIdentifier;
+
+mod alias_index;
 mod backend_bootstrap;
+mod bench;
+mod file_source;
+mod level_meter;
+mod match_cli;
+mod models;
+mod phonetic;
+mod net_source;
+mod recorder;
+mod resample;
+mod speech;
+mod stemmer;
 mod ui_qt;
 mod ui_tui;
+mod vad;
+mod voiceprints;
+#[cfg(feature = "whisper")]
+mod whisper_engine;
 
 const MIC_SPEAKER_ID: &str = "mic";
+/// Without key release events push-to-talk releases after this long without a key repeat.
+const PTT_AUTO_RELEASE: Duration = Duration::from_millis(700);
+/// How long releasing push-to-talk or muting waits for room in a full pipeline queue
+/// (a model swap or a whisper run) before giving up on the flush/reset.
+const GATE_CONTROL_TIMEOUT: Duration = Duration::from_secs(2);
 const BLOCK_KEY_PREFIX: &str = "block.minecraft.";
+/// The level meter goes blank when no audio block arrived for this long.
+const INPUT_LEVEL_STALE: Duration = Duration::from_secs(1);
+/// Recent block matches kept for the debug panel.
+const MATCH_HISTORY: usize = 64;
+/// Left edge of the TUI level bar, dBFS.
+const METER_MIN_DB: f32 = -60.0;
+/// Upper bound for `speech.max_alternatives`; longer N-best lists are mostly noise.
+const MAX_ALTERNATIVES: u16 = 10;
+/// Shorter aliases only match exactly (or by stem or sound); one typo is a third of them.
+const MIN_FUZZY_ALIAS_CHARS: usize = 5;
+/// Phonetic keys shorter than this are too ambiguous to match at all («лед» and «лет»).
+const MIN_PHONETIC_KEY: usize = 4;
+/// Shorter phonetic keys only match when equal; `speech.phonetic_threshold` applies above.
+const MIN_FUZZY_PHONETIC_KEY: usize = 5;
+/// Upper bound for `speech.trigger_delay_ms`; longer waits make deleting feel broken.
+const MAX_TRIGGER_DELAY_MS: u64 = 2_000;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum UiMode {
@@ -69,12 +123,20 @@ pub(crate) struct UiSnapshot {
     pub(crate) rec_ok: bool,
     pub(crate) rcon_ok: bool,
     pub(crate) player_online: bool,
+    /// `None` when the VAD gate is disabled.
+    pub(crate) listening: Option<bool>,
+    /// `None` when `microphone.activation` is `always`.
+    pub(crate) voice_gate: Option<bool>,
+    /// `None` when no audio reached the app recently.
+    pub(crate) input_level: Option<InputLevel>,
     pub(crate) player_name: String,
     pub(crate) rcon_host: String,
     pub(crate) rcon_port: u16,
     pub(crate) rcon_password: String,
     pub(crate) ui_mode: UiMode,
     pub(crate) overlay_error: Option<String>,
+    /// Recent block matches with the alias and method behind each, oldest first.
+    pub(crate) matches: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -90,12 +152,16 @@ pub(crate) struct UiState {
     rec_ok: bool,
     rcon_ok: bool,
     player_online: bool,
+    listening: Option<bool>,
+    voice_gate: Option<bool>,
+    input_level: Option<(InputLevel, Instant)>,
     player_name: String,
     rcon_host: String,
     rcon_port: u16,
     rcon_password: String,
     ui_mode: UiMode,
     overlay_error: Option<String>,
+    matches: VecDeque<String>,
 }
 
 pub(crate) type UiHandle = Arc<Mutex<UiState>>;
@@ -119,12 +185,16 @@ impl UiState {
             rec_ok: false,
             rcon_ok: false,
             player_online: false,
+            listening: None,
+            voice_gate: None,
+            input_level: None,
             player_name,
             rcon_host,
             rcon_port,
             rcon_password,
             ui_mode,
             overlay_error: None,
+            matches: VecDeque::with_capacity(MATCH_HISTORY),
         }
     }
 }
@@ -155,6 +225,8 @@ enum SettingsField {
     Password,
     PlayerName,
     UiMode,
+    Microphone,
+    Model,
 }
 
 impl SettingsField {
@@ -164,27 +236,45 @@ impl SettingsField {
 enum SettingsTab {
     Connection,
     App,
+    Microphone,
+    Model,
 }
 
 impl SettingsTab {
     fn next(self) -> Self {
         match self {
             Self::Connection => Self::App,
-            Self::App => Self::Connection,
+            Self::App => Self::Microphone,
+            Self::Microphone => Self::Model,
+            Self::Model => Self::Connection,
         }
     }
     fn prev(self) -> Self {
-        self.next()
+        match self {
+            Self::Connection => Self::Model,
+            Self::App => Self::Connection,
+            Self::Microphone => Self::App,
+            Self::Model => Self::Microphone,
+        }
+    }
+
+    /// Tabs that are a list picked with ↑↓ + Enter rather than a form.
+    fn is_list(self) -> bool {
+        matches!(self, Self::Microphone | Self::Model)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 struct TuiControls {
     selected: FooterButton,
     settings_open: bool,
     settings_field: SettingsField,
     settings_editing: bool,
     settings_tab: SettingsTab,
+    /// Debug panel with the recent block matches next to the logs.
+    matches_open: bool,
+    /// Footer hint for the push-to-talk/toggle key, e.g. `("Space", "говорить")`.
+    activation_hint: Option<(String, &'static str)>,
 }
 
 #[derive(Debug, Clone)]
@@ -194,6 +284,15 @@ struct SettingsDraft {
     password: String,
     player_name: String,
     ui_mode: UiMode,
+    mic_devices: Vec<InputDeviceInfo>,
+    /// Row under the cursor in the device list; 0 is the system default, `i + 1` is `mic_devices[i]`.
+    mic_cursor: usize,
+    /// Row of the device currently in use, same numbering as `mic_cursor`.
+    mic_active: usize,
+    models: Vec<ModelInfo>,
+    model_cursor: usize,
+    /// Index in `models` of the model in use, if it is among them.
+    model_active: Option<usize>,
 }
 
 pub(crate) fn ui_snapshot(ui: &UiHandle) -> UiSnapshot {
@@ -214,12 +313,19 @@ pub(crate) fn ui_snapshot(ui: &UiHandle) -> UiSnapshot {
         rec_ok: guard.rec_ok,
         rcon_ok: guard.rcon_ok,
         player_online: guard.player_online,
+        listening: guard.listening,
+        voice_gate: guard.voice_gate,
+        input_level: guard
+            .input_level
+            .filter(|(_, at)| at.elapsed() < INPUT_LEVEL_STALE)
+            .map(|(level, _)| level),
         player_name: guard.player_name.clone(),
         rcon_host: guard.rcon_host.clone(),
         rcon_port: guard.rcon_port,
         rcon_password: guard.rcon_password.clone(),
         ui_mode: guard.ui_mode,
         overlay_error: guard.overlay_error.clone(),
+        matches: guard.matches.iter().cloned().collect(),
     }
 }
 
@@ -243,6 +349,34 @@ pub(crate) fn ui_log(ui: &UiHandle, msg: impl Into<String>) {
     guard.logs.push_back(UiLogEntry { text: msg, count: 1 });
 }
 
+/// Adds a line to the match panel.
+fn ui_note_match(ui: &UiHandle, line: String) {
+    if let Ok(mut guard) = ui.lock() {
+        if guard.matches.len() >= MATCH_HISTORY {
+            guard.matches.pop_front();
+        }
+        guard.matches.push_back(line);
+    }
+}
+
+/// Takes the log lines written so far; used where there is no UI to draw them.
+pub(crate) fn ui_drain_logs(ui: &UiHandle) -> Vec<String> {
+    let Ok(mut guard) = ui.lock() else {
+        return Vec::new();
+    };
+    guard
+        .logs
+        .drain(..)
+        .map(|item| {
+            if item.count > 1 {
+                format!("{} ({}x)", item.text, item.count)
+            } else {
+                item.text
+            }
+        })
+        .collect()
+}
+
 fn ui_set_mic(ui: &UiHandle, ok: bool) {
     if let Ok(mut guard) = ui.lock() {
         guard.mic_ok = ok;
@@ -279,6 +413,25 @@ fn ui_set_player_online(ui: &UiHandle, online: bool) {
     }
 }
 
+fn ui_set_listening(ui: &UiHandle, listening: Option<bool>) {
+    if let Ok(mut guard) = ui.lock() {
+        guard.listening = listening;
+    }
+}
+
+fn ui_set_voice_gate(ui: &UiHandle, open: Option<bool>) {
+    if let Ok(mut guard) = ui.lock() {
+        guard.voice_gate = open;
+    }
+}
+
+/// Called from audio callbacks, so it never waits for the UI lock; a skipped update is harmless.
+fn ui_set_input_level(ui: &UiHandle, level: InputLevel) {
+    if let Ok(mut guard) = ui.try_lock() {
+        guard.input_level = Some((level, Instant::now()));
+    }
+}
+
 fn classify_overlay_error(msg: &str) -> Option<String> {
     let lower = msg.to_lowercase();
     if lower.contains("[rcon-error]") || lower.contains("rcon authentication failed") {
@@ -291,7 +444,10 @@ fn classify_overlay_error(msg: &str) -> Option<String> {
         Some(msg.to_string())
     } else if lower.contains("[notify-error]") {
         Some(msg.to_string())
-    } else if lower.contains("[microphone-status]") || lower.contains("[recognizer-error]") {
+    } else if lower.contains("[microphone-status]")
+        || lower.contains("[input-file-error]")
+        || lower.contains("[recognizer-error]")
+    {
         Some(msg.to_string())
     } else if lower.contains("[settings-error]") || lower.contains("[restart-error]") {
         Some(msg.to_string())
@@ -319,6 +475,56 @@ fn status_spans(icon: &str, label: &str, ok: bool) -> Vec<Span<'static>> {
     spans
 }
 
+fn activity_spans(icon: &str, label: &str, active: bool) -> Vec<Span<'static>> {
+    let color = if active { Color::Green } else { Color::DarkGray };
+    vec![Span::styled(format!("{icon} {label} ●"), Style::default().fg(color))]
+}
+
+fn level_meter_spans(level: Option<InputLevel>, width: usize) -> Vec<Span<'static>> {
+    let Some(level) = level else {
+        return vec![
+            Span::styled("󰕾 LVL ", Style::default().fg(Color::DarkGray)),
+            Span::styled("·".repeat(width), Style::default().fg(Color::DarkGray)),
+            Span::styled("  нет сигнала", Style::default().fg(Color::Red)),
+        ];
+    };
+    let fraction = |db: f32| ((db - METER_MIN_DB) / -METER_MIN_DB).clamp(0.0, 1.0);
+    let filled = (fraction(level.rms_db) * width as f32).round() as usize;
+    let peak = ((fraction(level.peak_db) * width as f32).round() as usize).min(width.saturating_sub(1));
+    let mut spans = vec![Span::raw("󰕾 LVL ")];
+    for i in 0..width {
+        let zone = i as f32 / width as f32;
+        let color = if zone >= 0.9 {
+            Color::Red
+        } else if zone >= 0.7 {
+            Color::Yellow
+        } else {
+            Color::Green
+        };
+        let (glyph, color) = if i < filled {
+            ("█", color)
+        } else if i == peak && level.peak_db > METER_MIN_DB {
+            ("▏", color)
+        } else {
+            ("·", Color::DarkGray)
+        };
+        spans.push(Span::styled(glyph, Style::default().fg(color)));
+    }
+    let db_text = if level.rms_db <= LEVEL_FLOOR_DB {
+        "  -inf dB".to_string()
+    } else {
+        format!("  {:.0} dB", level.rms_db)
+    };
+    spans.push(Span::styled(db_text, Style::default().fg(Color::Gray)));
+    if level.clipping {
+        spans.push(Span::styled(
+            "  CLIP",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+    }
+    spans
+}
+
 fn log_color(text: &str) -> Color {
     let lower = text.to_lowercase();
     if lower.contains("error") || lower.contains("ошибка") {
@@ -347,6 +553,7 @@ fn is_rcon_error_like(response: &str) -> bool {
 
 struct TuiGuard {
     terminal: Terminal<CrosstermBackend<std::io::Stdout>>,
+    key_release_events: bool,
 }
 
 impl TuiGuard {
@@ -356,7 +563,24 @@ impl TuiGuard {
         execute!(out, EnterAlternateScreen).map_err(|e| format!("enter alt screen error: {e}"))?;
         let backend = CrosstermBackend::new(out);
         let terminal = Terminal::new(backend).map_err(|e| format!("terminal init error: {e}"))?;
-        Ok(Self { terminal })
+        Ok(Self {
+            terminal,
+            key_release_events: false,
+        })
+    }
+
+    /// Asks the terminal for key release events (kitty keyboard protocol). Returns whether it worked.
+    fn enable_key_release_events(&mut self) -> bool {
+        if supports_keyboard_enhancement().unwrap_or(false)
+            && execute!(
+                self.terminal.backend_mut(),
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )
+            .is_ok()
+        {
+            self.key_release_events = true;
+        }
+        self.key_release_events
     }
 
     fn draw(
@@ -385,6 +609,18 @@ impl TuiGuard {
                 status_spans_row.extend(status_spans("󰒓", "RCON", snap.rcon_ok));
                 status_spans_row.push(Span::raw("   "));
                 status_spans_row.extend(status_spans("󰀄", "PLAYER", snap.player_online));
+                if let Some(open) = snap.voice_gate {
+                    status_spans_row.push(Span::raw("   "));
+                    if open {
+                        status_spans_row.extend(activity_spans("󰍬", "TALK", true));
+                    } else {
+                        status_spans_row.push(Span::styled("󰍭 MUTED ●", Style::default().fg(Color::Yellow)));
+                    }
+                }
+                if let Some(listening) = snap.listening {
+                    status_spans_row.push(Span::raw("   "));
+                    status_spans_row.extend(activity_spans("󰍬", "VAD", listening));
+                }
                 let status_line = Line::from(status_spans_row);
 
                 let top_has_problem = !(snap.mic_ok && snap.rec_ok && snap.rcon_ok && snap.player_online);
@@ -393,7 +629,13 @@ impl TuiGuard {
                 let top = Paragraph::new(vec![
                     Line::from("BlockDeletee"),
                     status_line,
-                    Line::from(format!("Игрок: {}", snap.player_name)),
+                    Line::from(
+                        [
+                            vec![Span::raw(format!("Игрок: {}", snap.player_name)), Span::raw("   ")],
+                            level_meter_spans(snap.input_level, 20),
+                        ]
+                        .concat(),
+                    ),
                 ])
                 .block(
                     Block::default()
@@ -404,7 +646,38 @@ impl TuiGuard {
                 );
                 f.render_widget(top, chunks[0]);
 
-                let visible_log_rows = chunks[1].height.saturating_sub(2) as usize;
+                let log_area = if controls.matches_open {
+                    let columns = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+                        .split(chunks[1]);
+                    let visible = columns[1].height.saturating_sub(2) as usize;
+                    let mut match_lines: Vec<Line> = snap
+                        .matches
+                        .iter()
+                        .skip(snap.matches.len().saturating_sub(visible))
+                        .map(|s| Line::from(s.clone()))
+                        .collect();
+                    if match_lines.is_empty() {
+                        match_lines.push(Line::from(Span::styled(
+                            "Совпадений еще не было",
+                            Style::default().fg(Color::DarkGray),
+                        )));
+                    }
+                    let matches = Paragraph::new(match_lines).block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_type(BorderType::Rounded)
+                            .border_style(Style::default().fg(Color::Cyan))
+                            .title("󰍉 Matches"),
+                    );
+                    f.render_widget(matches, columns[1]);
+                    columns[0]
+                } else {
+                    chunks[1]
+                };
+
+                let visible_log_rows = log_area.height.saturating_sub(2) as usize;
                 let logs_border_color = snap
                     .logs
                     .last()
@@ -432,7 +705,7 @@ impl TuiGuard {
                             .border_style(Style::default().fg(logs_border_color))
                             .title("󰍩 Logs"),
                     );
-                f.render_widget(logs, chunks[1]);
+                f.render_widget(logs, log_area);
 
                 let compact_footer = chunks[2].width < 78;
                 let footer_lines = if compact_footer {
@@ -447,12 +720,14 @@ impl TuiGuard {
                             Span::raw(" выбор  "),
                             Span::styled("Enter", Style::default().fg(Color::Yellow)),
                             Span::raw(" ок  "),
+                            Span::styled("m", Style::default().fg(Color::Yellow)),
+                            Span::raw(" матчи  "),
                             Span::styled("q", Style::default().fg(Color::Yellow)),
                             Span::raw(" выход"),
                         ]),
                     ]
                 } else {
-                    vec![Line::from(vec![
+                    let mut spans = vec![
                         footer_button_span("Настройки", controls.selected == FooterButton::Settings),
                         Span::raw("  "),
                         footer_button_span("Выйти", controls.selected == FooterButton::Exit),
@@ -461,9 +736,17 @@ impl TuiGuard {
                         Span::raw(" выбор  "),
                         Span::styled("Enter", Style::default().fg(Color::Yellow)),
                         Span::raw(" подтвердить  "),
+                        Span::styled("m", Style::default().fg(Color::Yellow)),
+                        Span::raw(" матчи  "),
                         Span::styled("q", Style::default().fg(Color::Yellow)),
                         Span::raw(" быстрый выход"),
-                    ])]
+                    ];
+                    if let Some((key_label, action)) = &controls.activation_hint {
+                        spans.push(Span::raw("  "));
+                        spans.push(Span::styled(key_label.clone(), Style::default().fg(Color::Yellow)));
+                        spans.push(Span::raw(format!(" {action}")));
+                    }
+                    vec![Line::from(spans)]
                 };
 
                 let footer = Paragraph::new(footer_lines)
@@ -532,15 +815,56 @@ impl TuiGuard {
                                     && controls.settings_field == SettingsField::UiMode,
                             ));
                         }
+                        SettingsTab::Microphone => {
+                            let rows = std::iter::once("По умолчанию (система)".to_string())
+                                .chain(draft.mic_devices.iter().map(|d| d.label()));
+                            for (row, label) in rows.enumerate() {
+                                settings_lines.push(settings_device_line(
+                                    &label,
+                                    row == draft.mic_cursor,
+                                    row == draft.mic_active,
+                                ));
+                            }
+                        }
+                        SettingsTab::Model => {
+                            if draft.models.is_empty() {
+                                settings_lines.push(Line::from(Span::styled(
+                                    "Моделей не найдено (speech.models_dir)",
+                                    Style::default().fg(Color::Gray),
+                                )));
+                            }
+                            for (row, model) in draft.models.iter().enumerate() {
+                                settings_lines.push(settings_device_line(
+                                    &model.label(),
+                                    row == draft.model_cursor,
+                                    Some(row) == draft.model_active,
+                                ));
+                            }
+                        }
                     }
 
                     settings_lines.push(Line::from(""));
-                    settings_lines.push(Line::from(vec![
-                        Span::styled("←/→", Style::default().fg(Color::Yellow)),
-                        Span::raw(" вкладка/переключить UI mode  "),
-                        Span::styled("↑↓", Style::default().fg(Color::Yellow)),
-                        Span::raw(" поле"),
-                    ]));
+                    if controls.settings_tab.is_list() {
+                        settings_lines.push(Line::from(vec![
+                            Span::styled("←/→", Style::default().fg(Color::Yellow)),
+                            Span::raw(" вкладка  "),
+                            Span::styled("↑↓", Style::default().fg(Color::Yellow)),
+                            Span::raw(if controls.settings_tab == SettingsTab::Model {
+                                " модель  "
+                            } else {
+                                " устройство  "
+                            }),
+                            Span::styled("Enter", Style::default().fg(Color::Yellow)),
+                            Span::raw(" переключить сразу"),
+                        ]));
+                    } else {
+                        settings_lines.push(Line::from(vec![
+                            Span::styled("←/→", Style::default().fg(Color::Yellow)),
+                            Span::raw(" вкладка/переключить UI mode  "),
+                            Span::styled("↑↓", Style::default().fg(Color::Yellow)),
+                            Span::raw(" поле"),
+                        ]));
+                    }
                     settings_lines.push(Line::from(vec![
                         Span::styled("Enter", Style::default().fg(Color::Yellow)),
                         Span::raw(" ред./ок  "),
@@ -616,6 +940,22 @@ fn settings_line(label: &str, value: &str, selected: bool, editing: bool) -> Lin
     Line::from(spans)
 }
 
+fn settings_device_line(label: &str, selected: bool, active: bool) -> Line<'static> {
+    let marker = if active { "(•) " } else { "( ) " };
+    Line::from(vec![
+        Span::styled(
+            if selected { "› " } else { "  " },
+            Style::default().fg(Color::Yellow),
+        ),
+        Span::styled(
+            format!("{marker}{label}"),
+            Style::default()
+                .fg(if selected { Color::Yellow } else if active { Color::Green } else { Color::White })
+                .add_modifier(if selected { Modifier::BOLD } else { Modifier::empty() }),
+        ),
+    ])
+}
+
 fn settings_tab_line(active: SettingsTab) -> Line<'static> {
     let tab = |label: &str, is_active: bool| {
         let text = if is_active {
@@ -634,6 +974,10 @@ fn settings_tab_line(active: SettingsTab) -> Line<'static> {
         tab("Connection", active == SettingsTab::Connection),
         Span::raw(" "),
         tab("App", active == SettingsTab::App),
+        Span::raw(" "),
+        tab("Microphone", active == SettingsTab::Microphone),
+        Span::raw(" "),
+        tab("Model", active == SettingsTab::Model),
     ])
 }
 
@@ -641,6 +985,8 @@ fn settings_fields_for_tab(tab: SettingsTab) -> &'static [SettingsField] {
     match tab {
         SettingsTab::Connection => &[SettingsField::Host, SettingsField::Port, SettingsField::Password],
         SettingsTab::App => &[SettingsField::PlayerName, SettingsField::UiMode],
+        SettingsTab::Microphone => &[SettingsField::Microphone],
+        SettingsTab::Model => &[SettingsField::Model],
     }
 }
 
@@ -798,6 +1144,58 @@ fn save_player_name_to_config(path: &Path, player_name: &str) -> Result<(), Stri
         .map_err(|e| format!("Не удалось сохранить config `{}`: {e}", path.display()))
 }
 
+/// `None` removes `microphone.device`, i.e. falls back to the system default input.
+fn save_microphone_device_to_config(path: &Path, device_name: Option<&str>) -> Result<(), String> {
+    let raw = fs::read_to_string(path)
+        .map_err(|e| format!("Не удалось прочитать config `{}`: {e}", path.display()))?;
+    let mut json: Value =
+        serde_json::from_str(&raw).map_err(|e| format!("Ошибка JSON в config: {e}"))?;
+
+    let root = json
+        .as_object_mut()
+        .ok_or_else(|| "config.json должен быть объектом".to_string())?;
+    let microphone = root
+        .entry("microphone")
+        .or_insert_with(|| Value::Object(serde_json::Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| "config.microphone должен быть объектом".to_string())?;
+    match device_name {
+        Some(name) => {
+            microphone.insert("device".to_string(), Value::String(name.to_string()));
+        }
+        None => {
+            microphone.remove("device");
+        }
+    }
+
+    let pretty = serde_json::to_string_pretty(&json)
+        .map_err(|e| format!("Не удалось сериализовать config: {e}"))?;
+    fs::write(path, pretty)
+        .map_err(|e| format!("Не удалось сохранить config `{}`: {e}", path.display()))
+}
+
+fn save_model_path_to_config(path: &Path, model_path: &str) -> Result<(), String> {
+    let raw = fs::read_to_string(path)
+        .map_err(|e| format!("Не удалось прочитать config `{}`: {e}", path.display()))?;
+    let mut json: Value =
+        serde_json::from_str(&raw).map_err(|e| format!("Ошибка JSON в config: {e}"))?;
+
+    let root = json
+        .as_object_mut()
+        .ok_or_else(|| "config.json должен быть объектом".to_string())?;
+    let speech = root
+        .entry("speech")
+        .or_insert_with(|| Value::Object(serde_json::Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| "config.speech должен быть объектом".to_string())?;
+    speech.insert("model_path".to_string(), Value::String(model_path.to_string()));
+
+    let pretty = serde_json::to_string_pretty(&json)
+        .map_err(|e| format!("Не удалось сериализовать config: {e}"))?;
+    fs::write(path, pretty)
+        .map_err(|e| format!("Не удалось сохранить config `{}`: {e}", path.display()))
+}
+
 pub(crate) fn restart_current_process() -> Result<(), String> {
     let exe = std::env::current_exe().map_err(|e| format!("Не удалось получить путь exe: {e}"))?;
     let args: Vec<_> = std::env::args_os().skip(1).collect();
@@ -907,6 +1305,9 @@ fn mode_option_span(label: &str, selected: bool) -> Span<'static> {
 
 impl Drop for TuiGuard {
     fn drop(&mut self) {
+        if self.key_release_events {
+            let _ = execute!(self.terminal.backend_mut(), PopKeyboardEnhancementFlags);
+        }
         let _ = disable_raw_mode();
         let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
     }
@@ -924,6 +1325,65 @@ struct Args {
 
     #[arg(long = "list-audio-devices")]
     list_audio_devices: bool,
+
+    /// WAV/raw s16le file to use instead of the microphone (`-` reads stdin).
+    #[arg(long = "input-file")]
+    input_file: Option<PathBuf>,
+
+    /// Playback speed for --input-file: 1.0 = real time, 0 = as fast as possible.
+    #[arg(long = "input-speed")]
+    input_speed: Option<f64>,
+
+    /// Record what the recognizer hears (same as `recording.enabled = true`).
+    #[arg(long)]
+    record: bool,
+
+    /// Send the microphone (or --input-file) to a remote BlockDeletee at HOST:PORT instead of running locally.
+    #[arg(long = "stream-to", value_name = "HOST:PORT", requires = "speaker")]
+    stream_to: Option<String>,
+
+    /// Speaker id announced with --stream-to.
+    #[arg(long)]
+    speaker: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+enum CliCommand {
+    /// Record a voice sample (microphone or --input-file) and store its voiceprint for speaker identification.
+    Enroll {
+        /// Speaker id from `speakers` the voice belongs to.
+        speaker: String,
+
+        /// How much audio to listen to.
+        #[arg(long, default_value_t = 20.0)]
+        seconds: f64,
+    },
+    /// Time block matching against the catalog: the old linear scans versus the indexes.
+    Bench {
+        /// Phrases to match, one per line; generated from the catalog aliases when omitted.
+        #[arg(long)]
+        phrases: Option<PathBuf>,
+
+        /// Runs of every phrase.
+        #[arg(long, default_value_t = 20)]
+        iterations: usize,
+    },
+    /// Show which blocks a phrase names and why, without audio or RCON; reads stdin when no phrase is given.
+    Match {
+        /// Phrase to match, e.g. "дай мне камня".
+        phrase: Vec<String>,
+
+        /// Overrides speech.fuzzy_threshold (0 turns fuzzy matching off).
+        #[arg(long)]
+        fuzzy_threshold: Option<f64>,
+
+        /// Overrides speech.phonetic_threshold (0 turns phonetic matching off).
+        #[arg(long)]
+        phonetic_threshold: Option<f64>,
+    },
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -954,11 +1414,27 @@ struct RawBlocksConfig {
     #[serde(default)]
     file: Option<String>,
     #[serde(default)]
+    locales: Vec<RawLocaleConfig>,
+    #[serde(default)]
     extra_aliases: HashMap<String, OneOrManyStrings>,
     #[serde(default)]
     shared_aliases: HashMap<String, OneOrManyStrings>,
 }
 
+#[derive(Debug, Clone, Deserialize, Default)]
+struct RawVadConfig {
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    threshold_db: Option<f64>,
+    #[serde(default)]
+    hangover_ms: Option<u32>,
+    #[serde(default)]
+    preroll_ms: Option<u32>,
+    #[serde(default)]
+    max_zcr: Option<f64>,
+}
+
 #[derive(Debug, Clone, Deserialize, Default)]
 struct RawMicrophoneConfig {
     #[serde(default)]
@@ -971,6 +1447,50 @@ struct RawMicrophoneConfig {
     blocksize: Option<u32>,
     #[serde(default)]
     device: Option<DeviceSelector>,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    input_file: Option<String>,
+    #[serde(default)]
+    input_format: Option<String>,
+    #[serde(default)]
+    input_samplerate: Option<u32>,
+    #[serde(default)]
+    input_channels: Option<u16>,
+    #[serde(default)]
+    input_speed: Option<f64>,
+    #[serde(default)]
+    vad: RawVadConfig,
+    #[serde(default)]
+    activation: Option<String>,
+    #[serde(default)]
+    activation_key: Option<String>,
+    #[serde(default)]
+    reconnect: RawReconnectConfig,
+    #[serde(default)]
+    channel: Option<RawChannelSelection>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum RawChannelSelection {
+    Index(i64),
+    Indices(Vec<i64>),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+struct RawReconnectConfig {
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    stall_ms: Option<u64>,
+    #[serde(default)]
+    silence_seconds: Option<u64>,
+    #[serde(default)]
+    initial_backoff_ms: Option<u64>,
+    #[serde(default)]
+    max_backoff_seconds: Option<u64>,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -978,12 +1498,16 @@ struct RawSpeechConfig {
     #[serde(default)]
     model_path: Option<String>,
     #[serde(default)]
+    models_dir: Option<String>,
+    #[serde(default)]
     sample_rate: Option<u32>,
     #[serde(default)]
     cooldown_seconds: Option<f64>,
     #[serde(default)]
     fuzzy_threshold: Option<f64>,
     #[serde(default)]
+    phonetic_threshold: Option<f64>,
+    #[serde(default)]
     use_grammar: Option<bool>,
     #[serde(default)]
     log_partials: Option<bool>,
@@ -991,6 +1515,48 @@ struct RawSpeechConfig {
     log_recognized: Option<bool>,
     #[serde(default)]
     min_phrase_chars: Option<usize>,
+    #[serde(default)]
+    min_word_confidence: Option<f32>,
+    #[serde(default)]
+    max_alternatives: Option<u16>,
+    #[serde(default)]
+    wake_phrases: Option<OneOrManyStrings>,
+    #[serde(default)]
+    wake_window_words: Option<usize>,
+    #[serde(default)]
+    negation_words: Option<OneOrManyStrings>,
+    #[serde(default)]
+    negation_window_words: Option<usize>,
+    #[serde(default)]
+    cancel_phrases: Option<OneOrManyStrings>,
+    #[serde(default)]
+    trigger_delay_ms: Option<u64>,
+    #[serde(default)]
+    speaker_model_path: Option<String>,
+    #[serde(default)]
+    voiceprints_file: Option<String>,
+    #[serde(default)]
+    speaker_max_distance: Option<f32>,
+    #[serde(default)]
+    engine: Option<String>,
+    #[serde(default)]
+    script: Option<String>,
+    #[serde(default)]
+    whisper: RawWhisperConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+struct RawWhisperConfig {
+    #[serde(default)]
+    binary: Option<String>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    max_segment_seconds: Option<f64>,
+    #[serde(default)]
+    silence_ms: Option<u64>,
 }
 
 #[derive(Debug, Clone, Deserialize, Default)]
@@ -1025,50 +1591,347 @@ struct RawAppConfig {
     speech: RawSpeechConfig,
     #[serde(default)]
     minecraft: RawMinecraftConfig,
+    #[serde(default)]
+    recording: RawRecordingConfig,
+    #[serde(default)]
+    network: RawNetworkConfig,
+    #[serde(default)]
+    speakers: BTreeMap<String, RawSpeakerConfig>,
 }
 
-#[derive(Debug, Clone)]
-struct UiConfig {
-    mode: Option<UiMode>,
+/// `"en_us"` (reads `en_us.json`) or `{ "locale": "en_us", "file": "lang/en_us.json", "stem": false }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum RawLocaleConfig {
+    Locale(String),
+    Detailed {
+        locale: String,
+        #[serde(default)]
+        file: Option<String>,
+        #[serde(default)]
+        stem: Option<bool>,
+    },
 }
 
-#[derive(Debug, Clone)]
-struct BlocksConfig {
-    file: String,
-    extra_aliases: HashMap<String, Vec<String>>,
-    shared_aliases: HashMap<String, Vec<String>>,
+/// `"bob": "BobCraft"` or `"bob": { "player": "BobCraft", "cooldown_seconds": 3 }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum RawSpeakerConfig {
+    Player(String),
+    Detailed {
+        player: String,
+        #[serde(default)]
+        cooldown_seconds: Option<f64>,
+    },
 }
 
-impl BlocksConfig {
-    fn custom_alias_phrases(&self) -> Vec<String> {
-        let mut out = Vec::new();
-        for aliases in self.extra_aliases.values() {
-            out.extend(aliases.clone());
-        }
-        out.extend(self.shared_aliases.keys().cloned());
-        out
-    }
+#[derive(Debug, Clone, Deserialize, Default)]
+struct RawRecordingConfig {
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    dir: Option<String>,
+    #[serde(default)]
+    segment_seconds: Option<u64>,
+    #[serde(default)]
+    max_segments: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+struct RawNetworkConfig {
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    bind: Option<String>,
+    #[serde(default)]
+    websocket: Option<bool>,
+    #[serde(default)]
+    max_connections: Option<usize>,
+    #[serde(default)]
+    token: Option<String>,
 }
 
 #[derive(Debug, Clone)]
-struct MicrophoneConfig {
+struct NetworkConfig {
     enabled: bool,
-    player_name: String,
-    samplerate: u32,
-    blocksize: u32,
-    device: Option<DeviceSelector>,
+    bind: String,
+    /// Also accept WebSocket clients on the same port.
+    websocket: bool,
+    max_connections: usize,
+    /// Shared secret every client must send in its handshake; empty = no check.
+    token: String,
 }
 
 #[derive(Debug, Clone)]
-struct SpeechConfig {
-    model_path: String,
-    sample_rate: u32,
+struct SpeakerConfig {
+    player_name: String,
     cooldown_seconds: f64,
-    fuzzy_threshold: f64,
-    use_grammar: bool,
+}
+
+#[derive(Debug, Clone)]
+struct RecordingConfig {
+    enabled: bool,
+    dir: String,
+    segment_seconds: u64,
+    /// Oldest segments are deleted beyond this; `events.jsonl` is kept whole.
+    max_segments: usize,
+}
+
+#[derive(Debug, Clone)]
+struct UiConfig {
+    mode: Option<UiMode>,
+}
+
+#[derive(Debug, Clone)]
+struct BlocksConfig {
+    /// Minecraft lang files merged into one catalog; the first one is the main language.
+    locales: Vec<LangFile>,
+    extra_aliases: HashMap<String, Vec<String>>,
+    shared_aliases: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+struct LangFile {
+    /// Minecraft locale code, e.g. `ru_ru`; picks the normalization rules for the file.
+    locale: String,
+    file: String,
+    /// Match inflected forms through `stemmer`; on by default where a stemmer exists.
+    stem: bool,
+}
+
+impl BlocksConfig {
+    fn primary_locale(&self) -> &str {
+        &self.locales[0].locale
+    }
+
+    /// Stemmers of the locales that have stemming on.
+    fn stemmers(&self) -> Vec<Stemmer> {
+        let mut stemmers: Vec<Stemmer> = self
+            .locales
+            .iter()
+            .filter(|l| l.stem)
+            .filter_map(|l| Stemmer::for_locale(&l.locale))
+            .collect();
+        stemmers.sort();
+        stemmers.dedup();
+        stemmers
+    }
+
+    fn custom_alias_phrases(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for aliases in self.extra_aliases.values() {
+            out.extend(aliases.clone());
+        }
+        out.extend(self.shared_aliases.keys().cloned());
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AudioSourceKind {
+    Device,
+    File,
+    Stdin,
+}
+
+impl AudioSourceKind {
+    fn from_config_str(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "device" | "microphone" | "mic" => Some(Self::Device),
+            "file" => Some(Self::File),
+            "stdin" => Some(Self::Stdin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RawAudioFormat {
+    Auto,
+    Wav,
+    S16le,
+}
+
+impl RawAudioFormat {
+    fn from_config_str(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" => Some(Self::Auto),
+            "wav" => Some(Self::Wav),
+            "s16le" | "raw" | "pcm" => Some(Self::S16le),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MicActivation {
+    Always,
+    PushToTalk,
+    Toggle,
+}
+
+impl MicActivation {
+    pub(crate) fn as_config_str(self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::PushToTalk => "push_to_talk",
+            Self::Toggle => "toggle",
+        }
+    }
+
+    fn from_config_str(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "always" => Some(Self::Always),
+            "push_to_talk" | "ptt" => Some(Self::PushToTalk),
+            "toggle" => Some(Self::Toggle),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `microphone.activation_key`: `space`, `tab`, `f1`..`f12` or a single character.
+fn parse_activation_key(value: &str) -> Option<KeyCode> {
+    let lower = value.trim().to_lowercase();
+    match lower.as_str() {
+        "space" => return Some(KeyCode::Char(' ')),
+        "tab" => return Some(KeyCode::Tab),
+        _ => {}
+    }
+    if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+        if (1..=12).contains(&n) {
+            return Some(KeyCode::F(n));
+        }
+    }
+    let mut chars = lower.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_alphanumeric() => Some(KeyCode::Char(c)),
+        _ => None,
+    }
+}
+
+fn activation_key_label(key: KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        KeyCode::Char(c) => c.to_uppercase().to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct VadConfig {
+    enabled: bool,
+    threshold_db: f64,
+    hangover_ms: u32,
+    preroll_ms: u32,
+    max_zcr: f64,
+}
+
+#[derive(Debug, Clone)]
+struct ReconnectConfig {
+    enabled: bool,
+    /// No audio callback for this long counts as a lost device.
+    stall_ms: u64,
+    /// All-zero input for this long counts as a lost device; 0 disables the check.
+    silence_seconds: u64,
+    initial_backoff_ms: u64,
+    max_backoff_seconds: u64,
+}
+
+#[derive(Debug, Clone)]
+struct MicrophoneConfig {
+    enabled: bool,
+    player_name: String,
+    samplerate: Option<u32>,
+    blocksize: u32,
+    device: Option<DeviceSelector>,
+    source: AudioSourceKind,
+    input_file: Option<String>,
+    input_format: RawAudioFormat,
+    input_samplerate: Option<u32>,
+    input_channels: u16,
+    input_speed: f64,
+    vad: VadConfig,
+    activation: MicActivation,
+    activation_key: KeyCode,
+    reconnect: ReconnectConfig,
+    channel: ChannelSelection,
+}
+
+#[derive(Debug, Clone)]
+struct SpeechConfig {
+    engine: SpeechEngineKind,
+    model_path: String,
+    /// `model_path` was picked from `models_dir` because the config sets none.
+    model_auto: bool,
+    /// Folder scanned for Vosk models in the settings.
+    models_dir: String,
+    /// Hypotheses for `engine = scripted`.
+    script: Option<String>,
+    #[cfg_attr(not(feature = "whisper"), allow(dead_code))]
+    whisper: WhisperConfig,
+    sample_rate: u32,
+    cooldown_seconds: f64,
+    fuzzy_threshold: f64,
+    /// Similarity of phonetic keys for the sound-alike match path; 0 = off.
+    phonetic_threshold: f64,
+    use_grammar: bool,
     log_partials: bool,
     log_recognized: bool,
     min_phrase_chars: usize,
+    /// Words scored below this do not count towards block matches; 0 = off.
+    min_word_confidence: f32,
+    /// N-best list size asked from Vosk for final results; 0 = only the best one.
+    max_alternatives: u16,
+    /// When set, blocks only count right after one of these phrases.
+    wake_phrases: Vec<String>,
+    wake_window_words: usize,
+    /// A block named within `negation_window_words` words after one of these is not deleted.
+    negation_words: Vec<String>,
+    negation_window_words: usize,
+    /// Drop the triggers of the speaker that are still waiting out `trigger_delay_ms`.
+    cancel_phrases: Vec<String>,
+    trigger_delay_ms: u64,
+    /// Vosk x-vector model; `None` turns speaker identification off.
+    speaker_model_path: Option<String>,
+    /// Enrolled voices, written by `blockdeletee enroll`.
+    voiceprints_file: String,
+    /// Cosine distance up to which a voice counts as the closest enrolled speaker.
+    speaker_max_distance: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpeechEngineKind {
+    Vosk,
+    /// Replays `speech.script`; no model needed.
+    Scripted,
+    /// whisper.cpp command line tool, only with `--features whisper`.
+    Whisper,
+}
+
+impl SpeechEngineKind {
+    fn from_config_str(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "vosk" => Some(Self::Vosk),
+            "scripted" | "script" => Some(Self::Scripted),
+            "whisper" => Some(Self::Whisper),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "whisper"), allow(dead_code))]
+struct WhisperConfig {
+    binary: String,
+    /// ggml model file.
+    model: String,
+    language: String,
+    /// Longest piece of speech sent to whisper at once.
+    max_segment_seconds: f64,
+    /// Trailing quiet that ends a segment.
+    silence_ms: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -1087,6 +1950,10 @@ pub(crate) struct AppConfig {
     microphone: MicrophoneConfig,
     speech: SpeechConfig,
     minecraft: MinecraftConfig,
+    recording: RecordingConfig,
+    network: NetworkConfig,
+    /// Speaker id -> player; `mic` falls back to `microphone.player_name`.
+    speakers: BTreeMap<String, SpeakerConfig>,
 }
 
 impl AppConfig {
@@ -1096,8 +1963,41 @@ impl AppConfig {
         let parsed: RawAppConfig =
             serde_json::from_str(&raw).map_err(|e| format!("Ошибка JSON в config: {e}"))?;
 
+        let config_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut locales: Vec<LangFile> = Vec::new();
+        for raw in parsed.blocks.locales {
+            let (locale, file, stem) = match raw {
+                RawLocaleConfig::Locale(locale) => (locale, None, None),
+                RawLocaleConfig::Detailed { locale, file, stem } => (locale, file, stem),
+            };
+            let locale = locale.trim().to_lowercase();
+            if locale.is_empty() || !locale.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+                return Err(format!("blocks.locales: неверная локаль `{locale}` (пример: en_us)"));
+            }
+            if locales.iter().any(|l| l.locale == locale) {
+                return Err(format!("blocks.locales: локаль `{locale}` указана дважды"));
+            }
+            let has_stemmer = Stemmer::for_locale(&locale).is_some();
+            if stem == Some(true) && !has_stemmer {
+                return Err(format!("blocks.locales: для `{locale}` нет стемминга (есть только для ru)"));
+            }
+            let file = nonempty_or(file, &format!("{locale}.json"));
+            locales.push(LangFile {
+                stem: stem.unwrap_or(has_stemmer),
+                locale,
+                file,
+            });
+        }
+        if locales.is_empty() {
+            locales.push(LangFile {
+                locale: "ru_ru".to_string(),
+                file: nonempty_or(parsed.blocks.file, "blocks.json"),
+                stem: true,
+            });
+        }
         let blocks = BlocksConfig {
-            file: nonempty_or(parsed.blocks.file, "blocks.json"),
+            locales,
             extra_aliases: clean_alias_map(parsed.blocks.extra_aliases),
             shared_aliases: clean_alias_map(parsed.blocks.shared_aliases),
         };
@@ -1113,28 +2013,161 @@ impl AppConfig {
         let microphone = MicrophoneConfig {
             enabled: parsed.microphone.enabled.unwrap_or(true),
             player_name: parsed.microphone.player_name.unwrap_or_default().trim().to_string(),
-            samplerate: parsed.microphone.samplerate.unwrap_or(48_000),
+            samplerate: parsed.microphone.samplerate.filter(|rate| *rate > 0),
             blocksize: parsed.microphone.blocksize.unwrap_or(9_600),
             device: parsed.microphone.device.and_then(|d| match d {
                 DeviceSelector::Name(s) if s.trim().is_empty() => None,
                 other => Some(other),
             }),
+            source: match parsed.microphone.source.as_deref() {
+                None => AudioSourceKind::Device,
+                Some(value) => AudioSourceKind::from_config_str(value).ok_or_else(|| {
+                    format!("microphone.source `{value}` неизвестен (device, file, stdin)")
+                })?,
+            },
+            input_file: parsed
+                .microphone
+                .input_file
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            input_format: match parsed.microphone.input_format.as_deref() {
+                None => RawAudioFormat::Auto,
+                Some(value) => RawAudioFormat::from_config_str(value).ok_or_else(|| {
+                    format!("microphone.input_format `{value}` неизвестен (auto, wav, s16le)")
+                })?,
+            },
+            input_samplerate: parsed.microphone.input_samplerate,
+            input_channels: parsed.microphone.input_channels.unwrap_or(1).max(1),
+            input_speed: parsed.microphone.input_speed.unwrap_or(1.0).max(0.0),
+            vad: VadConfig {
+                enabled: parsed.microphone.vad.enabled.unwrap_or(false),
+                threshold_db: parsed.microphone.vad.threshold_db.unwrap_or(-45.0).clamp(-90.0, 0.0),
+                hangover_ms: parsed.microphone.vad.hangover_ms.unwrap_or(500),
+                preroll_ms: parsed.microphone.vad.preroll_ms.unwrap_or(300),
+                max_zcr: parsed.microphone.vad.max_zcr.unwrap_or(0.35).clamp(0.0, 1.0),
+            },
+            activation: match parsed.microphone.activation.as_deref() {
+                None => MicActivation::Always,
+                Some(value) => MicActivation::from_config_str(value).ok_or_else(|| {
+                    format!("microphone.activation `{value}` неизвестен (always, push_to_talk, toggle)")
+                })?,
+            },
+            activation_key: match parsed.microphone.activation_key.as_deref() {
+                None => KeyCode::Char(' '),
+                Some(value) => parse_activation_key(value).ok_or_else(|| {
+                    format!("microphone.activation_key `{value}` не распознан (space, tab, f1..f12 или один символ)")
+                })?,
+            },
+            reconnect: ReconnectConfig {
+                enabled: parsed.microphone.reconnect.enabled.unwrap_or(true),
+                stall_ms: parsed.microphone.reconnect.stall_ms.unwrap_or(3_000).max(1_000),
+                silence_seconds: parsed.microphone.reconnect.silence_seconds.unwrap_or(0),
+                initial_backoff_ms: parsed.microphone.reconnect.initial_backoff_ms.unwrap_or(500).max(100),
+                max_backoff_seconds: parsed.microphone.reconnect.max_backoff_seconds.unwrap_or(30).max(1),
+            },
+            channel: match parsed.microphone.channel {
+                None => ChannelSelection::Mix,
+                Some(raw) => {
+                    let parsed_channel = match &raw {
+                        RawChannelSelection::Name(value) => ChannelSelection::from_config_str(value),
+                        RawChannelSelection::Index(index) => (*index >= 1)
+                            .then(|| ChannelSelection::Channels(vec![*index as usize - 1])),
+                        RawChannelSelection::Indices(indices) => indices
+                            .iter()
+                            .map(|i| (*i >= 1).then(|| *i as usize - 1))
+                            .collect::<Option<Vec<_>>>()
+                            .and_then(ChannelSelection::from_indices),
+                    };
+                    parsed_channel.ok_or_else(|| {
+                        format!("microphone.channel `{raw:?}` неверен: `mix`, номер канала (с 1) или список номеров")
+                    })?
+                }
+            },
         };
 
         let mut fuzzy_threshold = parsed.speech.fuzzy_threshold.unwrap_or(0.70);
         if fuzzy_threshold > 0.0 {
             fuzzy_threshold = fuzzy_threshold.clamp(0.5, 0.99);
         }
+        let mut phonetic_threshold = parsed.speech.phonetic_threshold.unwrap_or(0.9);
+        if phonetic_threshold > 0.0 {
+            phonetic_threshold = phonetic_threshold.clamp(0.5, 1.0);
+        }
+
+        // Without an explicit model, take the one in models_dir that speaks the main locale.
+        let models_dir = nonempty_or(parsed.speech.models_dir, "models");
+        let model_path = parsed
+            .speech
+            .model_path
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty());
+        let model_auto = model_path.is_none();
+        let model_path = model_path.unwrap_or_else(|| {
+            let models = discover_models(&resolve_path(config_dir, &models_dir)).unwrap_or_default();
+            let name = model_for_locale(&models, blocks.primary_locale())
+                .map_or(DEFAULT_MODEL_NAME, |model| model.name.as_str());
+            model_config_path(&models_dir, name)
+        });
+
+        // Without cancel phrases there is nothing to wait for, so triggers go out at once.
+        let cancel_phrases = clean_phrases(parsed.speech.cancel_phrases.map(OneOrManyStrings::into_vec).unwrap_or_default());
+        let trigger_delay_ms = parsed
+            .speech
+            .trigger_delay_ms
+            .unwrap_or(if cancel_phrases.is_empty() { 0 } else { 400 })
+            .min(MAX_TRIGGER_DELAY_MS);
 
         let speech = SpeechConfig {
-            model_path: nonempty_or(parsed.speech.model_path, "models/vosk-model-small-ru-0.22"),
+            engine: match parsed.speech.engine.as_deref() {
+                None => SpeechEngineKind::Vosk,
+                Some(value) => SpeechEngineKind::from_config_str(value).ok_or_else(|| {
+                    format!("speech.engine `{value}` неизвестен (vosk, scripted, whisper)")
+                })?,
+            },
+            model_path,
+            model_auto,
+            models_dir,
+            script: parsed
+                .speech
+                .script
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            whisper: WhisperConfig {
+                binary: nonempty_or(parsed.speech.whisper.binary, "whisper-cli"),
+                model: nonempty_or(parsed.speech.whisper.model, "models/ggml-base.bin"),
+                language: nonempty_or(parsed.speech.whisper.language, base_language(blocks.primary_locale())),
+                max_segment_seconds: parsed.speech.whisper.max_segment_seconds.unwrap_or(8.0).clamp(1.0, 30.0),
+                silence_ms: parsed.speech.whisper.silence_ms.unwrap_or(600).max(100),
+            },
             sample_rate: parsed.speech.sample_rate.unwrap_or(48_000),
             cooldown_seconds: parsed.speech.cooldown_seconds.unwrap_or(2.0),
             fuzzy_threshold,
+            phonetic_threshold,
             use_grammar: parsed.speech.use_grammar.unwrap_or(false),
             log_partials: parsed.speech.log_partials.unwrap_or(false),
             log_recognized: parsed.speech.log_recognized.unwrap_or(false),
             min_phrase_chars: parsed.speech.min_phrase_chars.unwrap_or(2),
+            min_word_confidence: parsed.speech.min_word_confidence.unwrap_or(0.0).clamp(0.0, 1.0),
+            max_alternatives: parsed.speech.max_alternatives.unwrap_or(0).min(MAX_ALTERNATIVES),
+            wake_phrases: clean_phrases(parsed.speech.wake_phrases.map(OneOrManyStrings::into_vec).unwrap_or_default()),
+            wake_window_words: parsed.speech.wake_window_words.unwrap_or(3).max(1),
+            negation_words: clean_phrases(
+                parsed
+                    .speech
+                    .negation_words
+                    .map(OneOrManyStrings::into_vec)
+                    .unwrap_or_else(|| vec!["не".to_string(), "кроме".to_string()]),
+            ),
+            negation_window_words: parsed.speech.negation_window_words.unwrap_or(2).max(1),
+            cancel_phrases,
+            trigger_delay_ms,
+            speaker_model_path: parsed
+                .speech
+                .speaker_model_path
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+            voiceprints_file: nonempty_or(parsed.speech.voiceprints_file, "voiceprints.json"),
+            speaker_max_distance: parsed.speech.speaker_max_distance.unwrap_or(0.5).clamp(0.05, 2.0),
         };
 
         let mut limits = HashMap::from([
@@ -1162,14 +2195,69 @@ impl AppConfig {
             dimension_y_limits: limits,
         };
 
+        let recording = RecordingConfig {
+            enabled: parsed.recording.enabled.unwrap_or(false),
+            dir: nonempty_or(parsed.recording.dir, "recordings"),
+            segment_seconds: parsed.recording.segment_seconds.unwrap_or(600).max(10),
+            max_segments: parsed.recording.max_segments.unwrap_or(12).max(1),
+        };
+
+        let network = NetworkConfig {
+            enabled: parsed.network.enabled.unwrap_or(false),
+            bind: nonempty_or(parsed.network.bind, "127.0.0.1:7700"),
+            websocket: parsed.network.websocket.unwrap_or(true),
+            max_connections: parsed.network.max_connections.unwrap_or(4).max(1),
+            token: parsed.network.token.unwrap_or_default().trim().to_string(),
+        };
+
+        let mut speakers = BTreeMap::new();
+        for (id, raw) in parsed.speakers {
+            let id = id.trim().to_string();
+            let (player, cooldown) = match raw {
+                RawSpeakerConfig::Player(player) => (player, None),
+                RawSpeakerConfig::Detailed {
+                    player,
+                    cooldown_seconds,
+                } => (player, cooldown_seconds),
+            };
+            let player = player.trim().to_string();
+            if id.is_empty() || player.is_empty() {
+                return Err(format!("speakers: пустой speaker id или player (`{id}` -> `{player}`)"));
+            }
+            speakers.insert(
+                id,
+                SpeakerConfig {
+                    player_name: player,
+                    cooldown_seconds: cooldown.unwrap_or(speech.cooldown_seconds).max(0.0),
+                },
+            );
+        }
+        if !microphone.player_name.is_empty() {
+            speakers
+                .entry(MIC_SPEAKER_ID.to_string())
+                .or_insert_with(|| SpeakerConfig {
+                    player_name: microphone.player_name.clone(),
+                    cooldown_seconds: speech.cooldown_seconds,
+                });
+        }
+
         Ok(Self {
             ui,
             blocks,
             microphone,
             speech,
             minecraft,
+            recording,
+            network,
+            speakers,
         })
     }
+
+    /// Player of the local microphone: `speakers.mic`, which `load` fills from
+    /// `microphone.player_name` when it is not set.
+    fn mic_player_name(&self) -> Option<String> {
+        self.speakers.get(MIC_SPEAKER_ID).map(|speaker| speaker.player_name.clone())
+    }
 }
 
 fn nonempty_or(value: Option<String>, default: &str) -> String {
@@ -1194,6 +2282,14 @@ impl IfEmpty for String {
     }
 }
 
+fn clean_phrases(input: Vec<String>) -> Vec<String> {
+    input
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
 fn clean_alias_map(input: HashMap<String, OneOrManyStrings>) -> HashMap<String, Vec<String>> {
     let mut out = HashMap::new();
     for (key, values) in input {
@@ -1214,8 +2310,71 @@ fn clean_alias_map(input: HashMap<String, OneOrManyStrings>) -> HashMap<String,
     out
 }
 
-fn normalize_text(text: &str) -> String {
-    let lowered = text.to_lowercase().replace('ё', "е").replace('э', "е");
+/// Spelling folds of one language, applied to lang file names and to recognized text so
+/// both spell a word the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TextRules {
+    /// ё and э read as е.
+    Russian,
+    /// ґ reads as г, apostrophes inside words (м'ята) are dropped.
+    Ukrainian,
+    /// Apostrophes inside words (jack o'lantern) are dropped.
+    English,
+    /// Lowercase only.
+    Plain,
+}
+
+const APOSTROPHES: [char; 3] = ['\'', '’', 'ʼ'];
+
+impl TextRules {
+    fn for_locale(locale: &str) -> Self {
+        match base_language(locale) {
+            "ru" => Self::Russian,
+            "uk" => Self::Ukrainian,
+            "en" => Self::English,
+            _ => Self::Plain,
+        }
+    }
+
+    fn fold(self, lowered: String) -> String {
+        match self {
+            Self::Russian => lowered.replace(['ё', 'э'], "е"),
+            Self::Ukrainian => lowered.replace('ґ', "г").replace(APOSTROPHES, ""),
+            Self::English => lowered.replace(APOSTROPHES, ""),
+            Self::Plain => lowered,
+        }
+    }
+}
+
+/// More words first, then longer, then alphabetical: the order blocks are reported in.
+fn alias_order(a: &str, b: &str) -> std::cmp::Ordering {
+    let a_wc = a.split_whitespace().count();
+    let b_wc = b.split_whitespace().count();
+    b_wc.cmp(&a_wc)
+        .then_with(|| b.len().cmp(&a.len()))
+        .then_with(|| a.cmp(b))
+}
+
+fn first_char(text: &str) -> char {
+    text.chars().next().unwrap_or(' ')
+}
+
+/// Trigram indexes of `texts` by word count and first character: fuzzy matches never cross
+/// either.
+fn index_by_start<'a>(texts: impl Iterator<Item = &'a String>) -> HashMap<(usize, char), TrigramIndex> {
+    let mut groups: HashMap<(usize, char), Vec<String>> = HashMap::new();
+    for text in texts {
+        let start = (text.split_whitespace().count(), first_char(text));
+        groups.entry(start).or_default().push(text.clone());
+    }
+    groups
+        .into_iter()
+        .map(|(start, texts)| (start, TrigramIndex::new(texts)))
+        .collect()
+}
+
+fn normalize_text(text: &str, rules: &[TextRules]) -> String {
+    let lowered = rules.iter().fold(text.to_lowercase(), |text, rule| rule.fold(text));
     let mut buf = String::with_capacity(lowered.len());
     for ch in lowered.chars() {
         if ch.is_alphanumeric() || ch == '_' || ch.is_whitespace() {
@@ -1257,55 +2416,152 @@ fn normalize_block_target(raw_target: &str) -> String {
     }
 }
 
+/// How an alias was found in the text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MatchMethod {
+    Exact,
+    Stem,
+    Fuzzy,
+    Phonetic,
+}
+
+impl MatchMethod {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Exact => "exact",
+            Self::Stem => "stem",
+            Self::Fuzzy => "fuzzy",
+            Self::Phonetic => "phonetic",
+        }
+    }
+}
+
+/// Where and how an alias was found, before it is expanded to its blocks.
+#[derive(Debug, Clone)]
+struct AliasMatch {
+    ngram: String,
+    method: MatchMethod,
+    score: f64,
+}
+
+impl AliasMatch {
+    fn new(ngram: String, method: MatchMethod, score: f64) -> Self {
+        Self { ngram, method, score }
+    }
+
+    /// Keeps the higher-scoring explanation when several n-grams match the same alias.
+    fn keep_best(found: &mut HashMap<String, Self>, alias: &str, candidate: Self) {
+        if found.get(alias).is_none_or(|old| candidate.score > old.score) {
+            found.insert(alias.to_string(), candidate);
+        }
+    }
+}
+
+/// One block named in a phrase and why: which alias matched which words, and how.
+#[derive(Debug, Clone, PartialEq)]
+struct BlockMatch {
+    block_id: String,
+    alias: String,
+    /// Normalized words of the phrase the alias matched; glued words lose their spaces.
+    ngram: String,
+    method: MatchMethod,
+    /// 1 for exact and stem matches, the similarity of spelling or sound otherwise.
+    score: f64,
+}
+
+impl BlockMatch {
+    /// `«алмазную руду» ~ «алмазная руда» (stem 1.00)`
+    fn reason(&self) -> String {
+        format!(
+            "«{}» ~ «{}» ({} {:.2})",
+            self.ngram,
+            self.alias,
+            self.method.as_str(),
+            self.score
+        )
+    }
+}
+
+fn block_ids(matches: &[BlockMatch]) -> Vec<String> {
+    matches.iter().map(|m| m.block_id.clone()).collect()
+}
+
 #[derive(Debug, Clone)]
 struct BlockCatalog {
+    /// Rules of every loaded locale; recognized text is folded with all of them because the
+    /// catalog cannot know which language a phrase is in.
+    text_rules: Vec<TextRules>,
     alias_to_blocks: HashMap<String, Vec<String>>,
     aliases_by_word_count: HashMap<usize, Vec<String>>,
     sorted_aliases: Vec<String>,
+    /// Aliases of at least `MIN_FUZZY_ALIAS_CHARS` letters by word count and first letter.
+    fuzzy_index: HashMap<(usize, char), TrigramIndex>,
+    /// `phonetic_key` -> aliases with that key, for keys of `MIN_PHONETIC_KEY` and more.
+    phonetic_aliases: HashMap<String, Vec<String>>,
+    /// Keys of `MIN_FUZZY_PHONETIC_KEY` and more by word count and first sound.
+    phonetic_index: HashMap<(usize, char), TrigramIndex>,
+    /// `speech.phonetic_threshold`; 0 turns the phonetic path off.
+    phonetic_threshold: f64,
+    /// Stemmers of `blocks.locales` with `stem` on; empty turns the stem path off.
+    stemmers: Vec<Stemmer>,
+    /// Stemmed alias -> the aliases it comes from.
+    stemmed_aliases: HashMap<String, Vec<String>>,
 }
 
 impl BlockCatalog {
+    /// `lang_files` are `(locale, path)` pairs; each file is normalized with its own locale's
+    /// rules, config aliases with the rules of all of them.
     fn load(
-        blocks_file: &Path,
+        lang_files: &[(String, PathBuf)],
         extra_aliases: &HashMap<String, Vec<String>>,
         shared_aliases: &HashMap<String, Vec<String>>,
     ) -> Result<Self, String> {
-        let raw = fs::read_to_string(blocks_file).map_err(|e| {
-            format!(
-                "Не удалось прочитать blocks.json `{}`: {e}",
-                blocks_file.display()
-            )
-        })?;
-        let parsed: Value =
-            serde_json::from_str(&raw).map_err(|e| format!("Ошибка JSON в blocks.json: {e}"))?;
-        let object = parsed
-            .as_object()
-            .ok_or_else(|| "blocks.json должен быть объектом JSON".to_string())?;
+        let mut text_rules: Vec<TextRules> = lang_files
+            .iter()
+            .map(|(locale, _)| TextRules::for_locale(locale))
+            .collect();
+        text_rules.sort();
+        text_rules.dedup();
 
         let mut mapping: HashMap<String, HashSet<String>> = HashMap::new();
         let mut known_block_ids: HashSet<String> = HashSet::new();
 
-        for (key, localized_name) in object {
-            let Some(block_id) = block_id_from_language_key(key) else {
-                continue;
-            };
-            known_block_ids.insert(block_id.clone());
+        for (locale, lang_file) in lang_files {
+            let raw = fs::read_to_string(lang_file).map_err(|e| {
+                format!(
+                    "Не удалось прочитать lang file {locale} `{}`: {e}",
+                    lang_file.display()
+                )
+            })?;
+            let parsed: Value = serde_json::from_str(&raw)
+                .map_err(|e| format!("Ошибка JSON в `{}`: {e}", lang_file.display()))?;
+            let object = parsed
+                .as_object()
+                .ok_or_else(|| format!("`{}` должен быть объектом JSON", lang_file.display()))?;
+            let rules = [TextRules::for_locale(locale)];
+
+            for (key, localized_name) in object {
+                let Some(block_id) = block_id_from_language_key(key) else {
+                    continue;
+                };
+                known_block_ids.insert(block_id.clone());
 
-            let aliases = match localized_name {
-                Value::String(s) => vec![s.clone()],
-                Value::Array(arr) => arr
-                    .iter()
-                    .map(|v| match v {
-                        Value::String(s) => s.clone(),
-                        other => other.to_string(),
-                    })
-                    .collect::<Vec<_>>(),
-                other => vec![other.to_string()],
-            };
-            for alias in aliases {
-                let n = normalize_text(&alias);
-                if !n.is_empty() {
-                    mapping.entry(n).or_default().insert(block_id.clone());
+                let aliases = match localized_name {
+                    Value::String(s) => vec![s.clone()],
+                    Value::Array(arr) => arr
+                        .iter()
+                        .map(|v| match v {
+                            Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        })
+                        .collect::<Vec<_>>(),
+                    other => vec![other.to_string()],
+                };
+                for alias in aliases {
+                    let n = normalize_text(&alias, &rules);
+                    if !n.is_empty() {
+                        mapping.entry(n).or_default().insert(block_id.clone());
+                    }
                 }
             }
         }
@@ -1313,7 +2569,7 @@ impl BlockCatalog {
         for (block_id, aliases) in extra_aliases {
             let normalized_block_id = normalize_block_target(block_id);
             for alias in aliases {
-                let n = normalize_text(alias);
+                let n = normalize_text(alias, &text_rules);
                 if !n.is_empty() {
                     mapping
                         .entry(n)
@@ -1324,7 +2580,7 @@ impl BlockCatalog {
         }
 
         for (alias, targets) in shared_aliases {
-            let normalized_alias = normalize_text(alias);
+            let normalized_alias = normalize_text(alias, &text_rules);
             if normalized_alias.is_empty() {
                 continue;
             }
@@ -1370,31 +2626,92 @@ impl BlockCatalog {
             let wc = alias.split_whitespace().count();
             aliases_by_word_count.entry(wc).or_default().push(alias.clone());
         }
-        sorted_aliases.sort_by(|a, b| {
-            let a_wc = a.split_whitespace().count();
-            let b_wc = b.split_whitespace().count();
-            b_wc.cmp(&a_wc)
-                .then_with(|| b.len().cmp(&a.len()))
-                .then_with(|| a.cmp(b))
-        });
+        sorted_aliases.sort_by(|a, b| alias_order(a, b));
+
+        let fuzzy_index = index_by_start(
+            sorted_aliases
+                .iter()
+                .filter(|alias| alias.chars().count() >= MIN_FUZZY_ALIAS_CHARS),
+        );
+
+        let mut phonetic_aliases: HashMap<String, Vec<String>> = HashMap::new();
+        for alias in &sorted_aliases {
+            let key = phonetic_key(alias);
+            if key.chars().count() >= MIN_PHONETIC_KEY {
+                phonetic_aliases.entry(key).or_default().push(alias.clone());
+            }
+        }
+        let phonetic_index = index_by_start(
+            phonetic_aliases
+                .keys()
+                .filter(|key| key.chars().count() >= MIN_FUZZY_PHONETIC_KEY),
+        );
 
         Ok(Self {
+            text_rules,
             alias_to_blocks,
             aliases_by_word_count,
             sorted_aliases,
+            fuzzy_index,
+            phonetic_aliases,
+            phonetic_index,
+            phonetic_threshold: 0.0,
+            stemmers: Vec::new(),
+            stemmed_aliases: HashMap::new(),
         })
     }
 
-    fn exact_match_aliases(&self, normalized_text: &str) -> HashSet<String> {
-        let padded = format!(" {normalized_text} ");
-        let mut exact = HashSet::new();
-        for alias in &self.sorted_aliases {
-            let token = format!(" {alias} ");
-            if padded.contains(&token) {
-                exact.insert(alias.clone());
+    /// Loads `blocks.locales` and applies the matching options of `speech`, as the
+    /// controller does.
+    fn from_config(config: &AppConfig, config_dir: &Path) -> Result<Self, String> {
+        let lang_files: Vec<(String, PathBuf)> = config
+            .blocks
+            .locales
+            .iter()
+            .map(|l| (l.locale.clone(), resolve_path(config_dir, &l.file)))
+            .collect();
+        Ok(Self::load(
+            &lang_files,
+            &config.blocks.extra_aliases,
+            &config.blocks.shared_aliases,
+        )?
+        .with_phonetic_threshold(config.speech.phonetic_threshold)
+        .with_stemmers(config.blocks.stemmers()))
+    }
+
+    fn with_phonetic_threshold(mut self, threshold: f64) -> Self {
+        self.phonetic_threshold = threshold;
+        self
+    }
+
+    /// Indexes every alias by its stem, so inflected forms in recognized text match too.
+    fn with_stemmers(mut self, stemmers: Vec<Stemmer>) -> Self {
+        self.stemmed_aliases.clear();
+        if !stemmers.is_empty() {
+            for alias in &self.sorted_aliases {
+                self.stemmed_aliases
+                    .entry(stem_text(alias, &stemmers))
+                    .or_default()
+                    .push(alias.clone());
             }
         }
-        exact
+        self.stemmers = stemmers;
+        self
+    }
+
+    /// Lowercased text with the folds of every loaded locale, as aliases are stored.
+    fn normalize(&self, text: &str) -> String {
+        normalize_text(text, &self.text_rules)
+    }
+
+    /// Aliases equal to an n-gram of the text.
+    fn exact_match_aliases(&self, normalized_text: &str) -> HashMap<String, AliasMatch> {
+        let words: Vec<&str> = normalized_text.split_whitespace().collect();
+        (1..=self.max_alias_words().min(words.len()))
+            .flat_map(|n| Self::make_ngrams(&words, n))
+            .filter(|ngram| self.alias_to_blocks.contains_key(ngram))
+            .map(|ngram| (ngram.clone(), AliasMatch::new(ngram, MatchMethod::Exact, 1.0)))
+            .collect()
     }
 
     fn make_ngrams(words: &[&str], n: usize) -> Vec<String> {
@@ -1406,106 +2723,180 @@ impl BlockCatalog {
             .collect()
     }
 
+    /// N-grams compared with aliases of `word_count` words. One-word aliases are also
+    /// compared with two or three neighbouring words glued together («алмаз ная»).
+    fn ngram_candidates(words: &[&str], word_count: usize) -> Vec<String> {
+        let mut candidates = Self::make_ngrams(words, word_count);
+        if word_count == 1 {
+            for n in 2..=3.min(words.len()) {
+                candidates.extend(Self::make_ngrams(words, n).into_iter().map(|g| g.replace(' ', "")));
+            }
+        }
+        candidates
+    }
+
     fn is_plausible_length(alias: &str, candidate: &str) -> bool {
         let diff = alias.len().abs_diff(candidate.len());
         let max_diff = 2usize.max((alias.len() as f64 * 0.35) as usize);
         diff <= max_diff
     }
 
+    /// Spelling-alike aliases of at least `MIN_FUZZY_ALIAS_CHARS` letters that start with
+    /// the same letter as the n-gram.
     fn fuzzy_match_aliases(
         &self,
         normalized_text: &str,
         threshold: f64,
-        already_matched: &HashSet<String>,
-    ) -> HashSet<String> {
+        already_matched: &HashMap<String, AliasMatch>,
+    ) -> HashMap<String, AliasMatch> {
         let words: Vec<&str> = normalized_text.split_whitespace().collect();
-        if words.is_empty() {
-            return HashSet::new();
-        }
-
-        let max_alias_words = self.aliases_by_word_count.keys().copied().max().unwrap_or(1);
-        let max_n = max_alias_words.min(words.len());
-        let mut ngram_cache: HashMap<usize, Vec<String>> = HashMap::new();
-        for n in 1..=max_n {
-            ngram_cache.insert(n, Self::make_ngrams(&words, n));
-        }
-
-        let mut collapsed_cache: HashMap<usize, Vec<String>> = HashMap::new();
-        for n in 2..=3.min(words.len()) {
-            let mut items = Vec::new();
-            for i in 0..=words.len() - n {
-                items.push(words[i..i + n].join(""));
+        let mut fuzzy = HashMap::new();
+        for word_count in 1..=self.max_alias_words().min(words.len()) {
+            for candidate in Self::ngram_candidates(&words, word_count) {
+                let Some(index) = self.fuzzy_index.get(&(word_count, first_char(&candidate))) else {
+                    continue;
+                };
+                for alias in index.candidates(&candidate, threshold) {
+                    if already_matched.contains_key(alias) || !Self::is_plausible_length(alias, &candidate) {
+                        continue;
+                    }
+                    let score = normalized_levenshtein(alias, &candidate);
+                    if score >= threshold {
+                        AliasMatch::keep_best(&mut fuzzy, alias, AliasMatch::new(candidate.clone(), MatchMethod::Fuzzy, score));
+                    }
+                }
             }
-            collapsed_cache.insert(n, items);
         }
+        fuzzy
+    }
 
-        let mut fuzzy = HashSet::new();
-        for (word_count, aliases) in &self.aliases_by_word_count {
-            let mut candidates = ngram_cache.get(word_count).cloned().unwrap_or_default();
-            if *word_count == 1 {
-                if let Some(v) = collapsed_cache.get(&2) {
-                    candidates.extend(v.clone());
-                }
-                if let Some(v) = collapsed_cache.get(&3) {
-                    candidates.extend(v.clone());
+    /// Aliases whose stem occurs among the stems of the text: «алмазную руду» finds
+    /// «алмазная руда», «камня» finds «камень».
+    fn stem_match_aliases(
+        &self,
+        normalized_text: &str,
+        already_matched: &HashMap<String, AliasMatch>,
+    ) -> HashMap<String, AliasMatch> {
+        let words: Vec<&str> = normalized_text.split_whitespace().collect();
+        let stemmed = stem_text(normalized_text, &self.stemmers);
+        let stems: Vec<&str> = stemmed.split_whitespace().collect();
+        let mut matched = HashMap::new();
+        for n in 1..=self.max_alias_words().min(stems.len()) {
+            // Stemming keeps every word, so the n-grams of both line up.
+            for (ngram, stem) in Self::make_ngrams(&words, n).into_iter().zip(Self::make_ngrams(&stems, n)) {
+                for alias in self.stemmed_aliases.get(&stem).into_iter().flatten() {
+                    if !already_matched.contains_key(alias) {
+                        matched.insert(alias.clone(), AliasMatch::new(ngram.clone(), MatchMethod::Stem, 1.0));
+                    }
                 }
             }
-            if candidates.is_empty() {
-                continue;
-            }
+        }
+        matched
+    }
 
-            for alias in aliases {
-                if already_matched.contains(alias) || alias.chars().count() < 5 {
-                    continue;
+    /// Aliases that sound like an n-gram of the text. Unlike the spelling-based fuzzy path
+    /// the first letter may differ as long as it sounds the same (г/к); keys shorter than
+    /// `MIN_FUZZY_PHONETIC_KEY` must be equal.
+    fn phonetic_match_aliases(
+        &self,
+        normalized_text: &str,
+        already_matched: &HashMap<String, AliasMatch>,
+    ) -> HashMap<String, AliasMatch> {
+        let (words, word_keys): (Vec<&str>, Vec<String>) = normalized_text
+            .split_whitespace()
+            .map(|word| (word, word_key(word)))
+            .filter(|(_, key)| !key.is_empty())
+            .unzip();
+        let keys: Vec<&str> = word_keys.iter().map(String::as_str).collect();
+        let mut by_key: HashMap<String, AliasMatch> = HashMap::new();
+        for word_count in 1..=self.max_alias_words().min(keys.len()) {
+            let ngrams = Self::ngram_candidates(&words, word_count);
+            for (ngram, candidate) in ngrams.into_iter().zip(Self::ngram_candidates(&keys, word_count)) {
+                // Gluing can put the same sound twice in a row.
+                let candidate = if word_count == 1 { word_key(&candidate) } else { candidate };
+                if let Some((key, _)) = self.phonetic_aliases.get_key_value(&candidate) {
+                    AliasMatch::keep_best(&mut by_key, key, AliasMatch::new(ngram.clone(), MatchMethod::Phonetic, 1.0));
                 }
-                let alias_first = alias.chars().next();
-                for candidate in &candidates {
-                    if !Self::is_plausible_length(alias, candidate) {
-                        continue;
-                    }
-                    if alias_first != candidate.chars().next() {
+                let Some(index) = self.phonetic_index.get(&(word_count, first_char(&candidate))) else {
+                    continue;
+                };
+                for key in index.candidates(&candidate, self.phonetic_threshold) {
+                    if !Self::is_plausible_length(key, &candidate) {
                         continue;
                     }
-                    if normalized_levenshtein(alias, candidate) >= threshold {
-                        fuzzy.insert(alias.clone());
-                        break;
+                    let score = normalized_levenshtein(key, &candidate);
+                    if score >= self.phonetic_threshold {
+                        AliasMatch::keep_best(&mut by_key, key, AliasMatch::new(ngram.clone(), MatchMethod::Phonetic, score));
                     }
                 }
             }
         }
-        fuzzy
+        let mut phonetic = HashMap::new();
+        for (key, found) in by_key {
+            for alias in &self.phonetic_aliases[&key] {
+                if !already_matched.contains_key(alias) {
+                    phonetic.insert(alias.clone(), found.clone());
+                }
+            }
+        }
+        phonetic
     }
 
-    fn match_blocks(&self, text: &str, fuzzy_threshold: f64) -> Vec<String> {
-        let normalized = normalize_text(text);
+    /// Blocks named in `text`, each with the alias and method that found it.
+    fn match_blocks(&self, text: &str, fuzzy_threshold: f64) -> Vec<BlockMatch> {
+        let normalized = self.normalize(text);
         if normalized.is_empty() {
             return Vec::new();
         }
 
-        let exact_aliases = self.exact_match_aliases(&normalized);
-        let mut matched_aliases = exact_aliases.clone();
+        let mut matched_aliases = self.exact_match_aliases(&normalized);
+        if !self.stemmers.is_empty() {
+            let stemmed = self.stem_match_aliases(&normalized, &matched_aliases);
+            matched_aliases.extend(stemmed);
+        }
         if fuzzy_threshold > 0.0 {
             let fuzzy = self.fuzzy_match_aliases(&normalized, fuzzy_threshold, &matched_aliases);
             matched_aliases.extend(fuzzy);
         }
+        if self.phonetic_threshold > 0.0 {
+            let phonetic = self.phonetic_match_aliases(&normalized, &matched_aliases);
+            matched_aliases.extend(phonetic);
+        }
+        self.blocks_for_aliases(matched_aliases)
+    }
+
+    /// `match_blocks` without the explanations.
+    fn match_block_ids(&self, text: &str, fuzzy_threshold: f64) -> Vec<String> {
+        block_ids(&self.match_blocks(text, fuzzy_threshold))
+    }
 
+    /// Blocks of `aliases` in `alias_order`, each block once, credited to its first alias.
+    fn blocks_for_aliases(&self, aliases: HashMap<String, AliasMatch>) -> Vec<BlockMatch> {
+        let mut aliases: Vec<(String, AliasMatch)> = aliases.into_iter().collect();
+        aliases.sort_by(|a, b| alias_order(&a.0, &b.0));
         let mut matched_blocks = Vec::new();
         let mut seen = HashSet::new();
-        for alias in &self.sorted_aliases {
-            if !matched_aliases.contains(alias) {
-                continue;
-            }
-            if let Some(blocks) = self.alias_to_blocks.get(alias) {
-                for block_id in blocks {
-                    if seen.insert(block_id.clone()) {
-                        matched_blocks.push(block_id.clone());
-                    }
+        for (alias, found) in aliases {
+            for block_id in self.alias_to_blocks.get(&alias).into_iter().flatten() {
+                if seen.insert(block_id.clone()) {
+                    matched_blocks.push(BlockMatch {
+                        block_id: block_id.clone(),
+                        alias: alias.clone(),
+                        ngram: found.ngram.clone(),
+                        method: found.method,
+                        score: found.score,
+                    });
                 }
             }
         }
         matched_blocks
     }
 
+    /// Word count of the longest alias.
+    fn max_alias_words(&self) -> usize {
+        self.aliases_by_word_count.keys().copied().max().unwrap_or(1)
+    }
+
     fn alias_count(&self) -> usize {
         self.alias_to_blocks.len()
     }
@@ -1515,11 +2906,40 @@ impl BlockCatalog {
     }
 }
 
+/// Mono PCM as produced by an input source, before resampling to the model rate.
+#[derive(Debug, Clone)]
+struct PcmBlock {
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+#[derive(Debug, Clone)]
+enum RecognizerInput {
+    Pcm(Vec<i16>),
+    /// End of a speech segment: finalize the current utterance right away.
+    Flush,
+    /// Drop the current utterance without emitting it.
+    Reset,
+}
+
+/// Messages into the audio pipeline; control messages stay ordered behind the audio already queued.
+#[derive(Debug, Clone)]
+enum PipelineInput {
+    Block(PcmBlock),
+    Control(RecognizerInput),
+}
+
 #[derive(Debug, Clone)]
 struct RecognizedPhraseEvent {
     speaker_id: String,
     text: String,
     is_partial: bool,
+    /// 0..1 when the speech engine reports it.
+    confidence: Option<f32>,
+    /// Per-word confidence and timing, when the engine reports them.
+    words: Vec<WordInfo>,
+    /// N-best list of a final result, `text` first; empty when not requested.
+    alternatives: Vec<Alternative>,
 }
 
 #[derive(Debug, Clone)]
@@ -1538,508 +2958,1819 @@ struct CachedChunkContext {
 struct PartialProgressState {
     last_partial: String,
     processed_committed_words: usize,
+    /// Blocks already matched from partials of this utterance; the final result skips them.
+    matched_blocks: HashSet<String>,
+    /// Cancel phrases already acted on in partials of this utterance.
+    cancels: usize,
 }
 
-fn list_input_devices() -> Result<Vec<String>, String> {
-    let host = cpal::default_host();
-    let default_name = host
-        .default_input_device()
-        .and_then(|d| d.name().ok())
-        .unwrap_or_default();
-    let devices = host
-        .input_devices()
-        .map_err(|e| format!("Не удалось получить список аудио-устройств: {e}"))?;
-
-    let mut lines = Vec::new();
-    for (index, device) in devices.enumerate() {
-        let name = device.name().unwrap_or_else(|_| "<unknown>".to_string());
-        let marker = if name == default_name { "*" } else { " " };
-        let (channels, samplerate) = device
-            .default_input_config()
-            .map(|cfg| (cfg.channels(), cfg.sample_rate().0))
-            .unwrap_or((0, 0));
-        lines.push(format!(
-            "{marker} {index}: {name} | in={channels} | default_samplerate={samplerate}"
-        ));
-    }
-    Ok(lines)
+/// Words of one event that the engine heard with less than `speech.min_word_confidence`.
+#[derive(Debug, Default)]
+struct LowConfidenceWords {
+    threshold: f32,
+    /// Normalized word -> its lowest score in the event.
+    words: HashMap<String, f32>,
+    /// Set when the engine only scored the whole phrase and that score is too low.
+    phrase: Option<f32>,
 }
 
-fn resolve_input_device(selector: &Option<DeviceSelector>) -> Result<Device, String> {
-    let host = cpal::default_host();
-    match selector {
-        None => host
-            .default_input_device()
-            .ok_or_else(|| "Не найдено устройство ввода по умолчанию".to_string()),
-        Some(DeviceSelector::Index(index)) => {
-            if *index < 0 {
-                return Err(format!("Неверный индекс устройства: {index}"));
-            }
-            let idx = *index as usize;
-            let mut devices = host
-                .input_devices()
-                .map_err(|e| format!("Не удалось получить аудио-устройства: {e}"))?;
-            devices
-                .nth(idx)
-                .ok_or_else(|| format!("Устройство ввода с индексом {idx} не найдено"))
+impl LowConfidenceWords {
+    fn from_event(event: &RecognizedPhraseEvent, threshold: f32, catalog: &BlockCatalog) -> Self {
+        let mut low = Self {
+            threshold,
+            ..Self::default()
+        };
+        if threshold <= 0.0 {
+            return low;
         }
-        Some(DeviceSelector::Name(name)) => {
-            let needle = name.trim().to_lowercase();
-            if needle.is_empty() {
-                return host
-                    .default_input_device()
-                    .ok_or_else(|| "Не найдено устройство ввода по умолчанию".to_string());
-            }
-            let devices = host
-                .input_devices()
-                .map_err(|e| format!("Не удалось получить аудио-устройства: {e}"))?;
-            for device in devices {
-                let dev_name = device.name().unwrap_or_default();
-                if dev_name.to_lowercase().contains(&needle) {
-                    return Ok(device);
-                }
+        for word in &event.words {
+            if let Some(confidence) = word.confidence.filter(|c| *c < threshold) {
+                let score = low.words.entry(catalog.normalize(&word.word)).or_insert(confidence);
+                *score = score.min(confidence);
             }
-            Err(format!("Устройство ввода с именем `{name}` не найдено"))
         }
+        if event.words.iter().all(|w| w.confidence.is_none()) {
+            low.phrase = event.confidence.filter(|c| *c < threshold);
+        }
+        low
     }
-}
-
-fn choose_input_config(
-    device: &Device,
-    sample_rate: u32,
-    blocksize: u32,
-) -> Result<(SupportedStreamConfigRange, StreamConfig), String> {
-    let ranges: Vec<SupportedStreamConfigRange> = device
-        .supported_input_configs()
-        .map_err(|e| format!("Не удалось получить поддерживаемые аудио-конфиги: {e}"))?
-        .collect();
 
-    let format_rank = |fmt: SampleFormat| -> u8 {
-        match fmt {
-            SampleFormat::I16 => 0,
-            SampleFormat::F32 => 1,
-            SampleFormat::I32 => 2,
-            SampleFormat::U16 => 3,
-            SampleFormat::I8 => 4,
-            SampleFormat::U8 => 5,
-            SampleFormat::U32 => 6,
-            SampleFormat::F64 => 7,
-            _ => 100,
-        }
-    };
+    fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.phrase.is_none()
+    }
+}
 
-    let mut candidates: Vec<SupportedStreamConfigRange> = ranges
+/// `BlockCatalog::match_blocks` that ignores words the engine was unsure about and logs
+/// the matches this drops. Blocks in `skip` were handled before and are left out silently.
+fn match_confident_blocks(
+    catalog: &BlockCatalog,
+    candidate: &str,
+    skip: &[String],
+    fuzzy_threshold: f64,
+    low: &LowConfidenceWords,
+    speaker_id: &str,
+    ui: &UiHandle,
+) -> Vec<BlockMatch> {
+    let min_word_confidence = low.threshold;
+    let matched: Vec<BlockMatch> = catalog
+        .match_blocks(candidate, fuzzy_threshold)
         .into_iter()
-        .filter(|range| {
-            sample_rate >= range.min_sample_rate().0
-                && sample_rate <= range.max_sample_rate().0
-                && range.channels() > 0
-        })
+        .filter(|m| !skip.contains(&m.block_id))
         .collect();
-
-    candidates.sort_by_key(|r| (format_rank(r.sample_format()), r.channels().saturating_sub(1)));
-
-    for range in candidates {
-        let cfg = StreamConfig {
-            channels: range.channels(),
-            sample_rate: SampleRate(sample_rate),
-            buffer_size: BufferSize::Fixed(blocksize),
-        };
-        return Ok((range, cfg));
+    if matched.is_empty() || low.is_empty() {
+        return matched;
+    }
+    if let Some(score) = low.phrase {
+        ui_log(
+            ui,
+            format!(
+                "[confidence] отклонено {speaker_id}: {} по «{candidate}» — фраза {score:.2} < {min_word_confidence:.2}",
+                block_ids(&matched).join(", ")
+            ),
+        );
+        return Vec::new();
     }
 
-    let def = device
-        .default_input_config()
-        .map_err(|e| format!("Не удалось получить default input config: {e}"))?;
-    Err(format!(
-        "Не найден поддерживаемый аудио-конфиг для sample_rate={sample_rate}. \
-default={}/{}",
-        def.channels(),
-        def.sample_rate().0
-    ))
+    let doubtful: Vec<String> = candidate
+        .split_whitespace()
+        .filter_map(|word| low.words.get(word).map(|score| format!("{word} {score:.2}")))
+        .collect();
+    if doubtful.is_empty() {
+        return matched;
+    }
+    let confident_text = candidate
+        .split_whitespace()
+        .filter(|word| !low.words.contains_key(*word))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let kept = if confident_text.is_empty() {
+        Vec::new()
+    } else {
+        catalog.match_block_ids(&confident_text, fuzzy_threshold)
+    };
+    let (accepted, rejected): (Vec<BlockMatch>, Vec<BlockMatch>) =
+        matched.into_iter().partition(|m| kept.contains(&m.block_id));
+    if !rejected.is_empty() {
+        ui_log(
+            ui,
+            format!(
+                "[confidence] отклонено {speaker_id}: {} по «{candidate}» — {} < {min_word_confidence:.2}",
+                block_ids(&rejected).join(", "),
+                doubtful.join(", ")
+            ),
+        );
+    }
+    accepted
 }
 
-struct MicrophoneSource {
-    stream: Option<Stream>,
+/// Word ranges of up to `window` words right after any of `phrases`. With
+/// `speech.wake_phrases` blocks count only inside them, with `speech.negation_words`
+/// the blocks named inside them are voided.
+struct PhraseWindows {
+    phrases: Vec<Vec<String>>,
+    window: usize,
 }
 
-impl MicrophoneSource {
-    fn start(
-        samplerate: u32,
-        blocksize: u32,
-        device_selector: &Option<DeviceSelector>,
-        ui: UiHandle,
-        on_pcm: impl Fn(Vec<i16>) + Send + Sync + 'static,
-    ) -> Result<Self, String> {
-        let device = resolve_input_device(device_selector)?;
-        let device_name = device.name().unwrap_or_else(|_| "<unknown>".to_string());
-        let (supported_range, stream_config) = choose_input_config(&device, samplerate, blocksize)?;
-        let sample_format = supported_range.sample_format();
-        let channels = stream_config.channels as usize;
-        let on_pcm = Arc::new(on_pcm);
-
-        let err_fn = {
-            let ui = Arc::clone(&ui);
-            move |err| {
-                ui_set_mic(&ui, false);
-                ui_log(&ui, format!("[microphone-status] {err}"));
-            }
-        };
+impl PhraseWindows {
+    /// `None` when no phrases are configured.
+    fn new(phrases: &[String], window: usize, catalog: &BlockCatalog) -> Option<Self> {
+        let phrases = phrase_words(phrases, catalog);
+        (!phrases.is_empty()).then_some(Self { phrases, window })
+    }
 
-        let stream = match sample_format {
-            SampleFormat::I8 => {
-                let on_pcm = Arc::clone(&on_pcm);
-                device
-                    .build_input_stream(
-                        &stream_config,
-                        move |data: &[i8], _| {
-                            let mono = to_mono_i8(data, channels);
-                            on_pcm(mono);
-                        },
-                        err_fn,
-                        None,
-                    )
-                    .map_err(|e| format!("Не удалось создать аудио-поток (i8): {e}"))?
-            }
-            SampleFormat::U8 => {
-                let on_pcm = Arc::clone(&on_pcm);
-                device
-                    .build_input_stream(
-                        &stream_config,
-                        move |data: &[u8], _| {
-                            let mono = to_mono_u8(data, channels);
-                            on_pcm(mono);
-                        },
-                        err_fn,
-                        None,
-                    )
-                    .map_err(|e| format!("Не удалось создать аудио-поток (u8): {e}"))?
-            }
-            SampleFormat::I16 => {
-                let on_pcm = Arc::clone(&on_pcm);
-                device
-                    .build_input_stream(
-                        &stream_config,
-                        move |data: &[i16], _| {
-                            let mono = to_mono_i16(data, channels);
-                            on_pcm(mono);
-                        },
-                        err_fn,
-                        None,
-                    )
-                    .map_err(|e| format!("Не удалось создать аудио-поток (i16): {e}"))?
-            }
-            SampleFormat::U16 => {
-                let on_pcm = Arc::clone(&on_pcm);
-                device
-                    .build_input_stream(
-                        &stream_config,
-                        move |data: &[u16], _| {
-                            let mono = to_mono_u16(data, channels);
-                            on_pcm(mono);
-                        },
-                        err_fn,
-                        None,
-                    )
-                    .map_err(|e| format!("Не удалось создать аудио-поток (u16): {e}"))?
-            }
-            SampleFormat::F32 => {
-                let on_pcm = Arc::clone(&on_pcm);
-                device
-                    .build_input_stream(
-                        &stream_config,
-                        move |data: &[f32], _| {
-                            let mono = to_mono_f32(data, channels);
-                            on_pcm(mono);
-                        },
-                        err_fn,
-                        None,
-                    )
-                    .map_err(|e| format!("Не удалось создать аудио-поток (f32): {e}"))?
-            }
-            SampleFormat::I32 => {
-                let on_pcm = Arc::clone(&on_pcm);
-                device
-                    .build_input_stream(
-                        &stream_config,
-                        move |data: &[i32], _| {
-                            let mono = to_mono_i32(data, channels);
-                            on_pcm(mono);
-                        },
-                        err_fn,
-                        None,
-                    )
-                    .map_err(|e| format!("Не удалось создать аудио-поток (i32): {e}"))?
-            }
-            SampleFormat::U32 => {
-                let on_pcm = Arc::clone(&on_pcm);
-                device
-                    .build_input_stream(
-                        &stream_config,
-                        move |data: &[u32], _| {
-                            let mono = to_mono_u32(data, channels);
-                            on_pcm(mono);
-                        },
-                        err_fn,
-                        None,
-                    )
-                    .map_err(|e| format!("Не удалось создать аудио-поток (u32): {e}"))?
-            }
-            SampleFormat::F64 => {
-                let on_pcm = Arc::clone(&on_pcm);
-                device
-                    .build_input_stream(
-                        &stream_config,
-                        move |data: &[f64], _| {
-                            let mono = to_mono_f64(data, channels);
-                            on_pcm(mono);
-                        },
-                        err_fn,
-                        None,
-                    )
-                    .map_err(|e| format!("Не удалось создать аудио-поток (f64): {e}"))?
-            }
-            other => {
-                return Err(format!(
-                    "Неподдерживаемый формат аудио `{other:?}`. Попробуй другое устройство."
-                ))
+    /// Merged ranges of up to `window` words after each phrase.
+    fn windows(&self, words: &[&str]) -> Vec<Range<usize>> {
+        let mut windows: Vec<Range<usize>> = Vec::new();
+        for start in 0..words.len() {
+            for phrase in &self.phrases {
+                if !phrase_at(words, start, phrase) {
+                    continue;
+                }
+                let end = start + phrase.len();
+                let window = end..(end + self.window).min(words.len());
+                match windows.last_mut() {
+                    Some(last) if window.start <= last.end => {
+                        last.start = last.start.min(window.start);
+                        last.end = last.end.max(window.end);
+                    }
+                    _ if !window.is_empty() => windows.push(window),
+                    _ => {}
+                }
             }
-        };
-
-        stream
-            .play()
-            .map_err(|e| format!("Не удалось запустить аудио-поток: {e}"))?;
-        ui_set_mic(&ui, true);
-        ui_log(
-            &ui,
-            format!(
-            "[microphone] запущен: {device_name} | channels={} | sample_rate={} | format={:?}",
-            stream_config.channels,
-            stream_config.sample_rate.0,
-            sample_format
-        ),
-        );
+        }
+        windows
+    }
 
-        Ok(Self {
-            stream: Some(stream),
-        })
+    /// The words of `normalized` inside the windows, as one phrase.
+    fn restrict(&self, normalized: &str) -> String {
+        let words: Vec<&str> = normalized.split_whitespace().collect();
+        self.windows(&words)
+            .into_iter()
+            .map(|range| words[range].join(" "))
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 
-    fn stop(&mut self) {
-        self.stream.take();
-        // UI status is flipped by caller on shutdown.
+    /// Blocks named in the windows of `words`, with the window text they were found in.
+    fn blocks(&self, words: &[&str], catalog: &BlockCatalog, fuzzy_threshold: f64) -> Vec<(String, String)> {
+        let mut out: Vec<(String, String)> = Vec::new();
+        for range in self.windows(words) {
+            let text = words[range].join(" ");
+            for block_id in catalog.match_block_ids(&text, fuzzy_threshold) {
+                if !out.iter().any(|(id, _)| *id == block_id) {
+                    out.push((block_id, text.clone()));
+                }
+            }
+        }
+        out
     }
 }
 
-fn to_mono_i8(data: &[i8], channels: usize) -> Vec<i16> {
-    if channels <= 1 {
-        return data.iter().map(|v| (*v as i16) << 8).collect();
-    }
-    data.chunks(channels)
-        .map(|chunk| {
-            let sum: i32 = chunk.iter().map(|v| *v as i32).sum();
-            ((sum / chunk.len() as i32) as i16) << 8
-        })
+/// Configured phrases split into normalized words.
+fn phrase_words(phrases: &[String], catalog: &BlockCatalog) -> Vec<Vec<String>> {
+    phrases
+        .iter()
+        .map(|p| catalog.normalize(p).split_whitespace().map(str::to_string).collect::<Vec<_>>())
+        .filter(|words| !words.is_empty())
         .collect()
 }
 
-fn to_mono_u8(data: &[u8], channels: usize) -> Vec<i16> {
-    if channels <= 1 {
-        return data
-            .iter()
-            .map(|v| (((*v as i32) - 128) << 8) as i16)
-            .collect();
-    }
-    data.chunks(channels)
-        .map(|chunk| {
-            let sum: i32 = chunk.iter().map(|v| *v as i32).sum();
-            let avg = sum / chunk.len() as i32;
-            ((avg - 128) << 8) as i16
-        })
-        .collect()
+fn phrase_at(words: &[&str], start: usize, phrase: &[String]) -> bool {
+    let end = start + phrase.len();
+    end <= words.len() && words[start..end].iter().zip(phrase).all(|(w, p)| w == p)
 }
 
-fn to_mono_i16(data: &[i16], channels: usize) -> Vec<i16> {
-    if channels <= 1 {
-        return data.to_vec();
+/// How many times any of `phrases` occurs in `words`, and the word index right after the
+/// last occurrence.
+fn find_phrases(words: &[&str], phrases: &[Vec<String>]) -> (usize, Option<usize>) {
+    let mut count = 0;
+    let mut after_last = None;
+    for start in 0..words.len() {
+        if let Some(phrase) = phrases.iter().find(|p| phrase_at(words, start, p)) {
+            count += 1;
+            after_last = Some(start + phrase.len());
+        }
     }
-    data.chunks(channels)
-        .map(|chunk| {
-            let sum: i32 = chunk.iter().map(|v| *v as i32).sum();
-            (sum / chunk.len() as i32) as i16
-        })
-        .collect()
+    (count, after_last)
 }
 
-fn to_mono_u16(data: &[u16], channels: usize) -> Vec<i16> {
-    if channels <= 1 {
-        return data.iter().map(|v| (*v as i32 - 32768) as i16).collect();
-    }
-    data.chunks(channels)
-        .map(|chunk| {
-            let sum: i64 = chunk.iter().map(|v| *v as i64).sum();
-            let avg = (sum / chunk.len() as i64) as i32;
-            (avg - 32768) as i16
-        })
-        .collect()
+/// Blocks matched from one phrase that wait out `speech.trigger_delay_ms`, so a cancel
+/// phrase right after can still stop them.
+struct PendingTrigger {
+    due: Instant,
+    speaker_id: String,
+    text: String,
+    is_partial: bool,
+    matches: Vec<BlockMatch>,
 }
 
-fn to_mono_i32(data: &[i32], channels: usize) -> Vec<i16> {
-    if channels <= 1 {
-        return data.iter().map(|v| (*v >> 16) as i16).collect();
+/// Picks blocks from an N-best list. Every alternative is matched against the catalog and
+/// each block scores the summed weight of the alternatives that name it; the best block wins
+/// together with the other blocks of the most likely alternative that contains it, unless its
+/// weight is below `min_confidence`.
+fn match_alternatives(
+    catalog: &BlockCatalog,
+    alternatives: &[Alternative],
+    fuzzy_threshold: f64,
+    min_confidence: f32,
+    speaker_id: &str,
+    ui: &UiHandle,
+) -> Vec<BlockMatch> {
+    let weights = Alternative::weights(alternatives);
+    let matches: Vec<Vec<BlockMatch>> = alternatives
+        .iter()
+        .map(|a| catalog.match_blocks(&a.text, fuzzy_threshold))
+        .collect();
+
+    let mut scores: Vec<(String, f32)> = Vec::new();
+    for (blocks, weight) in matches.iter().zip(&weights) {
+        for block in blocks {
+            match scores.iter_mut().find(|(id, _)| *id == block.block_id) {
+                Some((_, score)) => *score += weight,
+                None => scores.push((block.block_id.clone(), *weight)),
+            }
+        }
     }
-    data.chunks(channels)
-        .map(|chunk| {
-            let sum: i64 = chunk.iter().map(|v| *v as i64).sum();
-            let avg = (sum / chunk.len() as i64) as i32;
-            (avg >> 16) as i16
+    // First maximum, so on a tie the block of the more likely alternative wins.
+    let Some((winner, winner_score)) = scores
+        .iter()
+        .fold(None::<&(String, f32)>, |best, entry| match best {
+            Some(best) if best.1 >= entry.1 => Some(best),
+            _ => Some(entry),
         })
-        .collect()
-}
+        .cloned()
+    else {
+        return Vec::new();
+    };
+    let chosen = matches
+        .iter()
+        .position(|blocks| blocks.iter().any(|m| m.block_id == winner))
+        .unwrap_or_default();
 
-fn to_mono_u32(data: &[u32], channels: usize) -> Vec<i16> {
-    if channels <= 1 {
-        return data
-            .iter()
-            .map(|v| (((*v as i64) - 2_147_483_648_i64) >> 16) as i16)
-            .collect();
-    }
-    data.chunks(channels)
-        .map(|chunk| {
-            let sum: i128 = chunk.iter().map(|v| *v as i128).sum();
-            let avg = (sum / chunk.len() as i128) as i64;
-            ((avg - 2_147_483_648_i64) >> 16) as i16
+    let explained = alternatives
+        .iter()
+        .zip(&weights)
+        .zip(&matches)
+        .enumerate()
+        .map(|(index, ((alternative, weight), blocks))| {
+            format!(
+                "#{} «{}» {weight:.2} -> {}",
+                index + 1,
+                alternative.text,
+                if blocks.is_empty() { "—".to_string() } else { block_ids(blocks).join(", ") }
+            )
         })
-        .collect()
+        .collect::<Vec<_>>()
+        .join("; ");
+    ui_log(
+        ui,
+        format!(
+            "[alternatives] {speaker_id}: {explained} => {} ({winner_score:.2}, #{})",
+            block_ids(&matches[chosen]).join(", "),
+            chosen + 1
+        ),
+    );
+    // Vosk не дает уверенность слов в N-best, порог сравниваем с весом победителя.
+    if winner_score < min_confidence {
+        ui_log(
+            ui,
+            format!(
+                "[confidence] отклонено {speaker_id}: {} по N-best — {winner_score:.2} < {min_confidence:.2}",
+                block_ids(&matches[chosen]).join(", ")
+            ),
+        );
+        return Vec::new();
+    }
+    matches[chosen].clone()
 }
 
-fn to_mono_f32(data: &[f32], channels: usize) -> Vec<i16> {
-    fn conv(v: f32) -> i16 {
-        let x = v.clamp(-1.0, 1.0);
-        (x * i16::MAX as f32) as i16
-    }
-    if channels <= 1 {
-        return data.iter().map(|v| conv(*v)).collect();
-    }
-    data.chunks(channels)
-        .map(|chunk| {
-            let sum: f32 = chunk.iter().copied().sum();
-            conv(sum / chunk.len() as f32)
-        })
-        .collect()
+/// The matching half of the event worker: cancel phrases, partial n-grams, wake windows,
+/// negation, word confidence and N-best lists. RCON and the trigger queue stay in the
+/// worker, so scripted events can be checked without a server.
+struct EventMatcher {
+    catalog: BlockCatalog,
+    wake: Option<PhraseWindows>,
+    negation: Option<PhraseWindows>,
+    cancel_phrases: Vec<Vec<String>>,
+    fuzzy_threshold: f64,
+    min_word_confidence: f32,
+    repeat_window: Duration,
+    ui: UiHandle,
 }
 
-fn to_mono_f64(data: &[f64], channels: usize) -> Vec<i16> {
-    fn conv(v: f64) -> i16 {
-        let x = v.clamp(-1.0, 1.0);
-        (x * i16::MAX as f64) as i16
+impl EventMatcher {
+    fn new(speech: &SpeechConfig, catalog: BlockCatalog, ui: UiHandle) -> Self {
+        Self {
+            wake: PhraseWindows::new(&speech.wake_phrases, speech.wake_window_words, &catalog),
+            negation: PhraseWindows::new(&speech.negation_words, speech.negation_window_words, &catalog),
+            cancel_phrases: phrase_words(&speech.cancel_phrases, &catalog),
+            fuzzy_threshold: speech.fuzzy_threshold,
+            min_word_confidence: speech.min_word_confidence,
+            repeat_window: Duration::from_secs(1),
+            catalog,
+            ui,
+        }
     }
-    if channels <= 1 {
-        return data.iter().map(|v| conv(*v)).collect();
+
+    /// Whether `cleaned` holds a cancel phrase not acted on yet in this utterance; the
+    /// pending deletions of the speaker are then dropped. Checked before `match_event`.
+    fn new_cancel(&self, cleaned: &str, progress: &mut PartialProgressState) -> bool {
+        if self.cancel_phrases.is_empty() {
+            return false;
+        }
+        // Срабатывает и по последнему слову partial: ждать final дольше задержки.
+        let words: Vec<&str> = cleaned.split_whitespace().collect();
+        let (count, _) = find_phrases(&words, &self.cancel_phrases);
+        if count <= progress.cancels {
+            return false;
+        }
+        progress.cancels = count;
+        true
     }
-    data.chunks(channels)
-        .map(|chunk| {
-            let sum: f64 = chunk.iter().copied().sum();
-            conv(sum / chunk.len() as f64)
-        })
-        .collect()
-}
 
-fn spawn_recognizer_worker(
-    model_path: PathBuf,
-    sample_rate: u32,
-    log_partials: bool,
-    grammar_phrases: Option<Vec<String>>,
-    ui: UiHandle,
-    shutdown: Arc<AtomicBool>,
-    pcm_rx: Receiver<Vec<i16>>,
-    text_tx: Sender<RecognizedPhraseEvent>,
-) -> thread::JoinHandle<()> {
-    thread::spawn(move || {
-        set_log_level(LogLevel::Warn);
-        let Some(model) = Model::new(model_path.to_string_lossy().to_string()) else {
-            ui_set_rec(&ui, false);
-            ui_log(
-                &ui,
-                format!(
-                "[recognizer-error] Не удалось загрузить Vosk model из `{}`",
-                model_path.display()
-            ),
-            );
-            return;
+    /// Blocks to delete for one event of a speaker, negated ones already left out; `cleaned`
+    /// is its text after `BlockCatalog::normalize`. `progress` and `repeat_gate` carry the
+    /// speaker's state between events.
+    fn match_event(
+        &self,
+        event: &RecognizedPhraseEvent,
+        cleaned: &str,
+        progress: &mut PartialProgressState,
+        repeat_gate: &mut HashMap<String, RepeatGateState>,
+    ) -> Vec<BlockMatch> {
+        let catalog = &self.catalog;
+        let fuzzy_threshold = self.fuzzy_threshold;
+        let ui = &self.ui;
+        let mut matches = Vec::new();
+
+        let words: Vec<&str> = cleaned.split_whitespace().collect();
+        // В partial последнее слово еще может измениться.
+        let committed_count = if event.is_partial {
+            words.len().saturating_sub(1)
+        } else {
+            words.len()
         };
 
-        let mut recognizer = if let Some(phrases) = grammar_phrases.as_ref().filter(|p| !p.is_empty()) {
-            let refs: Vec<&str> = phrases.iter().map(|s| s.as_str()).collect();
-            match Recognizer::new_with_grammar(&model, sample_rate as f32, &refs) {
-                Some(r) => r,
-                None => {
-                    ui_log(
-                        &ui,
-                        "[recognizer-warning] grammar mode unavailable for current model, fallback to default"
-                            .to_string(),
-                    );
-                    match Recognizer::new(&model, sample_rate as f32) {
-                        Some(r) => r,
-                        None => {
-                            ui_set_rec(&ui, false);
-                            ui_log(&ui, "[recognizer-error] Не удалось создать Vosk recognizer");
-                            return;
-                        }
-                    }
+        // Блоки до отмены больше не ищем.
+        let after_cancel = find_phrases(&words, &self.cancel_phrases).1.unwrap_or_default();
+        let use_alternatives = !event.is_partial && event.alternatives.len() > 1 && after_cancel == 0;
+
+        // (текст, блоки, которые по нему уже найдены раньше и повторно не срабатывают)
+        let candidates: Vec<(String, Vec<String>)> = if event.is_partial {
+            // Если partial "откатился"/пересобрался, начинаем индекс заново.
+            if !cleaned.starts_with(&progress.last_partial) {
+                progress.processed_committed_words = 0;
+                progress.matched_blocks.clear();
+            }
+
+            // Каждое новое зафиксированное слово проверяем вместе с предыдущими
+            // (до длины самого длинного алиаса), чтобы "алмазная руда" сработала
+            // еще до final. Блоки, найденные без нового слова, уже обработаны.
+            // С wake-фразами окно не выходит за слова сразу после нее.
+            let max_words = catalog.max_alias_words();
+            let wake_windows = self.wake.as_ref().map(|wake| wake.windows(&words[..committed_count]));
+            let start_idx = progress.processed_committed_words.min(committed_count).max(after_cancel);
+            let mut out = Vec::new();
+            for idx in start_idx..committed_count {
+                if words[idx].len() < 2 {
+                    continue;
+                }
+                let mut window_start = (idx + 1).saturating_sub(max_words).max(after_cancel);
+                if let Some(wake_windows) = &wake_windows {
+                    let Some(allowed) = wake_windows.iter().find(|w| w.contains(&idx)) else {
+                        continue;
+                    };
+                    window_start = window_start.max(allowed.start);
                 }
+                let prefix = words[window_start..idx].join(" ");
+                let known = if prefix.is_empty() {
+                    Vec::new()
+                } else {
+                    catalog.match_block_ids(&prefix, fuzzy_threshold)
+                };
+                out.push((words[window_start..=idx].join(" "), known));
             }
+            progress.last_partial = cleaned.to_string();
+            progress.processed_committed_words = committed_count;
+            out
         } else {
-            match Recognizer::new(&model, sample_rate as f32) {
-                Some(r) => r,
-                None => {
-                    ui_set_rec(&ui, false);
-                    ui_log(&ui, "[recognizer-error] Не удалось создать Vosk recognizer");
+            let finished = std::mem::take(progress);
+            let known: Vec<String> = finished.matched_blocks.into_iter().collect();
+            let fresh = &words[after_cancel..];
+            match &self.wake {
+                // N-best финал сам ограничивает каждый вариант окнами wake-фраз.
+                Some(_) if use_alternatives => vec![(cleaned.to_string(), known)],
+                Some(wake) => {
+                    let windows = wake.windows(fresh);
+                    let text = fresh.join(" ");
+                    if windows.is_empty() && !catalog.match_blocks(&text, fuzzy_threshold).is_empty() {
+                        ui_log(
+                            ui,
+                            format!("[wake] {}: нет wake-фразы, пропущено «{text}»", event.speaker_id),
+                        );
+                    }
+                    windows
+                        .into_iter()
+                        .map(|range| (fresh[range].join(" "), known.clone()))
+                        .collect()
+                }
+                None => vec![(fresh.join(" "), known)],
+            }
+        };
+
+        // Блоки сразу после "не"/"кроме" не удаляем: "только не камень".
+        let negated = match &self.negation {
+            Some(negation) => negation.blocks(&words[..committed_count], catalog, fuzzy_threshold),
+            None => Vec::new(),
+        };
+        let low_confidence = LowConfidenceWords::from_event(event, self.min_word_confidence, catalog);
+        let mut seen_blocks = HashSet::new();
+
+        for (candidate, known_blocks) in candidates {
+            let now = Instant::now();
+            let state = repeat_gate.entry(candidate.clone()).or_insert(RepeatGateState {
+                last_seen: now,
+                count: 0,
+            });
+            if now.duration_since(state.last_seen) > self.repeat_window {
+                state.count = 0;
+            }
+            state.last_seen = now;
+            state.count += 1;
+
+            // 1, 9, 17, ... => пропускаем 7 из каждых 8 одинаковых повторов за секунду
+            if (state.count - 1) % 8 != 0 {
+                continue;
+            }
+
+            let matched = if use_alternatives {
+                let alternatives: Vec<Alternative> = match &self.wake {
+                    Some(wake) => event
+                        .alternatives
+                        .iter()
+                        .map(|a| Alternative {
+                            text: wake.restrict(&catalog.normalize(&a.text)),
+                            score: a.score,
+                        })
+                        .collect(),
+                    None => event.alternatives.clone(),
+                };
+                match_alternatives(
+                    catalog,
+                    &alternatives,
+                    fuzzy_threshold,
+                    self.min_word_confidence,
+                    &event.speaker_id,
+                    ui,
+                )
+                    .into_iter()
+                    .filter(|m| !known_blocks.contains(&m.block_id))
+                    .collect()
+            } else {
+                match_confident_blocks(
+                    catalog,
+                    &candidate,
+                    &known_blocks,
+                    fuzzy_threshold,
+                    &low_confidence,
+                    &event.speaker_id,
+                    ui,
+                )
+            };
+            for block_match in matched {
+                if !seen_blocks.insert(block_match.block_id.clone()) {
+                    continue;
+                }
+                match negated.iter().find(|(id, _)| *id == block_match.block_id) {
+                    Some((_, text)) => ui_log(
+                        ui,
+                        format!(
+                            "[negation] {}: не удаляю {} — «{text}» после отрицания",
+                            event.speaker_id, block_match.block_id
+                        ),
+                    ),
+                    None => matches.push(block_match),
+                }
+            }
+        }
+
+        if event.is_partial {
+            // Отклоненные отрицанием тоже считаются обработанными, чтобы final
+            // не повторял их в логе.
+            progress.matched_blocks.extend(seen_blocks);
+        }
+        matches
+    }
+}
+
+/// Event-worker state of one voice source; speakers never share cooldowns or chunk caches,
+/// even when they target the same player.
+#[derive(Debug)]
+struct SpeakerRuntime {
+    player_name: String,
+    cooldown_seconds: f64,
+    last_trigger: HashMap<String, Instant>,
+    repeat_gate: HashMap<String, RepeatGateState>,
+    partial_progress: PartialProgressState,
+    cached_chunk: Option<CachedChunkContext>,
+}
+
+impl SpeakerRuntime {
+    fn new(config: &SpeakerConfig) -> Self {
+        Self {
+            player_name: config.player_name.clone(),
+            cooldown_seconds: config.cooldown_seconds,
+            last_trigger: HashMap::new(),
+            repeat_gate: HashMap::new(),
+            partial_progress: PartialProgressState::default(),
+            cached_chunk: None,
+        }
+    }
+
+    /// Starts the cooldown of `block_id`; false while its previous trigger is still cooling down.
+    fn take_cooldown(&mut self, block_id: &str, now: Instant) -> bool {
+        if let Some(prev) = self.last_trigger.get(block_id) {
+            if now.duration_since(*prev).as_secs_f64() < self.cooldown_seconds {
+                return false;
+            }
+        }
+        self.last_trigger.insert(block_id.to_string(), now);
+        true
+    }
+
+    fn chunk_context(
+        &mut self,
+        rcon: &MinecraftRconService,
+        ttl: Duration,
+    ) -> Result<PlayerChunkContext, Box<dyn std::error::Error>> {
+        if let Some(cache) = self.cached_chunk.as_ref().filter(|c| c.fetched_at.elapsed() <= ttl) {
+            return Ok(cache.context.clone());
+        }
+        self.cached_chunk = None;
+        let context = rcon.get_player_chunk_context(&self.player_name)?;
+        self.cached_chunk = Some(CachedChunkContext {
+            fetched_at: Instant::now(),
+            context: context.clone(),
+        });
+        Ok(context)
+    }
+}
+
+/// Runtimes of the configured speakers, created on their first event.
+struct SpeakerRuntimes {
+    speakers: BTreeMap<String, SpeakerConfig>,
+    runtimes: HashMap<String, SpeakerRuntime>,
+    unmapped_warned: HashSet<String>,
+}
+
+impl SpeakerRuntimes {
+    fn new(speakers: BTreeMap<String, SpeakerConfig>) -> Self {
+        Self {
+            speakers,
+            runtimes: HashMap::new(),
+            unmapped_warned: HashSet::new(),
+        }
+    }
+
+    /// Runtime of a speaker that already sent an event.
+    fn get_mut(&mut self, speaker_id: &str) -> Option<&mut SpeakerRuntime> {
+        self.runtimes.get_mut(speaker_id)
+    }
+
+    /// Runtime for an event of `speaker_id`; ids missing from `speakers` get one warning each.
+    fn for_event(&mut self, speaker_id: &str, ui: &UiHandle) -> Option<&mut SpeakerRuntime> {
+        let Some(speaker) = self.speakers.get(speaker_id) else {
+            if self.unmapped_warned.insert(speaker_id.to_string()) {
+                ui_log(
+                    ui,
+                    format!("[mapping-warning] нет никнейма для {speaker_id} — добавь его в speakers"),
+                );
+            }
+            return None;
+        };
+        Some(
+            self.runtimes
+                .entry(speaker_id.to_string())
+                .or_insert_with(|| SpeakerRuntime::new(speaker)),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct InputDeviceInfo {
+    pub(crate) index: usize,
+    pub(crate) name: String,
+    pub(crate) is_default: bool,
+    channels: u16,
+    default_samplerate: u32,
+}
+
+impl InputDeviceInfo {
+    pub(crate) fn label(&self) -> String {
+        format!(
+            "{}{} ({} ch, {} Hz)",
+            self.name,
+            if self.is_default { " *" } else { "" },
+            self.channels,
+            self.default_samplerate
+        )
+    }
+
+    /// Position in `devices` of the device `selector` points at.
+    fn find(devices: &[Self], selector: &DeviceSelector) -> Option<usize> {
+        match selector {
+            DeviceSelector::Index(index) => devices.iter().position(|d| *index >= 0 && *index as usize == d.index),
+            DeviceSelector::Name(name) => device_position_by_name(devices.iter().map(|d| d.name.as_str()), name),
+        }
+    }
+}
+
+/// Position of the device called `name`. An exact name wins, so `USB Mic` does not land on
+/// `USB Mic 2` listed before it; only when none matches exactly is `name` looked up as a
+/// case-insensitive substring.
+fn device_position_by_name<'a>(names: impl Iterator<Item = &'a str> + Clone, name: &str) -> Option<usize> {
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let needle = name.to_lowercase();
+    names
+        .clone()
+        .position(|n| n == name)
+        .or_else(|| names.clone().position(|n| n.to_lowercase().contains(&needle)))
+}
+
+pub(crate) fn enumerate_input_devices() -> Result<Vec<InputDeviceInfo>, String> {
+    let host = cpal::default_host();
+    let default_name = host
+        .default_input_device()
+        .and_then(|d| d.name().ok())
+        .unwrap_or_default();
+    let devices = host
+        .input_devices()
+        .map_err(|e| format!("Не удалось получить список аудио-устройств: {e}"))?;
+
+    Ok(devices
+        .enumerate()
+        .map(|(index, device)| {
+            let name = device.name().unwrap_or_else(|_| "<unknown>".to_string());
+            let (channels, default_samplerate) = device
+                .default_input_config()
+                .map(|cfg| (cfg.channels(), cfg.sample_rate().0))
+                .unwrap_or((0, 0));
+            InputDeviceInfo {
+                index,
+                is_default: name == default_name,
+                name,
+                channels,
+                default_samplerate,
+            }
+        })
+        .collect())
+}
+
+fn list_input_devices() -> Result<Vec<String>, String> {
+    Ok(enumerate_input_devices()?
+        .into_iter()
+        .map(|info| {
+            let marker = if info.is_default { "*" } else { " " };
+            format!(
+                "{marker} {}: {} | channels={} | default_samplerate={}",
+                info.index, info.name, info.channels, info.default_samplerate
+            )
+        })
+        .collect())
+}
+
+fn resolve_input_device(selector: &Option<DeviceSelector>) -> Result<Device, String> {
+    let host = cpal::default_host();
+    match selector {
+        None => host
+            .default_input_device()
+            .ok_or_else(|| "Не найдено устройство ввода по умолчанию".to_string()),
+        Some(DeviceSelector::Index(index)) => {
+            if *index < 0 {
+                return Err(format!("Неверный индекс устройства: {index}"));
+            }
+            let idx = *index as usize;
+            let mut devices = host
+                .input_devices()
+                .map_err(|e| format!("Не удалось получить аудио-устройства: {e}"))?;
+            devices
+                .nth(idx)
+                .ok_or_else(|| format!("Устройство ввода с индексом {idx} не найдено"))
+        }
+        Some(DeviceSelector::Name(name)) => {
+            if name.trim().is_empty() {
+                return host
+                    .default_input_device()
+                    .ok_or_else(|| "Не найдено устройство ввода по умолчанию".to_string());
+            }
+            let mut devices: Vec<(Device, String)> = host
+                .input_devices()
+                .map_err(|e| format!("Не удалось получить аудио-устройства: {e}"))?
+                .map(|device| {
+                    let dev_name = device.name().unwrap_or_default();
+                    (device, dev_name)
+                })
+                .collect();
+            match device_position_by_name(devices.iter().map(|(_, dev_name)| dev_name.as_str()), name) {
+                Some(position) => Ok(devices.swap_remove(position).0),
+                None => Err(format!("Устройство ввода с именем `{name}` не найдено")),
+            }
+        }
+    }
+}
+
+fn choose_input_config(
+    device: &Device,
+    preferred_sample_rate: Option<u32>,
+    blocksize: u32,
+) -> Result<(SupportedStreamConfigRange, StreamConfig), String> {
+    let ranges: Vec<SupportedStreamConfigRange> = device
+        .supported_input_configs()
+        .map_err(|e| format!("Не удалось получить поддерживаемые аудио-конфиги: {e}"))?
+        .collect();
+    let def = device
+        .default_input_config()
+        .map_err(|e| format!("Не удалось получить default input config: {e}"))?;
+
+    let format_rank = |fmt: SampleFormat| -> u8 {
+        match fmt {
+            SampleFormat::I16 => 0,
+            SampleFormat::F32 => 1,
+            SampleFormat::I32 => 2,
+            SampleFormat::U16 => 3,
+            SampleFormat::I8 => 4,
+            SampleFormat::U8 => 5,
+            SampleFormat::U32 => 6,
+            SampleFormat::F64 => 7,
+            _ => 100,
+        }
+    };
+
+    // The requested rate wins when the device supports it; otherwise run at the device's
+    // default rate and let the resampler convert to the model rate.
+    let mut rates = Vec::new();
+    if let Some(rate) = preferred_sample_rate {
+        rates.push(rate);
+    }
+    rates.push(def.sample_rate().0);
+
+    for sample_rate in rates {
+        let mut candidates: Vec<&SupportedStreamConfigRange> = ranges
+            .iter()
+            .filter(|range| {
+                sample_rate >= range.min_sample_rate().0
+                    && sample_rate <= range.max_sample_rate().0
+                    && range.channels() > 0
+            })
+            .collect();
+        candidates.sort_by_key(|r| (format_rank(r.sample_format()), r.channels().saturating_sub(1)));
+
+        if let Some(range) = candidates.first() {
+            let cfg = StreamConfig {
+                channels: range.channels(),
+                sample_rate: SampleRate(sample_rate),
+                buffer_size: BufferSize::Fixed(blocksize),
+            };
+            return Ok((**range, cfg));
+        }
+    }
+
+    Err(format!(
+        "Не найден поддерживаемый аудио-конфиг для sample_rate={}. \
+default={}/{}",
+        preferred_sample_rate
+            .map(|r| r.to_string())
+            .unwrap_or_else(|| "default".to_string()),
+        def.channels(),
+        def.sample_rate().0
+    ))
+}
+
+/// Health of the current input stream, shared between cpal callbacks and the supervisor.
+struct StreamActivity {
+    base: Instant,
+    last_block_ms: AtomicU64,
+    last_sound_ms: AtomicU64,
+    failed: AtomicBool,
+    /// `LevelMeter` warnings from the callback; the supervisor logs them.
+    level_warnings: (Sender<String>, Receiver<String>),
+}
+
+impl StreamActivity {
+    fn new() -> Self {
+        Self {
+            base: Instant::now(),
+            last_block_ms: AtomicU64::new(0),
+            last_sound_ms: AtomicU64::new(0),
+            failed: AtomicBool::new(false),
+            level_warnings: bounded(WARNING_QUEUE),
+        }
+    }
+
+    fn log_level_warnings(&self, ui: &UiHandle) {
+        for line in self.level_warnings.1.try_iter() {
+            ui_log(ui, line);
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.base.elapsed().as_millis() as u64
+    }
+
+    fn touch(&self, samples: &[i16]) {
+        let now = self.now_ms();
+        self.last_block_ms.store(now, Ordering::Relaxed);
+        if samples.iter().any(|v| *v != 0) {
+            self.last_sound_ms.store(now, Ordering::Relaxed);
+        }
+    }
+
+    fn reset(&self) {
+        let now = self.now_ms();
+        self.last_block_ms.store(now, Ordering::Relaxed);
+        self.last_sound_ms.store(now, Ordering::Relaxed);
+        self.failed.store(false, Ordering::SeqCst);
+    }
+
+    fn since_block(&self) -> Duration {
+        Duration::from_millis(self.now_ms().saturating_sub(self.last_block_ms.load(Ordering::Relaxed)))
+    }
+
+    fn since_sound(&self) -> Duration {
+        Duration::from_millis(self.now_ms().saturating_sub(self.last_sound_ms.load(Ordering::Relaxed)))
+    }
+}
+
+enum MicCommand {
+    SwitchDevice(Option<DeviceSelector>),
+    Stop,
+}
+
+/// Owns the cpal stream on a supervisor thread (cpal streams are `!Send`) and rebuilds it
+/// when the device errors out, stops delivering audio or is switched from the settings.
+struct MicrophoneSource {
+    commands: Sender<MicCommand>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl MicrophoneSource {
+    fn start(
+        config: &MicrophoneConfig,
+        device: Option<DeviceSelector>,
+        voice_gate: Arc<AtomicBool>,
+        ui: UiHandle,
+        on_pcm: impl Fn(PcmBlock) + Send + Sync + 'static,
+    ) -> Result<Self, String> {
+        let activity = Arc::new(StreamActivity::new());
+        let on_pcm: Arc<dyn Fn(PcmBlock) + Send + Sync> = {
+            let activity = Arc::clone(&activity);
+            let meter = Mutex::new(LevelMeter::new(Arc::clone(&ui), activity.level_warnings.0.clone()));
+            Arc::new(move |block: PcmBlock| {
+                activity.touch(&block.samples);
+                // Metered before the mute check so a muted mic still shows it is alive.
+                if let Ok(mut meter) = meter.lock() {
+                    meter.feed(&block.samples);
+                }
+                // Push-to-talk / mute: drop audio right at the source while the gate is closed.
+                if !voice_gate.load(Ordering::Relaxed) {
                     return;
                 }
+                on_pcm(block)
+            })
+        };
+
+        let (commands, command_rx) = bounded::<MicCommand>(8);
+        let (ready_tx, ready_rx) = bounded::<Result<(), String>>(1);
+        let handle = {
+            let config = config.clone();
+            thread::spawn(move || {
+                let open = |device: &Option<DeviceSelector>| {
+                    open_input_stream(
+                        config.samplerate,
+                        config.blocksize,
+                        device,
+                        &config.channel,
+                        Arc::clone(&activity),
+                        Arc::clone(&ui),
+                        Arc::clone(&on_pcm),
+                    )
+                };
+                let stream = match open(&device) {
+                    Ok(stream) => {
+                        activity.reset();
+                        let _ = ready_tx.send(Ok(()));
+                        stream
+                    }
+                    Err(err) => {
+                        let _ = ready_tx.send(Err(err));
+                        return;
+                    }
+                };
+                supervise_input_stream(stream, device, &command_rx, &config.reconnect, &activity, &ui, open);
+            })
+        };
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(Self {
+                commands,
+                handle: Some(handle),
+            }),
+            Ok(Err(err)) => {
+                let _ = handle.join();
+                Err(err)
+            }
+            Err(_) => {
+                let _ = handle.join();
+                Err("Поток микрофона завершился до запуска".to_string())
+            }
+        }
+    }
+
+    fn commands(&self) -> Sender<MicCommand> {
+        self.commands.clone()
+    }
+
+    fn stop(&mut self) {
+        let _ = self.commands.send(MicCommand::Stop);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        // UI status is flipped by caller on shutdown.
+    }
+}
+
+fn supervise_input_stream(
+    stream: Stream,
+    mut device: Option<DeviceSelector>,
+    commands: &Receiver<MicCommand>,
+    reconnect: &ReconnectConfig,
+    activity: &StreamActivity,
+    ui: &UiHandle,
+    open: impl Fn(&Option<DeviceSelector>) -> Result<Stream, String>,
+) {
+    let initial_backoff = Duration::from_millis(reconnect.initial_backoff_ms);
+    let max_backoff = Duration::from_secs(reconnect.max_backoff_seconds).max(initial_backoff);
+    let mut stream = Some(stream);
+    let mut backoff = initial_backoff;
+    // `None` while nothing is scheduled: either the stream is up or reconnecting is disabled.
+    let mut next_attempt: Option<Instant> = None;
+    let mut attempts = 0u32;
+
+    loop {
+        activity.log_level_warnings(ui);
+        match commands.recv_timeout(Duration::from_millis(200)) {
+            Ok(MicCommand::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+            Ok(MicCommand::SwitchDevice(selector)) => {
+                // Release the old device first: some backends refuse to open it twice.
+                stream = None;
+                device = selector;
+                ui_set_mic(ui, false);
+                match open(&device) {
+                    Ok(new_stream) => {
+                        activity.reset();
+                        stream = Some(new_stream);
+                        next_attempt = None;
+                        backoff = initial_backoff;
+                        attempts = 0;
+                    }
+                    Err(err) => {
+                        ui_log(ui, format!("[microphone-status] не удалось открыть выбранное устройство: {err}"));
+                        if reconnect.enabled {
+                            next_attempt = Some(Instant::now() + backoff);
+                        }
+                    }
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if !reconnect.enabled {
+            continue;
+        }
+
+        if stream.is_some() {
+            let reason = if activity.failed.swap(false, Ordering::SeqCst) {
+                Some("ошибка аудио-потока".to_string())
+            } else if activity.since_block() >= Duration::from_millis(reconnect.stall_ms) {
+                Some(format!("нет аудио {} ms", activity.since_block().as_millis()))
+            } else if reconnect.silence_seconds > 0
+                && activity.since_sound() >= Duration::from_secs(reconnect.silence_seconds)
+            {
+                Some(format!("цифровая тишина {} s", activity.since_sound().as_secs()))
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                stream = None;
+                ui_set_mic(ui, false);
+                ui_log(
+                    ui,
+                    format!(
+                        "[microphone-status] устройство потеряно ({reason}), переподключение через {:.1}s",
+                        backoff.as_secs_f64()
+                    ),
+                );
+                next_attempt = Some(Instant::now() + backoff);
+            }
+            continue;
+        }
+
+        if next_attempt.is_none_or(|at| Instant::now() < at) {
+            continue;
+        }
+        attempts += 1;
+        match open(&device) {
+            Ok(new_stream) => {
+                activity.reset();
+                stream = Some(new_stream);
+                next_attempt = None;
+                ui_log(ui, format!("[microphone] переподключен (попытка {attempts})"));
+                backoff = initial_backoff;
+                attempts = 0;
+            }
+            Err(err) => {
+                backoff = (backoff * 2).min(max_backoff);
+                next_attempt = Some(Instant::now() + backoff);
+                ui_log(
+                    ui,
+                    format!(
+                        "[microphone-warning] переподключение не удалось (попытка {attempts}): {err}; следующая через {:.1}s",
+                        backoff.as_secs_f64()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+fn open_input_stream(
+    samplerate: Option<u32>,
+    blocksize: u32,
+    device_selector: &Option<DeviceSelector>,
+    channel: &ChannelSelection,
+    activity: Arc<StreamActivity>,
+    ui: UiHandle,
+    on_block: Arc<dyn Fn(PcmBlock) + Send + Sync>,
+) -> Result<Stream, String> {
+    let device = resolve_input_device(device_selector)?;
+    let device_name = device.name().unwrap_or_else(|_| "<unknown>".to_string());
+    let (supported_range, stream_config) = choose_input_config(&device, samplerate, blocksize)?;
+    let sample_format = supported_range.sample_format();
+    let channels = stream_config.channels as usize;
+    let stream_rate = stream_config.sample_rate.0;
+    if let Some(requested) = samplerate.filter(|r| *r != stream_rate) {
+        ui_log(
+            &ui,
+            format!(
+                "[microphone-warning] sample_rate={requested} не поддерживается `{device_name}`, использую {stream_rate}"
+            ),
+        );
+    }
+    let selection = channel.fit(channels).unwrap_or_else(|warning| {
+        ui_log(&ui, format!("[microphone-warning] {warning}"));
+        ChannelSelection::Mix
+    });
+    let channel_label = selection.to_string();
+    let on_pcm = Arc::new(move |samples: Vec<i16>| {
+        on_block(PcmBlock {
+            sample_rate: stream_rate,
+            samples,
+        })
+    });
+
+    let err_fn = {
+        let ui = Arc::clone(&ui);
+        move |err| {
+            activity.failed.store(true, Ordering::SeqCst);
+            ui_set_mic(&ui, false);
+            ui_log(&ui, format!("[microphone-status] {err}"));
+        }
+    };
+
+    let stream = match sample_format {
+        SampleFormat::I8 => {
+            let on_pcm = Arc::clone(&on_pcm);
+            device
+                .build_input_stream(
+                    &stream_config,
+                    move |data: &[i8], _| {
+                        let mono = to_mono_i8(data, channels, &selection);
+                        on_pcm(mono);
+                    },
+                    err_fn,
+                    None,
+                )
+                .map_err(|e| format!("Не удалось создать аудио-поток (i8): {e}"))?
+        }
+        SampleFormat::U8 => {
+            let on_pcm = Arc::clone(&on_pcm);
+            device
+                .build_input_stream(
+                    &stream_config,
+                    move |data: &[u8], _| {
+                        let mono = to_mono_u8(data, channels, &selection);
+                        on_pcm(mono);
+                    },
+                    err_fn,
+                    None,
+                )
+                .map_err(|e| format!("Не удалось создать аудио-поток (u8): {e}"))?
+        }
+        SampleFormat::I16 => {
+            let on_pcm = Arc::clone(&on_pcm);
+            device
+                .build_input_stream(
+                    &stream_config,
+                    move |data: &[i16], _| {
+                        let mono = to_mono_i16(data, channels, &selection);
+                        on_pcm(mono);
+                    },
+                    err_fn,
+                    None,
+                )
+                .map_err(|e| format!("Не удалось создать аудио-поток (i16): {e}"))?
+        }
+        SampleFormat::U16 => {
+            let on_pcm = Arc::clone(&on_pcm);
+            device
+                .build_input_stream(
+                    &stream_config,
+                    move |data: &[u16], _| {
+                        let mono = to_mono_u16(data, channels, &selection);
+                        on_pcm(mono);
+                    },
+                    err_fn,
+                    None,
+                )
+                .map_err(|e| format!("Не удалось создать аудио-поток (u16): {e}"))?
+        }
+        SampleFormat::F32 => {
+            let on_pcm = Arc::clone(&on_pcm);
+            device
+                .build_input_stream(
+                    &stream_config,
+                    move |data: &[f32], _| {
+                        let mono = to_mono_f32(data, channels, &selection);
+                        on_pcm(mono);
+                    },
+                    err_fn,
+                    None,
+                )
+                .map_err(|e| format!("Не удалось создать аудио-поток (f32): {e}"))?
+        }
+        SampleFormat::I32 => {
+            let on_pcm = Arc::clone(&on_pcm);
+            device
+                .build_input_stream(
+                    &stream_config,
+                    move |data: &[i32], _| {
+                        let mono = to_mono_i32(data, channels, &selection);
+                        on_pcm(mono);
+                    },
+                    err_fn,
+                    None,
+                )
+                .map_err(|e| format!("Не удалось создать аудио-поток (i32): {e}"))?
+        }
+        SampleFormat::U32 => {
+            let on_pcm = Arc::clone(&on_pcm);
+            device
+                .build_input_stream(
+                    &stream_config,
+                    move |data: &[u32], _| {
+                        let mono = to_mono_u32(data, channels, &selection);
+                        on_pcm(mono);
+                    },
+                    err_fn,
+                    None,
+                )
+                .map_err(|e| format!("Не удалось создать аудио-поток (u32): {e}"))?
+        }
+        SampleFormat::F64 => {
+            let on_pcm = Arc::clone(&on_pcm);
+            device
+                .build_input_stream(
+                    &stream_config,
+                    move |data: &[f64], _| {
+                        let mono = to_mono_f64(data, channels, &selection);
+                        on_pcm(mono);
+                    },
+                    err_fn,
+                    None,
+                )
+                .map_err(|e| format!("Не удалось создать аудио-поток (f64): {e}"))?
+        }
+        other => {
+            return Err(format!(
+                "Неподдерживаемый формат аудио `{other:?}`. Попробуй другое устройство."
+            ))
+        }
+    };
+
+    stream
+        .play()
+        .map_err(|e| format!("Не удалось запустить аудио-поток: {e}"))?;
+    ui_set_mic(&ui, true);
+    ui_log(
+        &ui,
+        format!(
+            "[microphone] запущен: {device_name} | channels={} | channel={channel_label} | sample_rate={} | format={:?}",
+            stream_config.channels,
+            stream_config.sample_rate.0,
+            sample_format
+        ),
+    );
+    Ok(stream)
+}
+
+enum AudioSource {
+    Microphone(MicrophoneSource),
+    File(FileSource),
+}
+
+impl AudioSource {
+    /// Microphone or file from the config. `send` queues a block and hands it back when the
+    /// queue is full: files offer it again instead of dropping audio when the recognizer lags,
+    /// a real-time microphone drops it. `voice_gate` mutes the microphone only.
+    fn start(
+        config: &AppConfig,
+        config_dir: &Path,
+        device: Option<DeviceSelector>,
+        voice_gate: Arc<AtomicBool>,
+        ui: &UiHandle,
+        send: impl Fn(PcmBlock) -> Option<PcmBlock> + Send + Sync + 'static,
+    ) -> Result<Self, String> {
+        let microphone = &config.microphone;
+        match microphone.source {
+            AudioSourceKind::Device => MicrophoneSource::start(
+                microphone,
+                device,
+                voice_gate,
+                Arc::clone(ui),
+                move |pcm: PcmBlock| {
+                    let _ = send(pcm);
+                },
+            )
+            .map(AudioSource::Microphone),
+            AudioSourceKind::File | AudioSourceKind::Stdin => {
+                let path = match microphone.source {
+                    AudioSourceKind::File => microphone
+                        .input_file
+                        .as_deref()
+                        .map(|p| resolve_path(config_dir, p)),
+                    _ => None,
+                };
+                FileSource::start(
+                    microphone,
+                    path,
+                    microphone.samplerate.unwrap_or(config.speech.sample_rate),
+                    Arc::clone(ui),
+                    send,
+                )
+                .map(AudioSource::File)
+            }
+        }
+    }
+
+    /// `start` without the controller and its voice gate; for the `--stream-to` client and
+    /// `enroll`.
+    fn start_headless(
+        config: &AppConfig,
+        config_dir: &Path,
+        ui: &UiHandle,
+        pcm_tx: Sender<PcmBlock>,
+    ) -> Result<Self, String> {
+        Self::start(
+            config,
+            config_dir,
+            config.microphone.device.clone(),
+            Arc::new(AtomicBool::new(true)),
+            ui,
+            move |pcm: PcmBlock| match pcm_tx.try_send(pcm) {
+                Err(TrySendError::Full(pcm)) => Some(pcm),
+                _ => None,
+            },
+        )
+    }
+
+    fn stop(&mut self) {
+        match self {
+            Self::Microphone(source) => source.stop(),
+            Self::File(source) => source.stop(),
+        }
+    }
+}
+
+/// Which interleaved input channels end up in the mono signal.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub(crate) enum ChannelSelection {
+    /// Average of all channels.
+    #[default]
+    Mix,
+    /// Average of the listed channels (0-based).
+    Channels(Vec<usize>),
+}
+
+impl ChannelSelection {
+    /// Parses `mix`, `2` or `1,2` (1-based, as printed on audio interfaces).
+    fn from_config_str(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        if value.is_empty() || value == "mix" || value == "all" {
+            return Some(Self::Mix);
+        }
+        let indices = value
+            .split(',')
+            .map(|part| part.trim().parse::<usize>().ok().filter(|n| *n >= 1).map(|n| n - 1))
+            .collect::<Option<Vec<_>>>()?;
+        Self::from_indices(indices)
+    }
+
+    fn from_indices(mut indices: Vec<usize>) -> Option<Self> {
+        indices.sort_unstable();
+        indices.dedup();
+        if indices.is_empty() {
+            None
+        } else {
+            Some(Self::Channels(indices))
+        }
+    }
+
+    /// Drops channels the stream does not have. Errors when nothing is left.
+    pub(crate) fn fit(&self, channels: usize) -> Result<Self, String> {
+        match self {
+            Self::Mix => Ok(Self::Mix),
+            Self::Channels(indices) => {
+                let kept: Vec<usize> = indices.iter().copied().filter(|i| *i < channels).collect();
+                if kept.is_empty() {
+                    Err(format!(
+                        "microphone.channel={self} недоступен: у входа всего {channels} канал(ов), использую mix"
+                    ))
+                } else {
+                    Ok(Self::Channels(kept))
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ChannelSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mix => f.write_str("mix"),
+            Self::Channels(indices) => {
+                let labels: Vec<String> = indices.iter().map(|i| (i + 1).to_string()).collect();
+                f.write_str(&labels.join(","))
+            }
+        }
+    }
+}
+
+/// Converts each sample to the i16 range and averages the selected channels of every frame.
+fn downmix<T: Copy>(
+    data: &[T],
+    channels: usize,
+    selection: &ChannelSelection,
+    conv: impl Fn(T) -> i32,
+) -> Vec<i16> {
+    let channels = channels.max(1);
+    match selection {
+        ChannelSelection::Mix if channels == 1 => data.iter().map(|v| conv(*v) as i16).collect(),
+        ChannelSelection::Mix => data
+            .chunks(channels)
+            .map(|chunk| {
+                let sum: i64 = chunk.iter().map(|v| conv(*v) as i64).sum();
+                (sum / chunk.len() as i64) as i16
+            })
+            .collect(),
+        ChannelSelection::Channels(indices) => data
+            .chunks_exact(channels)
+            .map(|frame| {
+                let sum: i64 = indices
+                    .iter()
+                    .filter_map(|i| frame.get(*i))
+                    .map(|v| conv(*v) as i64)
+                    .sum();
+                (sum / indices.len().max(1) as i64) as i16
+            })
+            .collect(),
+    }
+}
+
+pub(crate) fn to_mono_i8(data: &[i8], channels: usize, selection: &ChannelSelection) -> Vec<i16> {
+    downmix(data, channels, selection, |v| (v as i32) << 8)
+}
+
+pub(crate) fn to_mono_u8(data: &[u8], channels: usize, selection: &ChannelSelection) -> Vec<i16> {
+    downmix(data, channels, selection, |v| ((v as i32) - 128) << 8)
+}
+
+pub(crate) fn to_mono_i16(data: &[i16], channels: usize, selection: &ChannelSelection) -> Vec<i16> {
+    downmix(data, channels, selection, |v| v as i32)
+}
+
+pub(crate) fn to_mono_u16(data: &[u16], channels: usize, selection: &ChannelSelection) -> Vec<i16> {
+    downmix(data, channels, selection, |v| v as i32 - 32768)
+}
+
+pub(crate) fn to_mono_i32(data: &[i32], channels: usize, selection: &ChannelSelection) -> Vec<i16> {
+    downmix(data, channels, selection, |v| v >> 16)
+}
+
+pub(crate) fn to_mono_u32(data: &[u32], channels: usize, selection: &ChannelSelection) -> Vec<i16> {
+    downmix(data, channels, selection, |v| (((v as i64) - 2_147_483_648_i64) >> 16) as i32)
+}
+
+pub(crate) fn to_mono_f32(data: &[f32], channels: usize, selection: &ChannelSelection) -> Vec<i16> {
+    downmix(data, channels, selection, |v| (v.clamp(-1.0, 1.0) * i16::MAX as f32) as i32)
+}
+
+pub(crate) fn to_mono_f64(data: &[f64], channels: usize, selection: &ChannelSelection) -> Vec<i16> {
+    downmix(data, channels, selection, |v| (v.clamp(-1.0, 1.0) * i16::MAX as f64) as i32)
+}
+
+fn spawn_audio_pipeline_worker(
+    model_sample_rate: u32,
+    vad: VadConfig,
+    ui: UiHandle,
+    shutdown: Arc<AtomicBool>,
+    raw_rx: Receiver<PipelineInput>,
+    pcm_tx: Sender<RecognizerInput>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut resampler: Option<Resampler> = None;
+        let mut last_input_rate = 0u32;
+        let mut gate = if vad.enabled {
+            ui_set_listening(&ui, Some(false));
+            ui_log(
+                &ui,
+                format!(
+                    "[vad] включен: threshold={}dB, hangover={}ms, preroll={}ms, max_zcr={}",
+                    vad.threshold_db, vad.hangover_ms, vad.preroll_ms, vad.max_zcr
+                ),
+            );
+            Some(VoiceActivityGate::new(&vad, model_sample_rate))
+        } else {
+            None
+        };
+
+        loop {
+            match raw_rx.recv_timeout(Duration::from_millis(200)) {
+                Ok(PipelineInput::Control(control)) => {
+                    if let Some(gate) = gate.as_mut().filter(|g| g.is_open()) {
+                        gate.reset();
+                        ui_set_listening(&ui, Some(false));
+                    }
+                    if pcm_tx.send(control).is_err() {
+                        break;
+                    }
+                }
+                Ok(PipelineInput::Block(block)) => {
+                    if block.sample_rate != last_input_rate {
+                        last_input_rate = block.sample_rate;
+                        if block.sample_rate == model_sample_rate {
+                            resampler = None;
+                            ui_log(
+                                &ui,
+                                format!("[audio] sample_rate: вход={} Hz = модель, без ресемплинга", block.sample_rate),
+                            );
+                        } else {
+                            let r = Resampler::new(block.sample_rate, model_sample_rate);
+                            ui_log(
+                                &ui,
+                                format!(
+                                    "[audio] sample_rate: вход={} Hz -> модель={} Hz (ресемплинг)",
+                                    r.input_rate(),
+                                    r.output_rate()
+                                ),
+                            );
+                            resampler = Some(r);
+                        }
+                    }
+
+                    let pcm = match resampler.as_mut() {
+                        Some(r) => r.process(&block.samples),
+                        None => block.samples,
+                    };
+                    let Some(gate) = gate.as_mut() else {
+                        if !pcm.is_empty() && pcm_tx.send(RecognizerInput::Pcm(pcm)).is_err() {
+                            break;
+                        }
+                        continue;
+                    };
+
+                    let output = gate.process(&pcm);
+                    if output.opened || output.closed {
+                        ui_set_listening(&ui, Some(gate.is_open()));
+                    }
+                    if !output.forwarded.is_empty()
+                        && pcm_tx.send(RecognizerInput::Pcm(output.forwarded)).is_err()
+                    {
+                        break;
+                    }
+                    if output.closed && pcm_tx.send(RecognizerInput::Flush).is_err() {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if shutdown.load(Ordering::Relaxed) {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    })
+}
+
+/// Loads the configured speech engine once and shares it between the microphone recognizer
+/// and network connections.
+struct EngineCache {
+    config: SpeechConfig,
+    config_dir: PathBuf,
+    slot: Mutex<EngineSlot>,
+    /// Mirror of `EngineSlot::generation` that sessions poll without taking the lock.
+    generation: AtomicU64,
+    switching: AtomicBool,
+}
+
+struct EngineSlot {
+    engine: Option<Arc<dyn SpeechEngine>>,
+    /// `speech.model_path` of the loaded (or next to load) Vosk model.
+    model_path: String,
+    /// Bumped on every model switch; sessions opened on an older one reopen their streams.
+    generation: u64,
+}
+
+impl EngineCache {
+    fn new(config: SpeechConfig, config_dir: PathBuf) -> Self {
+        let model_path = config.model_path.clone();
+        Self {
+            config,
+            config_dir,
+            slot: Mutex::new(EngineSlot {
+                engine: None,
+                model_path,
+                generation: 0,
+            }),
+            generation: AtomicU64::new(0),
+            switching: AtomicBool::new(false),
+        }
+    }
+
+    fn get(&self) -> Result<(Arc<dyn SpeechEngine>, u64), String> {
+        let mut slot = self.slot.lock().map_err(|_| "engine mutex poisoned".to_string())?;
+        if let Some(engine) = slot.engine.as_ref() {
+            return Ok((Arc::clone(engine), slot.generation));
+        }
+        let engine = self.load(&slot.model_path)?;
+        slot.engine = Some(Arc::clone(&engine));
+        Ok((engine, slot.generation))
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    fn model_path(&self) -> PathBuf {
+        let model_path = match self.slot.lock() {
+            Ok(slot) => slot.model_path.clone(),
+            Err(_) => self.config.model_path.clone(),
+        };
+        resolve_path(&self.config_dir, &model_path)
+    }
+
+    /// Loads the Vosk model at `model_path` (as written in `speech.model_path`) and makes open
+    /// sessions move over to it. The old model keeps serving until the new one is ready.
+    fn switch_model(&self, model_path: &str) -> Result<(), String> {
+        if self.switching.swap(true, Ordering::SeqCst) {
+            return Err("другая модель еще загружается".to_string());
+        }
+        let loaded = self.load(model_path);
+        self.switching.store(false, Ordering::SeqCst);
+        let engine = loaded?;
+
+        let mut slot = self.slot.lock().map_err(|_| "engine mutex poisoned".to_string())?;
+        slot.engine = Some(engine);
+        slot.model_path = model_path.to_string();
+        slot.generation += 1;
+        self.generation.store(slot.generation, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn load(&self, model_path: &str) -> Result<Arc<dyn SpeechEngine>, String> {
+        let mut config = self.config.clone();
+        config.model_path = model_path.to_string();
+        speech::load_engine(&config, &self.config_dir)
+    }
+}
+
+/// One recognizer stream for one speaker: feeds PCM to the engine and turns its output into
+/// [`RecognizedPhraseEvent`]s. Follows model switches in [`EngineCache`] on its own.
+struct SpeechSession {
+    engines: Arc<EngineCache>,
+    generation: u64,
+    stream: Box<dyn SpeechStream>,
+    engine: &'static str,
+    sample_rate: u32,
+    grammar_phrases: Option<Vec<String>>,
+    speaker_id: String,
+    /// Set for the shared microphone: finals go to the enrolled voice they sound like.
+    identifier: Option<Arc<SpeakerIdentifier>>,
+    log_partials: bool,
+    last_partial_sent: String,
+    text_tx: Sender<RecognizedPhraseEvent>,
+    ui: UiHandle,
+}
+
+impl SpeechSession {
+    fn new(
+        engines: Arc<EngineCache>,
+        sample_rate: u32,
+        grammar_phrases: Option<Vec<String>>,
+        speaker_id: &str,
+        log_partials: bool,
+        text_tx: Sender<RecognizedPhraseEvent>,
+        ui: UiHandle,
+    ) -> Result<Self, String> {
+        let (engine, generation) = engines.get()?;
+        let stream = engine.open_stream(sample_rate, grammar_phrases.as_deref(), &ui)?;
+        Ok(Self {
+            engines,
+            generation,
+            stream,
+            engine: engine.name(),
+            sample_rate,
+            grammar_phrases,
+            speaker_id: speaker_id.to_string(),
+            identifier: None,
+            log_partials,
+            last_partial_sent: String::new(),
+            text_tx,
+            ui,
+        })
+    }
+
+    /// Reopens the stream on the current engine with the same rate and grammar. The
+    /// utterance in progress is finalized on the old model first.
+    fn follow_engine_switch(&mut self) {
+        if self.engines.generation() == self.generation {
+            return;
+        }
+        let reopened = self.engines.get().and_then(|(engine, generation)| {
+            let stream = engine.open_stream(self.sample_rate, self.grammar_phrases.as_deref(), &self.ui)?;
+            Ok((engine, generation, stream))
+        });
+        match reopened {
+            Ok((engine, generation, stream)) => {
+                self.flush();
+                self.stream = stream;
+                self.engine = engine.name();
+                self.generation = generation;
+                ui_log(&self.ui, format!("[recognizer] {}: новая модель подключена", self.speaker_id));
+            }
+            Err(err) => {
+                // Stay on the old stream; the next switch retries.
+                self.generation = self.engines.generation();
+                ui_log(&self.ui, format!("[recognizer-error] {}: {err}", self.speaker_id));
+            }
+        }
+    }
+
+    /// Whose voice a final result is: the closest enrolled speaker within
+    /// `speech.speaker_max_distance`, otherwise the session's own speaker.
+    fn identify(&self, hypothesis: &Hypothesis) -> String {
+        let (Some(identifier), Some(voice)) = (&self.identifier, &hypothesis.speaker) else {
+            return self.speaker_id.clone();
+        };
+        match identifier.voiceprints.closest(&voice.vector) {
+            Some((id, distance)) if distance <= identifier.max_distance => {
+                ui_log(
+                    &self.ui,
+                    format!("[speaker-id] {}: «{}» -> {id} ({distance:.2})", self.speaker_id, hypothesis.text),
+                );
+                id.to_string()
+            }
+            Some((id, distance)) => {
+                ui_log(
+                    &self.ui,
+                    format!(
+                        "[speaker-id] {}: голос не узнан, ближе всех {id} ({distance:.2} > {:.2})",
+                        self.speaker_id, identifier.max_distance
+                    ),
+                );
+                self.speaker_id.clone()
+            }
+            None => self.speaker_id.clone(),
+        }
+    }
+
+    fn emit(&self, hypothesis: Hypothesis, is_partial: bool) {
+        let speaker_id = if is_partial {
+            self.speaker_id.clone()
+        } else {
+            self.identify(&hypothesis)
+        };
+        let _ = self.text_tx.send(RecognizedPhraseEvent {
+            speaker_id,
+            text: hypothesis.text,
+            is_partial,
+            confidence: hypothesis.confidence,
+            words: hypothesis.words,
+            alternatives: hypothesis.alternatives,
+        });
+    }
+
+    fn accept(&mut self, pcm: &[i16]) {
+        self.follow_engine_switch();
+        let outputs = match self.stream.accept(pcm) {
+            Ok(outputs) => outputs,
+            Err(err) => {
+                ui_log(&self.ui, format!("[recognizer-error] {err}"));
+                return;
+            }
+        };
+        for output in outputs {
+            match output {
+                SpeechOutput::Final(hypothesis) => {
+                    self.last_partial_sent.clear();
+                    self.emit(hypothesis, false);
+                }
+                SpeechOutput::Partial(hypothesis) => {
+                    if self.log_partials {
+                        ui_log(&self.ui, format!("[partial:{}] {}", self.speaker_id, hypothesis.text));
+                    }
+                    // Partials have no voice yet; with identification blocks wait for the final.
+                    if self.identifier.is_none() && hypothesis.text != self.last_partial_sent {
+                        self.last_partial_sent = hypothesis.text.clone();
+                        self.emit(hypothesis, true);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finalizes whatever was said so far.
+    fn flush(&mut self) {
+        self.last_partial_sent.clear();
+        if let Some(hypothesis) = self.stream.flush() {
+            self.emit(hypothesis, false);
+        }
+    }
+
+    /// Drops the unfinished utterance.
+    fn reset(&mut self) {
+        self.last_partial_sent.clear();
+        self.stream.reset();
+    }
+}
+
+fn spawn_recognizer_worker(
+    engines: Arc<EngineCache>,
+    sample_rate: u32,
+    log_partials: bool,
+    grammar_phrases: Option<Vec<String>>,
+    ui: UiHandle,
+    shutdown: Arc<AtomicBool>,
+    pcm_rx: Receiver<RecognizerInput>,
+    text_tx: Sender<RecognizedPhraseEvent>,
+    recorder: Option<Arc<SessionRecorder>>,
+    identifier: Option<Arc<SpeakerIdentifier>>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let session = SpeechSession::new(
+            engines,
+            sample_rate,
+            grammar_phrases,
+            MIC_SPEAKER_ID,
+            log_partials,
+            text_tx,
+            Arc::clone(&ui),
+        );
+        let mut session = match session {
+            Ok(session) => session,
+            Err(err) => {
+                ui_set_rec(&ui, false);
+                ui_log(&ui, format!("[recognizer-error] {err}"));
+                return;
             }
         };
-        recognizer.set_words(false);
-        recognizer.set_partial_words(false);
+        session.identifier = identifier;
         ui_set_rec(&ui, true);
-        ui_log(&ui, "[recognizer] запущен");
-        let mut last_partial_sent = String::new();
+        ui_log(&ui, format!("[recognizer] запущен: {}", session.engine));
 
         loop {
             match pcm_rx.recv_timeout(Duration::from_millis(200)) {
-                Ok(chunk) => match recognizer.accept_waveform(&chunk) {
-                    Ok(DecodingState::Finalized) => {
-                        if let Some(text) = extract_complete_text(recognizer.result()) {
-                            last_partial_sent.clear();
-                            let _ = text_tx.send(RecognizedPhraseEvent {
-                                speaker_id: MIC_SPEAKER_ID.to_string(),
-                                text,
-                                is_partial: false,
-                            });
-                        }
-                    }
-                    Ok(DecodingState::Running) => {
-                        let partial = recognizer.partial_result().partial.to_string();
-                        let partial_trimmed = partial.trim().to_string();
-                        if !partial_trimmed.is_empty() && partial_trimmed != last_partial_sent {
-                            last_partial_sent = partial_trimmed.clone();
-                            let _ = text_tx.send(RecognizedPhraseEvent {
-                                speaker_id: MIC_SPEAKER_ID.to_string(),
-                                text: partial_trimmed.clone(),
-                                is_partial: true,
-                            });
-                        }
-                        if log_partials && !partial_trimmed.is_empty() {
-                                ui_log(&ui, format!("[partial:{MIC_SPEAKER_ID}] {}", partial));
-                        }
-                    }
-                    Ok(DecodingState::Failed) => {
-                        ui_log(&ui, "[recognizer-error] Vosk decoding failed");
-                    }
-                    Err(err) => {
-                        ui_log(&ui, format!("[recognizer-error] accept_waveform: {err}"));
+                Ok(RecognizerInput::Flush) => session.flush(),
+                Ok(RecognizerInput::Reset) => session.reset(),
+                Ok(RecognizerInput::Pcm(chunk)) => {
+                    if let Some(recorder) = &recorder {
+                        recorder.write_pcm(&chunk);
                     }
-                },
+                    session.accept(&chunk);
+                }
                 Err(RecvTimeoutError::Timeout) => {
                     if shutdown.load(Ordering::Relaxed) {
                         break;
@@ -2049,36 +4780,12 @@ fn spawn_recognizer_worker(
             }
         }
 
-        if let Some(text) = extract_complete_text(recognizer.final_result()) {
-            let _ = text_tx.send(RecognizedPhraseEvent {
-                speaker_id: MIC_SPEAKER_ID.to_string(),
-                text,
-                is_partial: false,
-            });
-        }
+        session.flush();
         ui_set_rec(&ui, false);
         ui_log(&ui, "[recognizer] остановлен");
     })
 }
 
-fn extract_complete_text(result: CompleteResult<'_>) -> Option<String> {
-    match result {
-        CompleteResult::Single(single) => {
-            let text = single.text.trim();
-            if text.is_empty() {
-                None
-            } else {
-                Some(text.to_string())
-            }
-        }
-        CompleteResult::Multiple(multi) => multi
-            .alternatives
-            .first()
-            .map(|a| a.text.trim().to_string())
-            .filter(|t| !t.is_empty()),
-    }
-}
-
 #[derive(Debug)]
 struct RconPacket {
     id: i32,
@@ -2574,6 +5281,19 @@ impl MinecraftRconService {
     }
 }
 
+/// Workers and endpoints started by `BlockDeleteController::start_pipeline`, shut down
+/// together by `stop_pipeline`.
+struct RunningPipeline {
+    raw_tx: Sender<PipelineInput>,
+    microphone: AudioSource,
+    network: Option<NetworkServer>,
+    recorder: Option<Arc<SessionRecorder>>,
+    pipeline_handle: thread::JoinHandle<()>,
+    recognizer_handle: thread::JoinHandle<()>,
+    event_worker: thread::JoinHandle<()>,
+    presence_worker: thread::JoinHandle<()>,
+}
+
 pub(crate) struct BlockDeleteController {
     config: AppConfig,
     config_path: PathBuf,
@@ -2581,25 +5301,316 @@ pub(crate) struct BlockDeleteController {
     catalog: BlockCatalog,
     rcon: Arc<MinecraftRconService>,
     ui: UiHandle,
+    voice_gate: Arc<AtomicBool>,
+    pipeline_tx: Mutex<Option<Sender<PipelineInput>>>,
+    mic_device: Mutex<Option<DeviceSelector>>,
+    mic_commands: Mutex<Option<Sender<MicCommand>>>,
+    engines: Mutex<Option<Arc<EngineCache>>>,
 }
 
 impl BlockDeleteController {
     pub(crate) fn new(config: AppConfig, config_path: PathBuf, config_dir: PathBuf, ui: UiHandle) -> Result<Self, String> {
-        let blocks_path = resolve_path(&config_dir, &config.blocks.file);
-        let catalog = BlockCatalog::load(
-            &blocks_path,
-            &config.blocks.extra_aliases,
-            &config.blocks.shared_aliases,
-        )?;
+        let catalog = BlockCatalog::from_config(&config, &config_dir)?;
         let rcon = Arc::new(MinecraftRconService::new(&config.minecraft, Arc::clone(&ui))?);
-        Ok(Self {
+        let voice_gate = Arc::new(AtomicBool::new(
+            config.microphone.activation == MicActivation::Always,
+        ));
+        let mic_device = Mutex::new(config.microphone.device.clone());
+        let controller = Self {
             config,
             config_path,
             config_dir,
             catalog,
             rcon,
             ui,
-        })
+            voice_gate,
+            pipeline_tx: Mutex::new(None),
+            mic_device,
+            mic_commands: Mutex::new(None),
+            engines: Mutex::new(None),
+        };
+        ui_log(
+            &controller.ui,
+            format!(
+                "[locales] {}, модель {}{}",
+                controller
+                    .config
+                    .blocks
+                    .locales
+                    .iter()
+                    .map(|l| if l.stem { format!("{} (стемминг)", l.locale) } else { l.locale.clone() })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                controller.config.speech.model_path,
+                if controller.config.speech.model_auto {
+                    " (speech.model_path не задан — выбрана по языку из speech.models_dir)"
+                } else {
+                    ""
+                }
+            ),
+        );
+        controller.check_model_language(&resolve_path(&controller.config_dir, &controller.config.speech.model_path));
+        Ok(controller)
+    }
+
+    /// Enrolled voices for the microphone session; logs why identification is off when
+    /// `speech.speaker_model_path` is set but it cannot run.
+    fn speaker_identifier(&self) -> Option<Arc<SpeakerIdentifier>> {
+        let speech = &self.config.speech;
+        speech.speaker_model_path.as_ref()?;
+        if speech.engine != SpeechEngineKind::Vosk {
+            ui_log(
+                &self.ui,
+                "[speaker-id-warning] speech.speaker_model_path работает только с speech.engine=vosk".to_string(),
+            );
+            return None;
+        }
+        if speech.max_alternatives > 0 {
+            ui_log(
+                &self.ui,
+                "[speaker-id-warning] при speech.max_alternatives Vosk не дает голос в final, говорящие не определяются"
+                    .to_string(),
+            );
+            return None;
+        }
+        match SpeakerIdentifier::load(speech, &self.config_dir) {
+            Ok(identifier) => identifier.map(|identifier| {
+                ui_log(
+                    &self.ui,
+                    format!(
+                        "[speaker-id] голоса: {} (порог {:.2}), блоки микрофона ищутся только по final",
+                        identifier.voiceprints.speaker_ids().join(", "),
+                        identifier.max_distance
+                    ),
+                );
+                Arc::new(identifier)
+            }),
+            Err(err) => {
+                ui_log(&self.ui, format!("[speaker-id-error] {err}"));
+                None
+            }
+        }
+    }
+
+    /// Warns when a Vosk model, judging by its folder name, speaks none of `blocks.locales`.
+    fn check_model_language(&self, model_dir: &Path) {
+        if self.config.speech.engine != SpeechEngineKind::Vosk {
+            return;
+        }
+        let name = model_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let Some(language) = models::language_from_name(&name) else {
+            return;
+        };
+        let locales = &self.config.blocks.locales;
+        if !locales.iter().any(|l| base_language(&l.locale) == base_language(&language)) {
+            ui_log(
+                &self.ui,
+                format!(
+                    "[locale-warning] модель {name} ({language}) не подходит ни к одной из blocks.locales — блоки не будут находиться"
+                ),
+            );
+        }
+    }
+
+    pub(crate) fn mic_activation(&self) -> (MicActivation, KeyCode) {
+        (
+            self.config.microphone.activation,
+            self.config.microphone.activation_key,
+        )
+    }
+
+    fn attach_pipeline(&self, pipeline_tx: &Sender<PipelineInput>) {
+        if let Ok(mut guard) = self.pipeline_tx.lock() {
+            *guard = Some(pipeline_tx.clone());
+        }
+        if self.config.microphone.activation != MicActivation::Always {
+            ui_set_voice_gate(&self.ui, Some(self.voice_gate.load(Ordering::SeqCst)));
+            ui_log(
+                &self.ui,
+                format!(
+                    "[mic] режим {}: клавиша {}",
+                    self.config.microphone.activation.as_config_str(),
+                    activation_key_label(self.config.microphone.activation_key)
+                ),
+            );
+        }
+    }
+
+    fn detach_pipeline(&self) {
+        if let Ok(mut guard) = self.pipeline_tx.lock() {
+            *guard = None;
+        }
+        if let Ok(mut guard) = self.mic_commands.lock() {
+            *guard = None;
+        }
+        if let Ok(mut guard) = self.engines.lock() {
+            *guard = None;
+        }
+    }
+
+    /// Input devices plus the position of the currently selected one (`None` = system default).
+    pub(crate) fn input_device_choices(&self) -> Result<(Vec<InputDeviceInfo>, Option<usize>), String> {
+        let devices = enumerate_input_devices()?;
+        let selected = self
+            .mic_device
+            .lock()
+            .ok()
+            .and_then(|guard| guard.clone())
+            .and_then(|selector| InputDeviceInfo::find(&devices, &selector));
+        Ok((devices, selected))
+    }
+
+    fn refresh_mic_draft(&self, draft: &mut SettingsDraft) {
+        match self.input_device_choices() {
+            Ok((devices, selected)) => {
+                draft.mic_active = selected.map(|i| i + 1).unwrap_or(0);
+                draft.mic_cursor = draft.mic_active;
+                draft.mic_devices = devices;
+            }
+            Err(err) => {
+                draft.mic_devices.clear();
+                draft.mic_cursor = 0;
+                draft.mic_active = 0;
+                ui_log(&self.ui, format!("[settings-error] {err}"));
+            }
+        }
+    }
+
+    /// Persists the chosen input device and switches the running microphone to it.
+    /// `None` selects the system default device.
+    pub(crate) fn select_input_device(&self, device: Option<&InputDeviceInfo>) -> Result<(), String> {
+        save_microphone_device_to_config(&self.config_path, device.map(|d| d.name.as_str()))?;
+        let selector = device.map(|d| DeviceSelector::Name(d.name.clone()));
+        if let Ok(mut guard) = self.mic_device.lock() {
+            *guard = selector.clone();
+        }
+        let label = device
+            .map(|d| d.name.clone())
+            .unwrap_or_else(|| "по умолчанию".to_string());
+
+        let commands = self.mic_commands.lock().ok().and_then(|guard| guard.clone());
+        match commands {
+            Some(commands) => {
+                commands
+                    .send(MicCommand::SwitchDevice(selector))
+                    .map_err(|_| "микрофон уже остановлен".to_string())?;
+                ui_log(&self.ui, format!("[settings] микрофон: {label}, переключаю..."));
+            }
+            None => ui_log(
+                &self.ui,
+                format!("[settings] микрофон: {label} (применится при microphone.source=device)"),
+            ),
+        }
+        Ok(())
+    }
+
+    /// Models found in `speech.models_dir` plus the position of the one in use.
+    pub(crate) fn model_choices(&self) -> Result<(Vec<ModelInfo>, Option<usize>), String> {
+        let models = discover_models(&resolve_path(&self.config_dir, &self.config.speech.models_dir))?;
+        let current = match self.engines.lock().ok().and_then(|guard| guard.clone()) {
+            Some(engines) => engines.model_path(),
+            None => resolve_path(&self.config_dir, &self.config.speech.model_path),
+        };
+        let selected = models.iter().position(|m| m.is_at(&current));
+        Ok((models, selected))
+    }
+
+    fn refresh_model_draft(&self, draft: &mut SettingsDraft) {
+        match self.model_choices() {
+            Ok((models, selected)) => {
+                draft.model_active = selected;
+                draft.model_cursor = selected.unwrap_or(0);
+                draft.models = models;
+            }
+            Err(err) => {
+                draft.models.clear();
+                draft.model_cursor = 0;
+                draft.model_active = None;
+                ui_log(&self.ui, format!("[settings-error] {err}"));
+            }
+        }
+    }
+
+    /// Persists the chosen model as `speech.model_path` and loads it in the background;
+    /// running recognizers move over once it is ready, keeping their grammar.
+    pub(crate) fn select_model(&self, model: &ModelInfo) -> Result<(), String> {
+        if let Some(problem) = &model.problem {
+            return Err(format!("модель {} не подходит: {problem}", model.name));
+        }
+        let model_path = model_config_path(&self.config.speech.models_dir, &model.name);
+        save_model_path_to_config(&self.config_path, &model_path)?;
+        self.check_model_language(&model.path);
+
+        let engines = self.engines.lock().ok().and_then(|guard| guard.clone());
+        match engines {
+            Some(engines) if self.config.speech.engine == SpeechEngineKind::Vosk => {
+                ui_log(&self.ui, format!("[settings] модель: {}, загружаю...", model.name));
+                let ui = Arc::clone(&self.ui);
+                let name = model.name.clone();
+                thread::spawn(move || match engines.switch_model(&model_path) {
+                    Ok(()) => ui_log(&ui, format!("[settings] модель {name} загружена")),
+                    Err(err) => ui_log(&ui, format!("[settings-error] модель {name}: {err}")),
+                });
+            }
+            _ => ui_log(
+                &self.ui,
+                format!("[settings] модель: {} (применится при запуске с speech.engine=vosk)", model.name),
+            ),
+        }
+        Ok(())
+    }
+
+    /// Opens or closes the push-to-talk/toggle gate. No-op in `always` mode.
+    pub(crate) fn set_voice_gate(&self, open: bool) {
+        let activation = self.config.microphone.activation;
+        if activation == MicActivation::Always || self.voice_gate.swap(open, Ordering::SeqCst) == open {
+            return;
+        }
+        ui_set_voice_gate(&self.ui, Some(open));
+        if activation == MicActivation::Toggle {
+            ui_log(
+                &self.ui,
+                if open { "[mic] микрофон включен" } else { "[mic] микрофон выключен" },
+            );
+        }
+        if open {
+            return;
+        }
+        // Releasing push-to-talk finalizes what was said while the key was held;
+        // muting in toggle mode throws the unfinished utterance away.
+        let control = match activation {
+            MicActivation::PushToTalk => RecognizerInput::Flush,
+            _ => RecognizerInput::Reset,
+        };
+        // Dropping it would let the utterance from before the mute finish after unmute
+        // and delete blocks, so wait for room instead.
+        if let Ok(guard) = self.pipeline_tx.lock() {
+            if let Some(tx) = guard.as_ref() {
+                let what = match control {
+                    RecognizerInput::Flush => "завершить фразу",
+                    _ => "сбросить недосказанную фразу",
+                };
+                // A disconnected queue means the pipeline is gone and there is nothing to finish.
+                if let Err(SendTimeoutError::Timeout(_)) =
+                    tx.send_timeout(PipelineInput::Control(control), GATE_CONTROL_TIMEOUT)
+                {
+                    ui_log(
+                        &self.ui,
+                        format!(
+                            "[mic-warning] очередь аудио занята {} с — не удалось {what}",
+                            GATE_CONTROL_TIMEOUT.as_secs()
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    pub(crate) fn toggle_voice_gate(&self) {
+        self.set_voice_gate(!self.voice_gate.load(Ordering::SeqCst));
     }
 
     fn validate_runtime_config(&self) -> Result<(), String> {
@@ -2609,90 +5620,180 @@ impl BlockDeleteController {
         if !self.config.microphone.enabled {
             return Err("microphone.enabled=false, включи микрофон в config.json".to_string());
         }
-        if self.config.microphone.samplerate != self.config.speech.sample_rate {
-            return Err(
-                "microphone.samplerate должен совпадать с speech.sample_rate (рекомендую 48000)"
-                    .to_string(),
-            );
+        if self.config.speech.sample_rate == 0 {
+            return Err("speech.sample_rate должен быть больше 0".to_string());
         }
-        if self.config.microphone.player_name.trim().is_empty() {
+        if self.config.mic_player_name().is_none() {
             return Err(
-                "microphone.player_name пустой, но microphone.enabled=true. Укажи никнейм."
+                "microphone.player_name и speakers.mic пустые, но microphone.enabled=true. Укажи никнейм."
                     .to_string(),
             );
         }
+        if self.config.microphone.source == AudioSourceKind::File
+            && self.config.microphone.input_file.is_none()
+        {
+            return Err("microphone.source=file, но microphone.input_file не задан".to_string());
+        }
         Ok(())
     }
 
-    pub(crate) fn run(&self) -> Result<(), String> {
-        self.validate_runtime_config()?;
-
-        let grammar_phrases = if self.config.speech.use_grammar {
-            let mut phrases: Vec<String> = self
-                .config
-                .blocks
-                .custom_alias_phrases()
-                .into_iter()
-                .map(|s| normalize_text(&s))
-                .filter(|s| !s.is_empty())
-                .collect();
-            if phrases.is_empty() {
-                phrases = self.catalog.aliases();
+    fn start_audio_source(&self, pcm_tx: &Sender<PipelineInput>) -> Result<AudioSource, String> {
+        let pcm_tx = pcm_tx.clone();
+        let source = AudioSource::start(
+            &self.config,
+            &self.config_dir,
+            self.mic_device.lock().ok().and_then(|guard| guard.clone()),
+            Arc::clone(&self.voice_gate),
+            &self.ui,
+            move |pcm: PcmBlock| match pcm_tx.try_send(PipelineInput::Block(pcm)) {
+                Err(TrySendError::Full(PipelineInput::Block(pcm))) => Some(pcm),
+                _ => None,
+            },
+        )?;
+        if let AudioSource::Microphone(microphone) = &source {
+            if let Ok(mut guard) = self.mic_commands.lock() {
+                *guard = Some(microphone.commands());
             }
-            phrases.sort();
-            phrases.dedup();
-            Some(phrases)
-        } else {
-            None
-        };
+        }
+        Ok(source)
+    }
+
+    /// Phrases the recognizer is limited to with `speech.use_grammar`: the custom aliases (or
+    /// every alias when there are none) plus the wake, negation and cancel phrases.
+    fn grammar_phrases(&self) -> Option<Vec<String>> {
+        if !self.config.speech.use_grammar {
+            return None;
+        }
+        let mut phrases: Vec<String> = self
+            .config
+            .blocks
+            .custom_alias_phrases()
+            .into_iter()
+            .map(|s| self.catalog.normalize(&s))
+            .filter(|s| !s.is_empty())
+            .collect();
+        if phrases.is_empty() {
+            phrases = self.catalog.aliases();
+        }
+        // Wake, negation and cancel phrases must be in the grammar, or the recognizer can never hear them.
+        let speech = &self.config.speech;
+        phrases.extend(
+            speech
+                .wake_phrases
+                .iter()
+                .chain(&speech.negation_words)
+                .chain(&speech.cancel_phrases)
+                .map(|s| self.catalog.normalize(s))
+                .filter(|s| !s.is_empty()),
+        );
+        phrases.sort();
+        phrases.dedup();
+        Some(phrases)
+    }
 
+    /// Starts everything between the audio source and RCON: recorder, audio pipeline,
+    /// recognizer, network server, event and presence workers.
+    fn start_pipeline(&self, shutdown: &Arc<AtomicBool>) -> Result<RunningPipeline, String> {
         ui_log(
             &self.ui,
             format!(
-                "[startup] блоков={}, microphone_enabled={}, fuzzy_threshold={}",
+                "[startup] блоков={}, microphone_enabled={}, fuzzy_threshold={}, model_sample_rate={}",
                 self.catalog.alias_count(),
                 self.config.microphone.enabled,
-                self.config.speech.fuzzy_threshold
+                self.config.speech.fuzzy_threshold,
+                self.config.speech.sample_rate
             ),
         );
 
-        let shutdown = Arc::new(AtomicBool::new(false));
-        {
-            let shutdown = Arc::clone(&shutdown);
-            ctrlc::set_handler(move || {
-                shutdown.store(true, Ordering::SeqCst);
-            })
-            .map_err(|e| format!("Не удалось установить Ctrl+C handler: {e}"))?;
-        }
-
-        let (pcm_tx, pcm_rx) = bounded::<Vec<i16>>(512);
+        let grammar_phrases = self.grammar_phrases();
+        let recorder = self.start_recorder();
+        let (raw_tx, raw_rx) = bounded::<PipelineInput>(512);
+        let (pcm_tx, pcm_rx) = bounded::<RecognizerInput>(512);
         let (text_tx, text_rx) = bounded::<RecognizedPhraseEvent>(512);
+        let pipeline_handle = spawn_audio_pipeline_worker(
+            self.config.speech.sample_rate,
+            self.config.microphone.vad.clone(),
+            Arc::clone(&self.ui),
+            Arc::clone(shutdown),
+            raw_rx,
+            pcm_tx,
+        );
+        let engines = Arc::new(EngineCache::new(self.config.speech.clone(), self.config_dir.clone()));
+        if let Ok(mut guard) = self.engines.lock() {
+            *guard = Some(Arc::clone(&engines));
+        }
+        let network = self.start_network_server(&engines, grammar_phrases.clone(), &text_tx);
         let recognizer_handle = spawn_recognizer_worker(
-            resolve_path(&self.config_dir, &self.config.speech.model_path),
+            Arc::clone(&engines),
             self.config.speech.sample_rate,
             self.config.speech.log_partials,
             grammar_phrases,
             Arc::clone(&self.ui),
-            Arc::clone(&shutdown),
+            Arc::clone(shutdown),
             pcm_rx,
             text_tx,
+            recorder.clone(),
+            self.speaker_identifier(),
         );
 
-        let mut microphone = MicrophoneSource::start(
-            self.config.microphone.samplerate,
-            self.config.microphone.blocksize,
-            &self.config.microphone.device,
-            Arc::clone(&self.ui),
-            {
-                let pcm_tx = pcm_tx.clone();
-                move |pcm: Vec<i16>| {
-                    let _ = pcm_tx.try_send(pcm);
-                }
-            },
-        )?;
+        let microphone = self.start_audio_source(&raw_tx)?;
+        self.attach_pipeline(&raw_tx);
+
+        let event_worker = self.spawn_event_worker(Arc::clone(shutdown), text_rx, recorder.clone());
+        let presence_worker = self.spawn_presence_watcher(Arc::clone(shutdown));
+
+        Ok(RunningPipeline {
+            raw_tx,
+            microphone,
+            network,
+            recorder,
+            pipeline_handle,
+            recognizer_handle,
+            event_worker,
+            presence_worker,
+        })
+    }
+
+    fn stop_pipeline(&self, pipeline: RunningPipeline) {
+        let RunningPipeline {
+            raw_tx,
+            mut microphone,
+            mut network,
+            recorder,
+            pipeline_handle,
+            recognizer_handle,
+            event_worker,
+            presence_worker,
+        } = pipeline;
+        self.detach_pipeline();
+        drop(raw_tx);
+        microphone.stop();
+        ui_set_mic(&self.ui, false);
+        if let Some(network) = network.as_mut() {
+            network.stop();
+        }
+        self.rcon.close();
+        let _ = pipeline_handle.join();
+        let _ = recognizer_handle.join();
+        if let Some(recorder) = &recorder {
+            recorder.finish();
+        }
+        drop(event_worker);
+        drop(presence_worker);
+    }
+
+    pub(crate) fn run(&self) -> Result<(), String> {
+        self.validate_runtime_config()?;
 
-        let event_worker = self.spawn_event_worker(Arc::clone(&shutdown), text_rx);
-        let presence_worker = self.spawn_presence_watcher(Arc::clone(&shutdown));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        {
+            let shutdown = Arc::clone(&shutdown);
+            ctrlc::set_handler(move || {
+                shutdown.store(true, Ordering::SeqCst);
+            })
+            .map_err(|e| format!("Не удалось установить Ctrl+C handler: {e}"))?;
+        }
+        let pipeline = self.start_pipeline(&shutdown)?;
 
         let mut tui = TuiGuard::enter()?;
         let mut controls = TuiControls {
@@ -2701,6 +5802,18 @@ impl BlockDeleteController {
             settings_field: SettingsField::Host,
             settings_editing: false,
             settings_tab: SettingsTab::Connection,
+            matches_open: false,
+            activation_hint: match self.config.microphone.activation {
+                MicActivation::Always => None,
+                MicActivation::PushToTalk => Some((
+                    activation_key_label(self.config.microphone.activation_key),
+                    "говорить",
+                )),
+                MicActivation::Toggle => Some((
+                    activation_key_label(self.config.microphone.activation_key),
+                    "вкл/выкл микрофон",
+                )),
+            },
         };
         let mut settings_draft = SettingsDraft {
             host: self.config.minecraft.rcon_host.clone(),
@@ -2708,20 +5821,64 @@ impl BlockDeleteController {
             password: self.config.minecraft.rcon_password.clone(),
             player_name: self.config.microphone.player_name.clone(),
             ui_mode: self.config.ui.mode.unwrap_or(UiMode::Tui),
+            mic_devices: Vec::new(),
+            mic_cursor: 0,
+            mic_active: 0,
+            models: Vec::new(),
+            model_cursor: 0,
+            model_active: None,
         };
         let mut restart_after_tui_exit = false;
+        let (activation, activation_key) = self.mic_activation();
+        let mut ptt_pressed_at: Option<Instant> = None;
+        if activation == MicActivation::PushToTalk && !tui.enable_key_release_events() {
+            ui_log(
+                &self.ui,
+                "[mic-warning] терминал не сообщает об отпускании клавиш, push-to-talk отпускается по таймауту",
+            );
+        }
         ui_log(&self.ui, "[ui] q - выйти");
 
         while !shutdown.load(Ordering::Relaxed) {
             tui.draw(&self.ui, &controls, &settings_draft)?;
+            if let Some(pressed_at) = ptt_pressed_at {
+                if !tui.key_release_events && pressed_at.elapsed() > PTT_AUTO_RELEASE {
+                    ptt_pressed_at = None;
+                    self.set_voice_gate(false);
+                }
+            }
             if event::poll(Duration::from_millis(100)).map_err(|e| format!("event poll error: {e}"))? {
                 if let CEvent::Key(key) =
                     event::read().map_err(|e| format!("event read error: {e}"))?
                 {
+                    if activation != MicActivation::Always
+                        && key.code == activation_key
+                        && !controls.settings_open
+                    {
+                        match (activation, key.kind) {
+                            (MicActivation::PushToTalk, KeyEventKind::Release) => {
+                                ptt_pressed_at = None;
+                                self.set_voice_gate(false);
+                            }
+                            (MicActivation::PushToTalk, _) => {
+                                ptt_pressed_at = Some(Instant::now());
+                                self.set_voice_gate(true);
+                            }
+                            (_, KeyEventKind::Press) => self.toggle_voice_gate(),
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    if key.kind == KeyEventKind::Release {
+                        continue;
+                    }
                     match key.code {
                         KeyCode::Char('q') | KeyCode::Char('Q') => {
                             shutdown.store(true, Ordering::SeqCst);
                         }
+                        KeyCode::Char('m') | KeyCode::Char('M') if !controls.settings_open => {
+                            controls.matches_open = !controls.matches_open;
+                        }
                         KeyCode::Left => {
                             if controls.settings_open && !controls.settings_editing {
                                 if controls.settings_field == SettingsField::UiMode {
@@ -2732,6 +5889,11 @@ impl BlockDeleteController {
                                 } else {
                                     controls.settings_tab = controls.settings_tab.prev();
                                     controls.settings_field = default_field_for_tab(controls.settings_tab);
+                                    match controls.settings_tab {
+                                        SettingsTab::Microphone => self.refresh_mic_draft(&mut settings_draft),
+                                        SettingsTab::Model => self.refresh_model_draft(&mut settings_draft),
+                                        _ => {}
+                                    }
                                 }
                             } else if !controls.settings_open {
                                 controls.selected = controls.selected.prev();
@@ -2747,19 +5909,36 @@ impl BlockDeleteController {
                                 } else {
                                     controls.settings_tab = controls.settings_tab.next();
                                     controls.settings_field = default_field_for_tab(controls.settings_tab);
+                                    match controls.settings_tab {
+                                        SettingsTab::Microphone => self.refresh_mic_draft(&mut settings_draft),
+                                        SettingsTab::Model => self.refresh_model_draft(&mut settings_draft),
+                                        _ => {}
+                                    }
                                 }
                             } else if !controls.settings_open {
                                 controls.selected = controls.selected.next();
                             }
                         }
                         KeyCode::Up => {
-                            if controls.settings_open && !controls.settings_editing {
+                            if controls.settings_open && controls.settings_tab == SettingsTab::Microphone {
+                                let rows = settings_draft.mic_devices.len() + 1;
+                                settings_draft.mic_cursor = (settings_draft.mic_cursor + rows - 1) % rows;
+                            } else if controls.settings_open && controls.settings_tab == SettingsTab::Model {
+                                let rows = settings_draft.models.len().max(1);
+                                settings_draft.model_cursor = (settings_draft.model_cursor + rows - 1) % rows;
+                            } else if controls.settings_open && !controls.settings_editing {
                                 controls.settings_field =
                                     settings_field_prev_in_tab(controls.settings_field, controls.settings_tab);
                             }
                         }
                         KeyCode::Down => {
-                            if controls.settings_open && !controls.settings_editing {
+                            if controls.settings_open && controls.settings_tab == SettingsTab::Microphone {
+                                let rows = settings_draft.mic_devices.len() + 1;
+                                settings_draft.mic_cursor = (settings_draft.mic_cursor + 1) % rows;
+                            } else if controls.settings_open && controls.settings_tab == SettingsTab::Model {
+                                let rows = settings_draft.models.len().max(1);
+                                settings_draft.model_cursor = (settings_draft.model_cursor + 1) % rows;
+                            } else if controls.settings_open && !controls.settings_editing {
                                 controls.settings_field =
                                     settings_field_next_in_tab(controls.settings_field, controls.settings_tab);
                             }
@@ -2773,7 +5952,23 @@ impl BlockDeleteController {
                         }
                         KeyCode::Enter => {
                             if controls.settings_open {
-                                if controls.settings_field == SettingsField::UiMode {
+                                if controls.settings_field == SettingsField::Microphone {
+                                    let device = settings_draft
+                                        .mic_cursor
+                                        .checked_sub(1)
+                                        .and_then(|i| settings_draft.mic_devices.get(i));
+                                    match self.select_input_device(device) {
+                                        Ok(()) => settings_draft.mic_active = settings_draft.mic_cursor,
+                                        Err(err) => ui_log(&self.ui, format!("[settings-error] {err}")),
+                                    }
+                                } else if controls.settings_field == SettingsField::Model {
+                                    if let Some(model) = settings_draft.models.get(settings_draft.model_cursor) {
+                                        match self.select_model(model) {
+                                            Ok(()) => settings_draft.model_active = Some(settings_draft.model_cursor),
+                                            Err(err) => ui_log(&self.ui, format!("[settings-error] {err}")),
+                                        }
+                                    }
+                                } else if controls.settings_field == SettingsField::UiMode {
                                     settings_draft.ui_mode = match settings_draft.ui_mode {
                                         UiMode::Tui => UiMode::Qt,
                                         UiMode::Qt => UiMode::Tui,
@@ -2814,7 +6009,7 @@ impl BlockDeleteController {
                                     SettingsField::PlayerName => {
                                         settings_draft.player_name.pop();
                                     }
-                                    SettingsField::UiMode => {}
+                                    SettingsField::UiMode | SettingsField::Microphone | SettingsField::Model => {}
                                 }
                             }
                         }
@@ -2879,6 +6074,7 @@ impl BlockDeleteController {
                                             // ignore accidental russian layout noise
                                         }
                                     }
+                                    SettingsField::Microphone | SettingsField::Model => {}
                                 }
                             }
                         }
@@ -2886,15 +6082,9 @@ impl BlockDeleteController {
                     }
                 }
             }
-        }
-
-        drop(pcm_tx);
-        microphone.stop();
-        ui_set_mic(&self.ui, false);
-        self.rcon.close();
-        let _ = recognizer_handle.join();
-        drop(event_worker);
-        drop(presence_worker);
+        }
+
+        self.stop_pipeline(pipeline);
         drop(tui);
         if restart_after_tui_exit {
             restart_current_process()?;
@@ -2907,76 +6097,13 @@ impl BlockDeleteController {
         shutdown: Arc<AtomicBool>,
     ) -> Result<(), String> {
         self.validate_runtime_config()?;
-
-        let grammar_phrases = if self.config.speech.use_grammar {
-            let mut phrases: Vec<String> = self
-                .config
-                .blocks
-                .custom_alias_phrases()
-                .into_iter()
-                .map(|s| normalize_text(&s))
-                .filter(|s| !s.is_empty())
-                .collect();
-            if phrases.is_empty() {
-                phrases = self.catalog.aliases();
-            }
-            phrases.sort();
-            phrases.dedup();
-            Some(phrases)
-        } else {
-            None
-        };
-
-        ui_log(
-            &self.ui,
-            format!(
-                "[startup] блоков={}, microphone_enabled={}, fuzzy_threshold={}",
-                self.catalog.alias_count(),
-                self.config.microphone.enabled,
-                self.config.speech.fuzzy_threshold
-            ),
-        );
-
-        let (pcm_tx, pcm_rx) = bounded::<Vec<i16>>(512);
-        let (text_tx, text_rx) = bounded::<RecognizedPhraseEvent>(512);
-        let recognizer_handle = spawn_recognizer_worker(
-            resolve_path(&self.config_dir, &self.config.speech.model_path),
-            self.config.speech.sample_rate,
-            self.config.speech.log_partials,
-            grammar_phrases,
-            Arc::clone(&self.ui),
-            Arc::clone(&shutdown),
-            pcm_rx,
-            text_tx,
-        );
-
-        let mut microphone = MicrophoneSource::start(
-            self.config.microphone.samplerate,
-            self.config.microphone.blocksize,
-            &self.config.microphone.device,
-            Arc::clone(&self.ui),
-            {
-                let pcm_tx = pcm_tx.clone();
-                move |pcm: Vec<i16>| {
-                    let _ = pcm_tx.try_send(pcm);
-                }
-            },
-        )?;
-
-        let event_worker = self.spawn_event_worker(Arc::clone(&shutdown), text_rx);
-        let presence_worker = self.spawn_presence_watcher(Arc::clone(&shutdown));
+        let pipeline = self.start_pipeline(&shutdown)?;
 
         while !shutdown.load(Ordering::Relaxed) {
             thread::sleep(Duration::from_millis(100));
         }
 
-        drop(pcm_tx);
-        microphone.stop();
-        ui_set_mic(&self.ui, false);
-        self.rcon.close();
-        let _ = recognizer_handle.join();
-        drop(event_worker);
-        drop(presence_worker);
+        self.stop_pipeline(pipeline);
         Ok(())
     }
 
@@ -3041,187 +6168,291 @@ impl BlockDeleteController {
         Ok(SaveSettingsOutcome { restart_required })
     }
 
+    fn start_network_server(
+        &self,
+        engines: &Arc<EngineCache>,
+        grammar_phrases: Option<Vec<String>>,
+        text_tx: &Sender<RecognizedPhraseEvent>,
+    ) -> Option<NetworkServer> {
+        if !self.config.network.enabled {
+            return None;
+        }
+        let context = NetworkContext {
+            engines: Arc::clone(engines),
+            model_sample_rate: self.config.speech.sample_rate,
+            grammar_phrases,
+            log_partials: self.config.speech.log_partials,
+            text_tx: text_tx.clone(),
+            ui: Arc::clone(&self.ui),
+        };
+        match NetworkServer::start(&self.config.network, context) {
+            Ok(server) => Some(server),
+            Err(err) => {
+                ui_log(&self.ui, format!("[network-error] {err}"));
+                None
+            }
+        }
+    }
+
+    fn start_recorder(&self) -> Option<Arc<SessionRecorder>> {
+        let recording = &self.config.recording;
+        if !recording.enabled {
+            return None;
+        }
+        match SessionRecorder::start(
+            recording,
+            &resolve_path(&self.config_dir, &recording.dir),
+            self.config.speech.sample_rate,
+            Arc::clone(&self.ui),
+        ) {
+            Ok(recorder) => Some(Arc::new(recorder)),
+            Err(err) => {
+                ui_log(&self.ui, format!("[recorder-error] {err}"));
+                None
+            }
+        }
+    }
+
     fn spawn_event_worker(
         &self,
         shutdown: Arc<AtomicBool>,
         text_rx: Receiver<RecognizedPhraseEvent>,
+        recorder: Option<Arc<SessionRecorder>>,
     ) -> thread::JoinHandle<()> {
-        let player_name = self.config.microphone.player_name.clone();
+        let primary_player = self.config.mic_player_name();
+        let speakers = self.config.speakers.clone();
         let min_phrase_chars = self.config.speech.min_phrase_chars;
         let log_recognized = self.config.speech.log_recognized;
-        let fuzzy_threshold = self.config.speech.fuzzy_threshold;
-        let cooldown_seconds = self.config.speech.cooldown_seconds;
-        let catalog = self.catalog.clone();
+        let trigger_delay = Duration::from_millis(self.config.speech.trigger_delay_ms);
         let rcon = Arc::clone(&self.rcon);
         let ui = Arc::clone(&self.ui);
+        let matcher = EventMatcher::new(&self.config.speech, self.catalog.clone(), Arc::clone(&ui));
+
+        if matcher.wake.is_some() {
+            ui_log(
+                &ui,
+                format!(
+                    "[wake] блоки только в {} словах после: {}",
+                    self.config.speech.wake_window_words,
+                    self.config.speech.wake_phrases.join(", ")
+                ),
+            );
+        }
+        if !matcher.cancel_phrases.is_empty() {
+            if trigger_delay.is_zero() {
+                ui_log(
+                    &ui,
+                    "[cancel-warning] speech.trigger_delay_ms = 0: удаление не ждет, speech.cancel_phrases ничего не отменят"
+                        .to_string(),
+                );
+            } else {
+                ui_log(
+                    &ui,
+                    format!(
+                        "[cancel] удаление ждет {} мс, отмена: {}",
+                        trigger_delay.as_millis(),
+                        self.config.speech.cancel_phrases.join(", ")
+                    ),
+                );
+            }
+        }
+        ui_log(
+            &ui,
+            format!(
+                "[speakers] {}",
+                speakers
+                    .iter()
+                    .map(|(id, speaker)| format!("{id} -> {}", speaker.player_name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        );
 
         thread::spawn(move || {
-            let mut last_trigger: BTreeMap<(String, String), Instant> = BTreeMap::new();
-            let mut repeat_gate: HashMap<(String, String), RepeatGateState> = HashMap::new();
-            let repeat_window = Duration::from_secs(1);
-            let mut partial_progress: HashMap<String, PartialProgressState> = HashMap::new();
-            let mut cached_chunk: Option<CachedChunkContext> = None;
+            let mut runtimes = SpeakerRuntimes::new(speakers);
+            let mut pending: VecDeque<PendingTrigger> = VecDeque::new();
             let chunk_cache_ttl = Duration::from_millis(700);
+            let idle_wait = Duration::from_millis(200);
+            // The header indicator follows the local player only.
+            let set_player_online = |player: &str, online: bool| {
+                if primary_player.as_deref() == Some(player) {
+                    ui_set_player_online(&ui, online);
+                }
+            };
+            let fire = |runtime: &mut SpeakerRuntime, trigger: PendingTrigger| {
+                let chunk_context = match runtime.chunk_context(&rcon, chunk_cache_ttl) {
+                    Ok(ctx) => ctx,
+                    Err(err) => {
+                        if err.downcast_ref::<PlayerLookupError>().is_some() {
+                            set_player_online(&runtime.player_name, false);
+                            ui_log(&ui, format!("[rcon-player-error] {err}"));
+                        } else {
+                            ui_log(&ui, format!("[rcon-error] {err}"));
+                        }
+                        return;
+                    }
+                };
+
+                for block_match in trigger.matches {
+                    if !runtime.take_cooldown(&block_match.block_id, Instant::now()) {
+                        continue;
+                    }
+
+                    match rcon.delete_block_in_chunk_context(&chunk_context, &block_match.block_id) {
+                        Ok(result) => {
+                            set_player_online(&result.player_name, true);
+                            if let Some(recorder) = &recorder {
+                                recorder.note_event(
+                                    "trigger",
+                                    serde_json::json!({
+                                        "speaker": trigger.speaker_id,
+                                        "text": trigger.text,
+                                        "partial": trigger.is_partial,
+                                        "player": result.player_name,
+                                        "block": result.block_id,
+                                        "alias": block_match.alias,
+                                        "ngram": block_match.ngram,
+                                        "method": block_match.method.as_str(),
+                                        "score": block_match.score,
+                                        "chunk": [result.chunk_x, result.chunk_z],
+                                    }),
+                                );
+                            }
+                            ui_log(
+                                &ui,
+                                format!(
+                                "[trigger] speaker={} -> player={}, block={}, dimension={}, chunk=({},{}), fill_commands={}, match={}",
+                                trigger.speaker_id,
+                                result.player_name,
+                                result.block_id,
+                                result.dimension,
+                                result.chunk_x,
+                                result.chunk_z,
+                                result.commands_sent,
+                                block_match.reason()
+                            ),
+                            );
+                        }
+                        Err(err) => {
+                            if err.downcast_ref::<PlayerLookupError>().is_some() {
+                                set_player_online(&runtime.player_name, false);
+                                ui_log(&ui, format!("[rcon-player-error] {err}"));
+                            } else {
+                                ui_log(&ui, format!("[rcon-error] {err}"));
+                            }
+                        }
+                    }
+                }
+            };
 
             loop {
-                match text_rx.recv_timeout(Duration::from_millis(200)) {
+                let now = Instant::now();
+                let due = pending.iter().take_while(|trigger| trigger.due <= now).count();
+                for trigger in pending.drain(..due) {
+                    if let Some(runtime) = runtimes.get_mut(&trigger.speaker_id) {
+                        fire(runtime, trigger);
+                    }
+                }
+                let wait = pending
+                    .front()
+                    .map_or(idle_wait, |next| next.due.saturating_duration_since(now).min(idle_wait));
+
+                match text_rx.recv_timeout(wait) {
                     Ok(event) => {
-                        let cleaned = normalize_text(&event.text);
+                        if let Some(recorder) = &recorder {
+                            recorder.note_event(
+                                if event.is_partial { "partial" } else { "final" },
+                                serde_json::json!({
+                                    "speaker": event.speaker_id,
+                                    "text": event.text,
+                                    "confidence": event.confidence,
+                                    "words": event
+                                        .words
+                                        .iter()
+                                        .map(|w| serde_json::json!({
+                                            "word": w.word,
+                                            "conf": w.confidence,
+                                            "start": w.start,
+                                            "end": w.end,
+                                        }))
+                                        .collect::<Vec<_>>(),
+                                    "alternatives": event
+                                        .alternatives
+                                        .iter()
+                                        .map(|a| serde_json::json!({ "text": a.text, "score": a.score }))
+                                        .collect::<Vec<_>>(),
+                                }),
+                            );
+                        }
+                        let cleaned = matcher.catalog.normalize(&event.text);
                         if cleaned.chars().count() < min_phrase_chars {
                             continue;
                         }
                         if log_recognized {
-                            ui_log(&ui, format!("[recognized:{}] {}", event.speaker_id, cleaned));
+                            match event.confidence {
+                                Some(confidence) => ui_log(
+                                    &ui,
+                                    format!("[recognized:{}] {} ({confidence:.2})", event.speaker_id, cleaned),
+                                ),
+                                None => ui_log(&ui, format!("[recognized:{}] {}", event.speaker_id, cleaned)),
+                            }
                         }
 
-                        if event.speaker_id != MIC_SPEAKER_ID {
-                            ui_log(&ui, format!("[mapping-warning] нет никнейма для {}", event.speaker_id));
+                        let Some(runtime) = runtimes.for_event(&event.speaker_id, &ui) else {
                             continue;
-                        }
-
-                        let candidates: Vec<String> = if event.is_partial {
-                            let st = partial_progress
-                                .entry(event.speaker_id.clone())
-                                .or_default();
-
-                            let current_words: Vec<&str> = cleaned.split_whitespace().collect();
-                            let committed_count = current_words.len().saturating_sub(1);
-
-                            // Если partial "откатился"/пересобрался, начинаем индекс заново.
-                            if !cleaned.starts_with(&st.last_partial) {
-                                st.processed_committed_words = 0;
-                            }
-
-                            let start_idx = st.processed_committed_words.min(committed_count);
-                            let mut out = Vec::new();
-                            for word in current_words.iter().skip(start_idx).take(committed_count.saturating_sub(start_idx)) {
-                                if word.len() >= 2 {
-                                    out.push((*word).to_string());
-                                }
-                            }
-                            st.last_partial = cleaned.clone();
-                            st.processed_committed_words = committed_count;
-                            out
-                        } else {
-                            partial_progress.remove(&event.speaker_id);
-                            vec![cleaned.clone()]
                         };
 
-                        let mut block_ids: Vec<String> = Vec::new();
-                        let mut seen_blocks = HashSet::new();
-
-                        for candidate in candidates {
-                            let key = (event.speaker_id.clone(), candidate.clone());
-                            let now = Instant::now();
-                            let state = repeat_gate.entry(key).or_insert(RepeatGateState {
-                                last_seen: now,
-                                count: 0,
+                        // Отмена снимает еще не выполненные удаления этого говорящего.
+                        if matcher.new_cancel(&cleaned, &mut runtime.partial_progress) {
+                            let mut cancelled: Vec<String> = Vec::new();
+                            pending.retain(|trigger| {
+                                if trigger.speaker_id != event.speaker_id {
+                                    return true;
+                                }
+                                cancelled.extend(block_ids(&trigger.matches));
+                                false
                             });
-                            if now.duration_since(state.last_seen) > repeat_window {
-                                state.count = 0;
-                            }
-                            state.last_seen = now;
-                            state.count += 1;
-
-                            // 1, 9, 17, ... => пропускаем 7 из каждых 8 одинаковых повторов за секунду
-                            if (state.count - 1) % 8 != 0 {
-                                continue;
+                            if let Some(recorder) = &recorder {
+                                recorder.note_event(
+                                    "cancel",
+                                    serde_json::json!({
+                                        "speaker": event.speaker_id,
+                                        "text": cleaned,
+                                        "blocks": cancelled,
+                                    }),
+                                );
                             }
-
-                            for block_id in catalog.match_blocks(&candidate, fuzzy_threshold) {
-                                if seen_blocks.insert(block_id.clone()) {
-                                    block_ids.push(block_id);
-                                }
+                            if cancelled.is_empty() {
+                                ui_log(&ui, format!("[cancel] {}: нечего отменять", event.speaker_id));
+                            } else {
+                                ui_log(
+                                    &ui,
+                                    format!("[cancel] {}: отменено {}", event.speaker_id, cancelled.join(", ")),
+                                );
                             }
                         }
-
-                        if block_ids.is_empty() {
+                        let matches = matcher.match_event(
+                            &event,
+                            &cleaned,
+                            &mut runtime.partial_progress,
+                            &mut runtime.repeat_gate,
+                        );
+                        if matches.is_empty() {
                             continue;
                         }
-
-                        let chunk_context = if let Some(cache) = &cached_chunk {
-                            if cache.context.player_name == player_name
-                                && cache.fetched_at.elapsed() <= chunk_cache_ttl
-                            {
-                                cache.context.clone()
-                            } else {
-                                match rcon.get_player_chunk_context(&player_name) {
-                                    Ok(ctx) => {
-                                        cached_chunk = Some(CachedChunkContext {
-                                            fetched_at: Instant::now(),
-                                            context: ctx.clone(),
-                                        });
-                                        ctx
-                                    }
-                                    Err(err) => {
-                                        cached_chunk = None;
-                                        if err.downcast_ref::<PlayerLookupError>().is_some() {
-                                            ui_set_player_online(&ui, false);
-                                            ui_log(&ui, format!("[rcon-player-error] {err}"));
-                                        } else {
-                                            ui_log(&ui, format!("[rcon-error] {err}"));
-                                        }
-                                        continue;
-                                    }
-                                }
-                            }
-                        } else {
-                            match rcon.get_player_chunk_context(&player_name) {
-                                Ok(ctx) => {
-                                    cached_chunk = Some(CachedChunkContext {
-                                        fetched_at: Instant::now(),
-                                        context: ctx.clone(),
-                                    });
-                                    ctx
-                                }
-                                Err(err) => {
-                                    if err.downcast_ref::<PlayerLookupError>().is_some() {
-                                        ui_set_player_online(&ui, false);
-                                        ui_log(&ui, format!("[rcon-player-error] {err}"));
-                                    } else {
-                                        ui_log(&ui, format!("[rcon-error] {err}"));
-                                    }
-                                    continue;
-                                }
-                            }
-                        };
-
-                        for block_id in block_ids {
-                            let key = (player_name.clone(), block_id.clone());
-                            let now = Instant::now();
-                            if let Some(prev) = last_trigger.get(&key) {
-                                if now.duration_since(*prev).as_secs_f64() < cooldown_seconds {
-                                    continue;
-                                }
-                            }
-                            last_trigger.insert(key, now);
-
-                            match rcon.delete_block_in_chunk_context(&chunk_context, &block_id) {
-                                Ok(result) => {
-                                    ui_set_player_online(&ui, true);
-                                    ui_log(
-                                        &ui,
-                                        format!(
-                                        "[trigger] speaker=Microphone -> player={}, block={}, dimension={}, chunk=({},{}), fill_commands={}",
-                                        result.player_name,
-                                        result.block_id,
-                                        result.dimension,
-                                        result.chunk_x,
-                                        result.chunk_z,
-                                        result.commands_sent
-                                    ),
-                                    );
-                                }
-                                Err(err) => {
-                                    if err.downcast_ref::<PlayerLookupError>().is_some() {
-                                        ui_set_player_online(&ui, false);
-                                        ui_log(&ui, format!("[rcon-player-error] {err}"));
-                                    } else {
-                                        ui_log(&ui, format!("[rcon-error] {err}"));
-                                    }
-                                }
-                            }
+                        for block_match in &matches {
+                            ui_note_match(
+                                &ui,
+                                format!("{}: {} <- {}", event.speaker_id, block_match.block_id, block_match.reason()),
+                            );
                         }
+                        pending.push_back(PendingTrigger {
+                            due: Instant::now() + trigger_delay,
+                            speaker_id: event.speaker_id.clone(),
+                            text: cleaned.clone(),
+                            is_partial: event.is_partial,
+                            matches,
+                        });
                     }
                     Err(RecvTimeoutError::Timeout) => {
                         if shutdown.load(Ordering::Relaxed) {
@@ -3235,37 +6466,49 @@ impl BlockDeleteController {
     }
 
     fn spawn_presence_watcher(&self, shutdown: Arc<AtomicBool>) -> thread::JoinHandle<()> {
-        let player_name = self.config.microphone.player_name.clone();
+        let primary_player = self.config.mic_player_name();
+        let players: BTreeSet<String> = self
+            .config
+            .speakers
+            .values()
+            .map(|speaker| speaker.player_name.clone())
+            .collect();
         let rcon = Arc::clone(&self.rcon);
         let ui = Arc::clone(&self.ui);
 
         thread::spawn(move || {
-            let mut was_online = false;
+            let mut online: HashSet<String> = HashSet::new();
             while !shutdown.load(Ordering::Relaxed) {
-                match rcon.get_player_chunk_context(&player_name) {
-                    Ok(_) => {
-                        ui_set_player_online(&ui, true);
-                        if !was_online {
-                            was_online = true;
-                            ui_log(&ui, format!("[player] {} зашел на сервер", player_name));
-                            match rcon.send_private_message(
-                                &player_name,
-                                "[BlockDelete] все успешно работает",
-                            ) {
-                                Ok(()) => ui_log(&ui, "[notify] отправлено личное сообщение игроку"),
-                                Err(err) => ui_log(&ui, format!("[notify-error] {err}")),
+                for player_name in &players {
+                    let is_primary = primary_player.as_ref() == Some(player_name);
+                    match rcon.get_player_chunk_context(player_name) {
+                        Ok(_) => {
+                            if is_primary {
+                                ui_set_player_online(&ui, true);
+                            }
+                            if online.insert(player_name.clone()) {
+                                ui_log(&ui, format!("[player] {} зашел на сервер", player_name));
+                                match rcon.send_private_message(
+                                    player_name,
+                                    "[BlockDelete] все успешно работает",
+                                ) {
+                                    Ok(()) => ui_log(
+                                        &ui,
+                                        format!("[notify] отправлено личное сообщение игроку {player_name}"),
+                                    ),
+                                    Err(err) => ui_log(&ui, format!("[notify-error] {err}")),
+                                }
                             }
                         }
-                    }
-                    Err(err) => {
-                        if err.downcast_ref::<PlayerLookupError>().is_some() {
-                            if was_online {
+                        Err(err) => {
+                            if err.downcast_ref::<PlayerLookupError>().is_some()
+                                && online.remove(player_name)
+                            {
                                 ui_log(&ui, format!("[player] {} вышел с сервера", player_name));
                             }
-                            was_online = false;
-                            ui_set_player_online(&ui, false);
-                        } else {
-                            ui_set_player_online(&ui, false);
+                            if is_primary {
+                                ui_set_player_online(&ui, false);
+                            }
                         }
                     }
                 }
@@ -3303,6 +6546,49 @@ fn real_main() -> Result<(), String> {
 
     let bootstrap = backend_bootstrap::BackendBootstrap::from_config_path(&args.config)?;
     let mut config = bootstrap.config.clone();
+    if let Some(input_file) = &args.input_file {
+        if input_file.as_os_str() == "-" {
+            config.microphone.source = AudioSourceKind::Stdin;
+        } else {
+            // CLI paths are relative to the working directory, config paths to the config file.
+            let absolute = std::env::current_dir()
+                .map(|cwd| cwd.join(input_file))
+                .unwrap_or_else(|_| input_file.clone());
+            config.microphone.source = AudioSourceKind::File;
+            config.microphone.input_file = Some(absolute.to_string_lossy().to_string());
+        }
+    }
+    if let Some(speed) = args.input_speed {
+        config.microphone.input_speed = speed.max(0.0);
+    }
+    if args.record {
+        config.recording.enabled = true;
+    }
+    match &args.command {
+        Some(CliCommand::Enroll { speaker, seconds }) => {
+            return voiceprints::run_enrollment(&config, &bootstrap.config_dir, speaker, *seconds);
+        }
+        Some(CliCommand::Bench { phrases, iterations }) => {
+            return bench::run_match_bench(&config, &bootstrap.config_dir, phrases.as_deref(), *iterations);
+        }
+        Some(CliCommand::Match {
+            phrase,
+            fuzzy_threshold,
+            phonetic_threshold,
+        }) => {
+            return match_cli::run_match_command(
+                &config,
+                &bootstrap.config_dir,
+                phrase,
+                *fuzzy_threshold,
+                *phonetic_threshold,
+            );
+        }
+        None => {}
+    }
+    if let (Some(addr), Some(speaker)) = (&args.stream_to, &args.speaker) {
+        return net_source::run_stream_client(&config, &bootstrap.config_dir, addr, speaker);
+    }
     let ui_mode = match config.ui.mode {
         Some(mode) => mode,
         None => {
@@ -3332,3 +6618,438 @@ fn real_main() -> Result<(), String> {
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use speech::ScriptedEngine;
+
+    const LANG: &str = r#"{
+        "block.minecraft.stone": "Камень",
+        "block.minecraft.diamond_ore": "Алмазная руда",
+        "block.minecraft.sand": "Песок",
+        "block.minecraft.dirt": "Земля",
+        "block.minecraft.ice": "Лёд",
+        "item.minecraft.stick": "Палка"
+    }"#;
+
+    fn catalog() -> BlockCatalog {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "blockdeletee-test-{}-{}.json",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&path, LANG).unwrap();
+        let catalog = BlockCatalog::load(&[("ru_ru".to_string(), path.clone())], &HashMap::new(), &HashMap::new());
+        let _ = fs::remove_file(&path);
+        catalog
+            .unwrap()
+            .with_phonetic_threshold(0.8)
+            .with_stemmers(vec![Stemmer::Russian])
+    }
+
+    fn matcher(wake: &[&str], negation: &[&str], cancel: &[&str], min_word_confidence: f32) -> EventMatcher {
+        let catalog = catalog();
+        let phrases = |list: &[&str]| list.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        EventMatcher {
+            wake: PhraseWindows::new(&phrases(wake), 3, &catalog),
+            negation: PhraseWindows::new(&phrases(negation), 2, &catalog),
+            cancel_phrases: phrase_words(&phrases(cancel), &catalog),
+            fuzzy_threshold: 0.8,
+            min_word_confidence,
+            repeat_window: Duration::from_secs(1),
+            catalog,
+            ui: Arc::new(Mutex::new(UiState::new(
+                "Steve".to_string(),
+                "127.0.0.1".to_string(),
+                25575,
+                String::new(),
+                UiMode::Tui,
+            ))),
+        }
+    }
+
+    fn event(output: &SpeechOutput) -> RecognizedPhraseEvent {
+        let (hypothesis, is_partial) = match output {
+            SpeechOutput::Partial(hypothesis) => (hypothesis, true),
+            SpeechOutput::Final(hypothesis) => (hypothesis, false),
+        };
+        RecognizedPhraseEvent {
+            speaker_id: "mic".to_string(),
+            text: hypothesis.text.clone(),
+            is_partial,
+            confidence: hypothesis.confidence,
+            words: hypothesis.words.clone(),
+            alternatives: hypothesis.alternatives.clone(),
+        }
+    }
+
+    /// Feeds a script as the events of one speaker: whether each event cancels, and the
+    /// blocks it matched.
+    fn run(matcher: &EventMatcher, script: &str) -> Vec<(bool, Vec<String>)> {
+        let engine = ScriptedEngine::parse(script).unwrap();
+        let mut progress = PartialProgressState::default();
+        let mut repeat_gate = HashMap::new();
+        engine
+            .lines
+            .iter()
+            .map(|line| {
+                let event = event(&line.output);
+                let cleaned = matcher.catalog.normalize(&event.text);
+                let cancel = matcher.new_cancel(&cleaned, &mut progress);
+                let matches = matcher.match_event(&event, &cleaned, &mut progress, &mut repeat_gate);
+                (cancel, block_ids(&matches))
+            })
+            .collect()
+    }
+
+    fn blocks(ids: &[&str]) -> (bool, Vec<String>) {
+        (false, ids.iter().map(|id| format!("minecraft:{id}")).collect())
+    }
+
+    fn logged(matcher: &EventMatcher, prefix: &str) -> bool {
+        ui_drain_logs(&matcher.ui).iter().any(|line| line.starts_with(prefix))
+    }
+
+    #[test]
+    fn exact_device_name_beats_a_substring() {
+        let devices = ["USB Mic 2", "USB Mic", "Built-in Microphone"].map(|name| name.to_string());
+        let find = |name: &str| device_position_by_name(devices.iter().map(String::as_str), name);
+        assert_eq!(find("USB Mic"), Some(1));
+        assert_eq!(find(" USB Mic 2 "), Some(0));
+        assert_eq!(find("usb mic"), Some(0));
+        assert_eq!(find("built-in"), Some(2));
+        assert_eq!(find("Headset"), None);
+        assert_eq!(find("  "), None);
+    }
+
+    #[test]
+    fn catalog_explains_each_match() {
+        let catalog = catalog();
+        let explain = |text: &str| {
+            catalog
+                .match_blocks(text, 0.8)
+                .into_iter()
+                .map(|m| (m.block_id, m.method, m.alias))
+                .collect::<Vec<_>>()
+        };
+        let one = |id: &str, method, alias: &str| vec![(format!("minecraft:{id}"), method, alias.to_string())];
+        assert_eq!(explain("удали Камень"), one("stone", MatchMethod::Exact, "камень"));
+        assert_eq!(explain("убери камнем"), one("stone", MatchMethod::Stem, "камень"));
+        assert_eq!(explain("алмазную руду"), one("diamond_ore", MatchMethod::Stem, "алмазная руда"));
+        assert_eq!(explain("зимля"), one("dirt", MatchMethod::Fuzzy, "земля"));
+        assert_eq!(explain("гамень"), one("stone", MatchMethod::Phonetic, "камень"));
+        assert!(explain("палка").is_empty());
+    }
+
+    #[test]
+    fn short_phonetic_keys_only_match_exactly() {
+        let catalog = catalog();
+        assert_eq!(phonetic_key("лед"), phonetic_key("лет"));
+        assert!(!catalog.phonetic_aliases.contains_key(&phonetic_key("лед")));
+        assert_eq!(catalog.match_block_ids("лёд", 0.8), ["minecraft:ice"]);
+        assert!(catalog.match_block_ids("лет", 0.8).is_empty());
+    }
+
+    #[test]
+    fn partials_match_each_new_ngram_once() {
+        let matcher = matcher(&[], &[], &[], 0.0);
+        let script = "0.5 partial алмазная\n\
+                      0.8 partial алмазная руда\n\
+                      1.0 partial алмазная руда и\n\
+                      1.5 final алмазная руда и камень\n";
+        assert_eq!(
+            run(&matcher, script),
+            [blocks(&[]), blocks(&[]), blocks(&["diamond_ore"]), blocks(&["stone"])]
+        );
+    }
+
+    #[test]
+    fn rewritten_partial_starts_over() {
+        let matcher = matcher(&[], &[], &[], 0.0);
+        let script = "0.5 partial песок и\n\
+                      0.8 partial камень и\n\
+                      1.5 final камень и\n";
+        assert_eq!(run(&matcher, script), [blocks(&["sand"]), blocks(&["stone"]), blocks(&[])]);
+    }
+
+    #[test]
+    fn repeated_final_is_gated() {
+        let matcher = matcher(&[], &[], &[], 0.0);
+        let script = "1.0 final камень\n2.0 final камень\n3.0 final песок\n";
+        assert_eq!(run(&matcher, script), [blocks(&["stone"]), blocks(&[]), blocks(&["sand"])]);
+    }
+
+    #[test]
+    fn wake_phrase_limits_blocks_to_its_window() {
+        let matcher = matcher(&["удали"], &[], &[], 0.0);
+        let script = "0.5 partial камень удали алмазная руда ну\n\
+                      1.0 final камень удали алмазная руда ну\n\
+                      2.0 final просто песок\n";
+        assert_eq!(run(&matcher, script), [blocks(&["diamond_ore"]), blocks(&[]), blocks(&[])]);
+        assert!(logged(&matcher, "[wake] mic: нет wake-фразы, пропущено «просто песок»"));
+    }
+
+    #[test]
+    fn negated_blocks_are_dropped() {
+        let matcher = matcher(&[], &["не", "кроме"], &[], 0.0);
+        let script = "1.0 final убери песок но не камень\n2.0 final все кроме алмазной руды\n";
+        assert_eq!(run(&matcher, script), [blocks(&["sand"]), blocks(&[])]);
+        assert!(logged(&matcher, "[negation] mic: не удаляю minecraft:stone"));
+    }
+
+    #[test]
+    fn cancel_phrase_fires_once_and_hides_earlier_words() {
+        let matcher = matcher(&[], &[], &["отмена"], 0.0);
+        let script = "0.5 partial камень\n\
+                      0.8 partial камень отмена\n\
+                      1.0 partial камень отмена песок\n\
+                      1.5 final камень отмена песок\n\
+                      2.0 final отмена\n";
+        assert_eq!(
+            run(&matcher, script),
+            [blocks(&[]), (true, Vec::new()), blocks(&[]), blocks(&["sand"]), (true, Vec::new())]
+        );
+    }
+
+    #[test]
+    fn low_confidence_words_are_ignored() {
+        let matcher = matcher(&[], &[], &[], 0.5);
+        let script = "1.0 final:0.3 камень\n2.0 final:0.9 песок\n";
+        assert_eq!(run(&matcher, script), [blocks(&[]), blocks(&["sand"])]);
+        assert!(logged(&matcher, "[confidence] отклонено mic: minecraft:stone"));
+
+        let word = |word: &str, confidence| WordInfo {
+            word: word.to_string(),
+            confidence: Some(confidence),
+            start: 0.0,
+            end: 0.0,
+        };
+        let event = RecognizedPhraseEvent {
+            speaker_id: "mic".to_string(),
+            text: "камень песок".to_string(),
+            is_partial: false,
+            confidence: None,
+            words: vec![word("камень", 0.2), word("песок", 0.9)],
+            alternatives: Vec::new(),
+        };
+        let matches = matcher.match_event(
+            &event,
+            "камень песок",
+            &mut PartialProgressState::default(),
+            &mut HashMap::new(),
+        );
+        assert_eq!(block_ids(&matches), ["minecraft:sand"]);
+    }
+
+    #[test]
+    fn nbest_final_picks_the_most_likely_block() {
+        let matcher = matcher(&[], &[], &[], 0.0);
+        assert_eq!(run(&matcher, "1.0 final кабель | камень | камни\n"), [blocks(&["stone"])]);
+        assert!(logged(&matcher, "[alternatives] mic:"));
+    }
+
+    #[test]
+    fn nbest_final_below_min_word_confidence_is_rejected() {
+        let matcher = matcher(&[], &[], &[], 0.5);
+        assert_eq!(run(&matcher, "1.0 final кабель | камень | камни\n"), [blocks(&[])]);
+        assert!(logged(&matcher, "[confidence] отклонено mic: minecraft:stone по N-best — 0.33 < 0.50"));
+        assert_eq!(run(&matcher, "1.0 final камень | кабель\n"), [blocks(&["stone"])]);
+    }
+
+    #[test]
+    fn channel_selection_picks_and_mixes_interleaved_channels() {
+        let first = ChannelSelection::Channels(vec![0]);
+        let outer = ChannelSelection::from_config_str("1,3").unwrap();
+        assert_eq!(outer, ChannelSelection::Channels(vec![0, 2]));
+
+        // Two frames of three channels each.
+        let i16_data: [i16; 6] = [1000, -2000, 3000, -400, 500, 600];
+        assert_eq!(to_mono_i16(&i16_data, 3, &first), [1000, -400]);
+        assert_eq!(to_mono_i16(&i16_data, 3, &outer), [2000, 100]);
+        assert_eq!(to_mono_i16(&i16_data, 3, &ChannelSelection::Mix), [666, 233]);
+
+        let f32_data: [f32; 6] = [0.5, -1.0, 1.5, 0.0, 0.25, -0.25];
+        assert_eq!(to_mono_f32(&f32_data, 3, &first), [16383, 0]);
+        assert_eq!(to_mono_f32(&f32_data, 3, &outer), [24575, -4095]);
+        assert_eq!(to_mono_f32(&f32_data, 3, &ChannelSelection::Mix), [5461, 0]);
+
+        let u16_data: [u16; 6] = [32768, 0, 65535, 33768, 32768, 34768];
+        assert_eq!(to_mono_u16(&u16_data, 3, &first), [0, 1000]);
+        assert_eq!(to_mono_u16(&u16_data, 3, &outer), [16383, 1500]);
+        assert_eq!(to_mono_u16(&u16_data, 3, &ChannelSelection::Mix), [0, 1000]);
+    }
+
+    #[test]
+    fn channel_selection_fits_the_device_channel_count() {
+        let outer = ChannelSelection::Channels(vec![0, 2]);
+        assert_eq!(outer.fit(3), Ok(outer.clone()));
+        assert_eq!(outer.fit(2), Ok(ChannelSelection::Channels(vec![0])));
+        assert_eq!(ChannelSelection::Mix.fit(1), Ok(ChannelSelection::Mix));
+        let error = ChannelSelection::Channels(vec![2, 3]).fit(2).unwrap_err();
+        assert!(error.contains("microphone.channel=3,4"), "{error}");
+
+        assert_eq!(ChannelSelection::from_config_str("mix"), Some(ChannelSelection::Mix));
+        assert_eq!(ChannelSelection::from_config_str("0"), None);
+        assert_eq!(ChannelSelection::from_config_str("2,x"), None);
+    }
+
+    fn load_config(json: &str) -> AppConfig {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "blockdeletee-config-{}-{}.json",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&path, json).unwrap();
+        let config = AppConfig::load(&path);
+        let _ = fs::remove_file(&path);
+        config.unwrap()
+    }
+
+    #[test]
+    fn speakers_mic_overrides_the_microphone_player() {
+        let config = load_config(
+            r#"{
+                "microphone": { "player_name": "Steve" },
+                "speech": { "cooldown_seconds": 2 },
+                "speakers": { "mic": "Alex", "radio": { "player": "Steve", "cooldown_seconds": 5 } }
+            }"#,
+        );
+        assert_eq!(config.mic_player_name().as_deref(), Some("Alex"));
+        assert_eq!(config.speakers["radio"].player_name, "Steve");
+        assert_eq!(config.speakers["radio"].cooldown_seconds, 5.0);
+
+        let config = load_config(r#"{ "microphone": { "player_name": " Steve " } }"#);
+        assert_eq!(config.mic_player_name().as_deref(), Some("Steve"));
+
+        let config = load_config(r#"{ "speakers": { "mic": "Alex" } }"#);
+        assert!(config.microphone.player_name.is_empty());
+        assert_eq!(config.mic_player_name().as_deref(), Some("Alex"));
+        assert_eq!(load_config("{}").mic_player_name(), None);
+    }
+
+    #[test]
+    fn speakers_keep_their_own_cooldowns_chunk_caches_and_matches() {
+        let speaker = |cooldown_seconds| SpeakerConfig {
+            player_name: "Steve".to_string(),
+            cooldown_seconds,
+        };
+        let speakers = BTreeMap::from([("mic".to_string(), speaker(10.0)), ("radio".to_string(), speaker(0.0))]);
+        let mut runtimes = SpeakerRuntimes::new(speakers);
+        let matcher = matcher(&[], &[], &[], 0.0);
+        let ui = Arc::clone(&matcher.ui);
+
+        let now = Instant::now();
+        let mic = runtimes.for_event("mic", &ui).unwrap();
+        assert!(mic.take_cooldown("minecraft:stone", now));
+        assert!(!mic.take_cooldown("minecraft:stone", now + Duration::from_secs(1)));
+        assert!(mic.take_cooldown("minecraft:sand", now + Duration::from_secs(1)));
+        assert!(mic.take_cooldown("minecraft:stone", now + Duration::from_secs(11)));
+        let radio = runtimes.for_event("radio", &ui).unwrap();
+        assert!(radio.take_cooldown("minecraft:stone", now + Duration::from_secs(1)));
+        assert!(radio.take_cooldown("minecraft:stone", now + Duration::from_secs(1)));
+
+        // A fresh cache answers without RCON; the other speaker still has to ask the server,
+        // which nothing listens for here.
+        let context = PlayerChunkContext {
+            player_name: "Steve".to_string(),
+            dimension: "minecraft:overworld".to_string(),
+            chunk_x: 1,
+            chunk_z: 2,
+            x1: 16,
+            x2: 31,
+            z1: 32,
+            z2: 47,
+            segments: vec![(-64, 319)],
+        };
+        runtimes.get_mut("mic").unwrap().cached_chunk = Some(CachedChunkContext {
+            fetched_at: Instant::now(),
+            context,
+        });
+        let minecraft = MinecraftConfig {
+            rcon_host: "127.0.0.1".to_string(),
+            rcon_port: 1,
+            rcon_password: "secret".to_string(),
+            fill_max_blocks: 32768,
+            dimension_y_limits: HashMap::new(),
+        };
+        let rcon = MinecraftRconService::new(&minecraft, Arc::clone(&ui)).unwrap();
+        let ttl = Duration::from_secs(60);
+        let cached = runtimes.get_mut("mic").unwrap().chunk_context(&rcon, ttl).unwrap();
+        assert_eq!((cached.chunk_x, cached.chunk_z), (1, 2));
+        assert!(runtimes.get_mut("radio").unwrap().chunk_context(&rcon, ttl).is_err());
+        assert!(runtimes.get_mut("radio").unwrap().cached_chunk.is_none());
+
+        // Both say «камень» within the repeat window; each speaker gets its own match.
+        let final_event = |speaker_id: &str| RecognizedPhraseEvent {
+            speaker_id: speaker_id.to_string(),
+            text: "камень".to_string(),
+            is_partial: false,
+            confidence: None,
+            words: Vec::new(),
+            alternatives: Vec::new(),
+        };
+        for speaker_id in ["mic", "radio"] {
+            let runtime = runtimes.get_mut(speaker_id).unwrap();
+            let matches = matcher.match_event(
+                &final_event(speaker_id),
+                "камень",
+                &mut runtime.partial_progress,
+                &mut runtime.repeat_gate,
+            );
+            assert_eq!(block_ids(&matches), ["minecraft:stone"], "{speaker_id}");
+        }
+    }
+
+    #[test]
+    fn unmapped_speakers_are_warned_about_once() {
+        let speakers = BTreeMap::from([(
+            "mic".to_string(),
+            SpeakerConfig {
+                player_name: "Steve".to_string(),
+                cooldown_seconds: 0.0,
+            },
+        )]);
+        let mut runtimes = SpeakerRuntimes::new(speakers);
+        let matcher = matcher(&[], &[], &[], 0.0);
+        for _ in 0..3 {
+            assert!(runtimes.for_event("radio", &matcher.ui).is_none());
+            assert!(runtimes.for_event("mic", &matcher.ui).is_some());
+        }
+        assert!(runtimes.for_event("phone", &matcher.ui).is_none());
+        let warnings: Vec<String> = ui_drain_logs(&matcher.ui)
+            .into_iter()
+            .filter(|line| line.contains("[mapping-warning]"))
+            .collect();
+        assert_eq!(warnings.len(), 2, "{warnings:?}");
+        assert!(warnings[0].contains("radio") && warnings[1].contains("phone"));
+    }
+
+    #[test]
+    fn match_command_verdicts_follow_the_event_matcher() {
+        let matcher = matcher(&["удали"], &["не"], &["отмена"], 0.0);
+        let verdicts = |phrase: &str| {
+            match_cli::verdicts(&matcher, phrase)
+                .into_iter()
+                .map(|(block_match, why)| (block_match.block_id, why))
+                .collect::<Vec<_>>()
+        };
+        let verdict = |id: &str, why: Option<&str>| (format!("minecraft:{id}"), why.map(str::to_string));
+
+        assert_eq!(
+            verdicts("камень удали песок не земля"),
+            [
+                verdict("stone", Some("нет wake-фразы")),
+                verdict("dirt", Some("«земля» после отрицания")),
+                verdict("sand", None),
+            ]
+        );
+        assert_eq!(
+            verdicts("удали камень отмена удали песок"),
+            [verdict("stone", Some("до фразы отмены")), verdict("sand", None)]
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    block_ids, ui_drain_logs, AppConfig, BlockCatalog, BlockMatch, EventMatcher, PartialProgressState,
+    RecognizedPhraseEvent, UiMode, UiState,
+};
+
+/// `blockdeletee match`: matches phrases against the catalog the way the app does and
+/// explains every block found, without audio or RCON. Reads stdin line by line when no
+/// phrase is given.
+pub(crate) fn run_match_command(
+    config: &AppConfig,
+    config_dir: &Path,
+    phrase: &[String],
+    fuzzy_threshold: Option<f64>,
+    phonetic_threshold: Option<f64>,
+) -> Result<(), String> {
+    let speech = &config.speech;
+    let fuzzy_threshold = fuzzy_threshold.map_or(speech.fuzzy_threshold, |t| clamp_threshold(t, 0.99));
+    let phonetic_threshold = phonetic_threshold.map_or(speech.phonetic_threshold, |t| clamp_threshold(t, 1.0));
+    let catalog = BlockCatalog::from_config(config, config_dir)?.with_phonetic_threshold(phonetic_threshold);
+    eprintln!(
+        "[match] {} алиасов ({}), fuzzy {fuzzy_threshold:.2}, phonetic {phonetic_threshold:.2}",
+        catalog.alias_count(),
+        config
+            .blocks
+            .locales
+            .iter()
+            .map(|l| if l.stem { format!("{} (стемминг)", l.locale) } else { l.locale.clone() })
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let ui = Arc::new(Mutex::new(UiState::new(
+        config.microphone.player_name.clone(),
+        config.minecraft.rcon_host.clone(),
+        config.minecraft.rcon_port,
+        config.minecraft.rcon_password.clone(),
+        UiMode::Tui,
+    )));
+    let mut matcher = EventMatcher::new(speech, catalog, ui);
+    matcher.fuzzy_threshold = fuzzy_threshold;
+
+    let explain = |phrase: &str| {
+        println!("«{}»", matcher.catalog.normalize(phrase));
+        let verdicts = verdicts(&matcher, phrase);
+        if verdicts.is_empty() {
+            println!("  — ничего не найдено");
+        }
+        for (block_match, rejected) in verdicts {
+            let verdict = rejected.map(|why| format!(" [не удалится: {why}]")).unwrap_or_default();
+            println!("  {} <- {}{verdict}", block_match.block_id, block_match.reason());
+        }
+    };
+
+    if !phrase.is_empty() {
+        explain(&phrase.join(" "));
+        return Ok(());
+    }
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    if interactive {
+        eprintln!("[match] вводи фразы, Ctrl+D — выход");
+    }
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            eprint!("> ");
+            let _ = io::stderr().flush();
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line = line.map_err(|e| format!("Не удалось прочитать stdin: {e}"))?;
+        let phrase = line.trim();
+        if phrase.is_empty() || phrase.starts_with('#') {
+            continue;
+        }
+        explain(phrase);
+    }
+    Ok(())
+}
+
+/// Every block found in `phrase`, with the reason the event worker would not delete it when
+/// it would not. The decision is `EventMatcher::match_event` on `phrase` as a final result.
+pub(crate) fn verdicts(matcher: &EventMatcher, phrase: &str) -> Vec<(BlockMatch, Option<String>)> {
+    let normalized = matcher.catalog.normalize(phrase);
+    let event = RecognizedPhraseEvent {
+        speaker_id: "match".to_string(),
+        text: phrase.to_string(),
+        is_partial: false,
+        confidence: None,
+        words: Vec::new(),
+        alternatives: Vec::new(),
+    };
+    ui_drain_logs(&matcher.ui);
+    let kept = block_ids(&matcher.match_event(
+        &event,
+        &normalized,
+        &mut PartialProgressState::default(),
+        &mut HashMap::new(),
+    ));
+    let logs = ui_drain_logs(&matcher.ui);
+    let cancelled = matcher.new_cancel(&normalized, &mut PartialProgressState::default());
+    matcher
+        .catalog
+        .match_blocks(phrase, matcher.fuzzy_threshold)
+        .into_iter()
+        .map(|block_match| {
+            if kept.contains(&block_match.block_id) {
+                return (block_match, None);
+            }
+            // Отрицание пишет в лог сам блок и причину после «— ».
+            let why = logs
+                .iter()
+                .find(|line| line.contains(&block_match.block_id))
+                .and_then(|line| line.rsplit_once("— "))
+                .map(|(_, why)| why.to_string())
+                .unwrap_or_else(|| if cancelled { "до фразы отмены" } else { "нет wake-фразы" }.to_string());
+            (block_match, Some(why))
+        })
+        .collect()
+}
+
+/// The range `AppConfig::load` allows for similarity thresholds; `0` turns the path off.
+fn clamp_threshold(threshold: f64, max: f64) -> f64 {
+    if threshold > 0.0 {
+        threshold.clamp(0.5, max)
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Model used when `speech.model_path` is not set, as before models were picked by locale.
+pub(crate) const DEFAULT_MODEL_NAME: &str = "vosk-model-small-ru-0.22";
+
+/// A folder under `speech.models_dir` that looks like a Vosk model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ModelInfo {
+    /// Folder name, e.g. `vosk-model-small-ru-0.22`.
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    /// Language code guessed from the folder name (`ru`, `en-us`).
+    pub(crate) language: Option<String>,
+    /// Why the folder cannot be loaded; `None` for a usable model.
+    pub(crate) problem: Option<String>,
+}
+
+impl ModelInfo {
+    pub(crate) fn label(&self) -> String {
+        let mut label = self.name.clone();
+        if let Some(language) = &self.language {
+            label.push_str(&format!(" ({language})"));
+        }
+        if let Some(problem) = &self.problem {
+            label.push_str(&format!(" — {problem}"));
+        }
+        label
+    }
+
+    /// Whether this folder is the model at `path`.
+    pub(crate) fn is_at(&self, path: &Path) -> bool {
+        same_path(&self.path, path)
+    }
+
+    /// Small models load in seconds and fit in a few hundred MB; the big ones take minutes
+    /// and gigabytes.
+    pub(crate) fn is_small(&self) -> bool {
+        self.name.to_lowercase().split(['-', '_']).any(|part| part == "small")
+    }
+
+    /// Whether the model speaks the language of a Minecraft locale such as `en_us`.
+    pub(crate) fn speaks(&self, locale: &str) -> bool {
+        self.language
+            .as_deref()
+            .is_some_and(|language| base_language(language) == base_language(locale))
+    }
+}
+
+/// Best usable model for `locale`: [`DEFAULT_MODEL_NAME`] when it speaks it, then `en-us`
+/// for `en_us` before a model that is just `en`, and small models before big ones.
+pub(crate) fn model_for_locale<'a>(models: &'a [ModelInfo], locale: &str) -> Option<&'a ModelInfo> {
+    let exact = locale.replace('_', "-");
+    models
+        .iter()
+        .filter(|m| m.problem.is_none() && m.speaks(locale))
+        .min_by_key(|m| {
+            (
+                m.name != DEFAULT_MODEL_NAME,
+                m.language.as_deref() != Some(exact.as_str()),
+                !m.is_small(),
+            )
+        })
+}
+
+/// `speech.model_path` value for a model folder inside `models_dir`.
+pub(crate) fn model_config_path(models_dir: &str, name: &str) -> String {
+    format!("{}/{name}", models_dir.trim_end_matches(['/', '\\']))
+}
+
+/// `en_us`, `en-us` and `en` -> `en`.
+pub(crate) fn base_language(code: &str) -> &str {
+    code.split(['_', '-']).next().unwrap_or_default()
+}
+
+/// Lists every subfolder of `dir`, sorted by name, with the usable models validated.
+pub(crate) fn discover_models(dir: &Path) -> Result<Vec<ModelInfo>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Не удалось открыть папку моделей `{}`: {e}", dir.display()))?;
+    let mut models: Vec<ModelInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .map(|path| {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            ModelInfo {
+                language: language_from_name(&name),
+                problem: validate_model(&path).err(),
+                name,
+                path,
+            }
+        })
+        .collect();
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(models)
+}
+
+/// Checks for the files Vosk needs, in both the current (`am/`, `conf/`, `graph/`) and the
+/// old flat layout.
+pub(crate) fn validate_model(path: &Path) -> Result<(), String> {
+    let has = |candidates: &[&str]| candidates.iter().any(|c| path.join(c).is_file());
+    if !has(&["am/final.mdl", "final.mdl"]) {
+        return Err("нет am/final.mdl".to_string());
+    }
+    if !has(&["conf/mfcc.conf", "mfcc.conf"]) {
+        return Err("нет conf/mfcc.conf".to_string());
+    }
+    let static_graph = has(&["graph/HCLG.fst", "HCLG.fst"]);
+    let lookahead_graph = has(&["graph/HCLr.fst"]) && has(&["graph/Gr.fst"]);
+    if !static_graph && !lookahead_graph {
+        return Err("нет graph/HCLG.fst или graph/HCLr.fst + Gr.fst".to_string());
+    }
+    Ok(())
+}
+
+/// `vosk-model-small-ru-0.22` -> `ru`, `vosk-model-en-us-0.22-lgraph` -> `en-us`.
+pub(crate) fn language_from_name(name: &str) -> Option<String> {
+    let name = name.to_lowercase();
+    let codes: Vec<&str> = name
+        .split(['-', '_'])
+        .skip_while(|part| matches!(*part, "vosk" | "model" | "small"))
+        .take_while(|part| part.len() == 2 && part.chars().all(|c| c.is_ascii_alphabetic()))
+        .collect();
+    (!codes.is_empty()).then(|| codes.join("-"))
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(name: &str) -> ModelInfo {
+        ModelInfo {
+            name: name.to_string(),
+            path: PathBuf::from(name),
+            language: language_from_name(name),
+            problem: None,
+        }
+    }
+
+    fn pick<'a>(models: &'a [ModelInfo], locale: &str) -> Option<&'a str> {
+        model_for_locale(models, locale).map(|m| m.name.as_str())
+    }
+
+    #[test]
+    fn old_default_wins_for_its_language() {
+        let models = [model("vosk-model-ru-0.42"), model("vosk-model-small-ru-0.22"), model("vosk-model-small-ru-0.4")];
+        assert_eq!(pick(&models, "ru_ru"), Some(DEFAULT_MODEL_NAME));
+        assert_eq!(pick(&models, "en_us"), None);
+    }
+
+    #[test]
+    fn small_models_come_before_big_ones() {
+        let models = [model("vosk-model-ru-0.42"), model("vosk-model-small-ru-0.4")];
+        assert_eq!(pick(&models, "ru_ru"), Some("vosk-model-small-ru-0.4"));
+
+        let models = [model("vosk-model-en-0.22"), model("vosk-model-en-us-0.22"), model("vosk-model-small-en-us-0.15")];
+        assert_eq!(pick(&models, "en_us"), Some("vosk-model-small-en-us-0.15"));
+        assert_eq!(pick(&models, "en_gb"), Some("vosk-model-small-en-us-0.15"));
+    }
+
+    #[test]
+    fn broken_models_are_skipped() {
+        let mut broken = model(DEFAULT_MODEL_NAME);
+        broken.problem = Some("нет am/final.mdl".to_string());
+        let models = [model("vosk-model-ru-0.42"), broken];
+        assert_eq!(pick(&models, "ru_ru"), Some("vosk-model-ru-0.42"));
+        assert_eq!(model_config_path("voices/", "vosk-model-ru-0.42"), "voices/vosk-model-ru-0.42");
+    }
+}
@@ -0,0 +1,807 @@
+use std::collections::HashSet;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use serde::Deserialize;
+
+use crate::resample::Resampler;
+use crate::{
+    ui_drain_logs, ui_log, AppConfig, AudioSource, EngineCache, NetworkConfig, PcmBlock, RecognizedPhraseEvent,
+    SpeechSession, UiHandle, UiMode, UiState, MIC_SPEAKER_ID,
+};
+
+/// Rate the `--stream-to` client sends at; plenty for speech and ~32 KB/s on the wire.
+const STREAM_SAMPLE_RATE: u32 = 16_000;
+/// A client that connected but did not finish the handshake is dropped after this.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+const MAX_SPEAKER_ID_CHARS: usize = 32;
+const MAX_HANDSHAKE_BYTES: u64 = 4096;
+/// Upper bound for one WebSocket message; a second of 48 kHz audio is ~96 KB.
+const MAX_WS_MESSAGE_BYTES: usize = 1 << 20;
+const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// First message of every connection (a JSON line for raw TCP, a text message for WebSocket).
+#[derive(Debug, Deserialize)]
+struct NetHandshake {
+    speaker: String,
+    sample_rate: u32,
+    #[serde(default)]
+    token: String,
+}
+
+/// What every connection needs to build its own recognizer.
+pub(crate) struct NetworkContext {
+    pub(crate) engines: Arc<EngineCache>,
+    pub(crate) model_sample_rate: u32,
+    pub(crate) grammar_phrases: Option<Vec<String>>,
+    pub(crate) log_partials: bool,
+    pub(crate) text_tx: Sender<RecognizedPhraseEvent>,
+    pub(crate) ui: UiHandle,
+}
+
+/// Accepts PCM streams from remote speakers over TCP (optionally WebSocket on the same port)
+/// and runs one recognizer per connection.
+pub(crate) struct NetworkServer {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl NetworkServer {
+    pub(crate) fn start(config: &NetworkConfig, context: NetworkContext) -> Result<Self, String> {
+        let listener = TcpListener::bind(&config.bind)
+            .map_err(|e| format!("Не удалось открыть порт `{}`: {e}", config.bind))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Не удалось настроить сокет `{}`: {e}", config.bind))?;
+        let local = listener
+            .local_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|_| config.bind.clone());
+        ui_log(
+            &context.ui,
+            format!(
+                "[network] слушаю {local} ({}), максимум подключений={}{}",
+                if config.websocket { "tcp + websocket" } else { "tcp" },
+                config.max_connections,
+                if config.token.is_empty() { "" } else { ", нужен token" }
+            ),
+        );
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let shared = Arc::new(ServerShared {
+            config: config.clone(),
+            context,
+            speakers: Mutex::new(HashSet::new()),
+            stop: Arc::clone(&stop),
+        });
+        let handle = thread::spawn(move || accept_loop(listener, shared));
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    pub(crate) fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+struct ServerShared {
+    config: NetworkConfig,
+    context: NetworkContext,
+    /// Speaker ids with a live connection; also caps the number of connections.
+    speakers: Mutex<HashSet<String>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ServerShared {
+    fn claim_speaker(self: &Arc<Self>, speaker: &str) -> Result<SpeakerClaim, String> {
+        let mut speakers = self
+            .speakers
+            .lock()
+            .map_err(|_| "внутренняя ошибка".to_string())?;
+        if speakers.contains(speaker) {
+            return Err(format!("speaker `{speaker}` уже подключен"));
+        }
+        if speakers.len() >= self.config.max_connections {
+            return Err(format!(
+                "достигнут лимит подключений ({})",
+                self.config.max_connections
+            ));
+        }
+        speakers.insert(speaker.to_string());
+        Ok(SpeakerClaim {
+            shared: Arc::clone(self),
+            speaker: speaker.to_string(),
+        })
+    }
+}
+
+/// Frees the speaker id when the connection ends, however it ends.
+struct SpeakerClaim {
+    shared: Arc<ServerShared>,
+    speaker: String,
+}
+
+impl Drop for SpeakerClaim {
+    fn drop(&mut self) {
+        if let Ok(mut speakers) = self.shared.speakers.lock() {
+            speakers.remove(&self.speaker);
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<ServerShared>) {
+    let mut connections: Vec<thread::JoinHandle<()>> = Vec::new();
+    while !shared.stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, peer)) => {
+                let shared = Arc::clone(&shared);
+                connections.push(thread::spawn(move || handle_connection(stream, peer, shared)));
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(100)),
+            Err(err) => {
+                ui_log(&shared.context.ui, format!("[network-warning] accept: {err}"));
+                thread::sleep(Duration::from_millis(500));
+            }
+        }
+        connections.retain(|handle| !handle.is_finished());
+    }
+    for handle in connections {
+        let _ = handle.join();
+    }
+}
+
+fn handle_connection(stream: TcpStream, peer: SocketAddr, shared: Arc<ServerShared>) {
+    let ui = &shared.context.ui;
+    let mut conn = match NetConnection::accept(stream, &shared.config, Arc::clone(&shared.stop)) {
+        Ok(conn) => conn,
+        Err(err) => {
+            ui_log(ui, format!("[network-warning] {peer}: {err}"));
+            return;
+        }
+    };
+
+    let accepted = conn
+        .read_handshake()
+        .and_then(|handshake| validate_handshake(&shared.config, handshake))
+        .and_then(|handshake| {
+            let claim = shared.claim_speaker(&handshake.speaker)?;
+            Ok((handshake, claim))
+        })
+        .and_then(|(handshake, claim)| {
+            let session = SpeechSession::new(
+                Arc::clone(&shared.context.engines),
+                shared.context.model_sample_rate,
+                shared.context.grammar_phrases.clone(),
+                &handshake.speaker,
+                shared.context.log_partials,
+                shared.context.text_tx.clone(),
+                Arc::clone(ui),
+            )?;
+            Ok((handshake, claim, session))
+        });
+    let (handshake, _claim, mut session) = match accepted {
+        Ok(accepted) => accepted,
+        Err(err) => {
+            let _ = conn.reply(&format!("ERR {err}"));
+            ui_log(ui, format!("[network-warning] {peer}: подключение отклонено: {err}"));
+            return;
+        }
+    };
+    if let Err(err) = conn.reply("OK") {
+        ui_log(ui, format!("[network-warning] {peer}: {err}"));
+        return;
+    }
+
+    let speaker = handshake.speaker;
+    let model_rate = shared.context.model_sample_rate;
+    ui_log(
+        ui,
+        format!(
+            "[network] {speaker} подключен: {peer}, {} Hz, {}",
+            handshake.sample_rate,
+            conn.transport_name()
+        ),
+    );
+    let mut resampler =
+        (handshake.sample_rate != model_rate).then(|| Resampler::new(handshake.sample_rate, model_rate));
+    let mut received = 0u64;
+    loop {
+        match conn.read_pcm() {
+            Ok(Some(samples)) => {
+                received += samples.len() as u64;
+                let pcm = match resampler.as_mut() {
+                    Some(r) => r.process(&samples),
+                    None => samples,
+                };
+                if !pcm.is_empty() {
+                    session.accept(&pcm);
+                }
+            }
+            Ok(None) => break,
+            Err(_) if shared.stop.load(Ordering::Relaxed) => break,
+            Err(err) => {
+                ui_log(ui, format!("[network-warning] {speaker}: {err}"));
+                break;
+            }
+        }
+    }
+    session.flush();
+    ui_log(
+        ui,
+        format!(
+            "[network] {speaker} отключен ({:.1}s аудио)",
+            received as f64 / handshake.sample_rate as f64
+        ),
+    );
+}
+
+fn validate_handshake(config: &NetworkConfig, handshake: NetHandshake) -> Result<NetHandshake, String> {
+    if !config.token.is_empty() && !tokens_equal(&handshake.token, &config.token) {
+        return Err("неверный token".to_string());
+    }
+    let speaker = handshake.speaker.trim();
+    let valid_chars = speaker
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if speaker.is_empty() || speaker.chars().count() > MAX_SPEAKER_ID_CHARS || !valid_chars {
+        return Err(format!(
+            "speaker `{speaker}` неверен: 1..{MAX_SPEAKER_ID_CHARS} символов [A-Za-z0-9_.-]"
+        ));
+    }
+    if speaker == MIC_SPEAKER_ID {
+        return Err(format!("speaker `{MIC_SPEAKER_ID}` зарезервирован за локальным микрофоном"));
+    }
+    if !(8_000..=192_000).contains(&handshake.sample_rate) {
+        return Err(format!(
+            "sample_rate {} вне диапазона 8000..192000",
+            handshake.sample_rate
+        ));
+    }
+    Ok(NetHandshake {
+        speaker: speaker.to_string(),
+        ..handshake
+    })
+}
+
+/// Blocking reads that wake up periodically to honour the stop flag and an optional deadline,
+/// so a slow or silent peer never holds a thread forever.
+struct PatientReader {
+    stream: TcpStream,
+    stop: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl Read for PatientReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.stream.read(buf) {
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    if self.stop.load(Ordering::Relaxed) {
+                        return Err(io::Error::new(ErrorKind::ConnectionAborted, "остановлено"));
+                    }
+                    if self.deadline.is_some_and(|d| Instant::now() >= d) {
+                        return Err(io::Error::new(ErrorKind::TimedOut, "нет данных от клиента"));
+                    }
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    /// JSON handshake line, then raw s16le bytes.
+    Raw,
+    WebSocket,
+}
+
+enum WsMessage {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+struct NetConnection {
+    reader: BufReader<PatientReader>,
+    writer: TcpStream,
+    transport: Transport,
+    /// Odd trailing byte of the previous read, completed by the next one.
+    carry: Option<u8>,
+}
+
+impl NetConnection {
+    /// Detects the transport from the first byte and performs the WebSocket upgrade if needed.
+    fn accept(stream: TcpStream, config: &NetworkConfig, stop: Arc<AtomicBool>) -> Result<Self, String> {
+        stream
+            .set_nonblocking(false)
+            .and_then(|_| stream.set_read_timeout(Some(POLL_INTERVAL)))
+            .and_then(|_| stream.set_nodelay(true))
+            .map_err(|e| e.to_string())?;
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+        let mut reader = BufReader::new(PatientReader {
+            stream,
+            stop,
+            deadline: Some(Instant::now() + HANDSHAKE_TIMEOUT),
+        });
+        let first = reader
+            .fill_buf()
+            .map_err(|e| e.to_string())?
+            .first()
+            .copied()
+            .ok_or_else(|| "соединение закрыто до handshake".to_string())?;
+
+        let mut conn = Self {
+            reader,
+            writer,
+            transport: Transport::Raw,
+            carry: None,
+        };
+        if first == b'G' {
+            if !config.websocket {
+                let _ = conn
+                    .writer
+                    .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n");
+                return Err("WebSocket выключен (network.websocket=false)".to_string());
+            }
+            conn.upgrade_websocket(!config.token.is_empty())?;
+            conn.transport = Transport::WebSocket;
+        }
+        Ok(conn)
+    }
+
+    fn transport_name(&self) -> &'static str {
+        match self.transport {
+            Transport::Raw => "tcp",
+            Transport::WebSocket => "websocket",
+        }
+    }
+
+    /// Without a token only pages served from this machine may connect: any site open in
+    /// the browser could otherwise reach `ws://127.0.0.1` and delete blocks.
+    fn upgrade_websocket(&mut self, token_required: bool) -> Result<(), String> {
+        let mut key = None;
+        let mut upgrade = false;
+        let mut origin = None;
+        let mut limited = (&mut self.reader).take(MAX_HANDSHAKE_BYTES);
+        loop {
+            let mut line = String::new();
+            if limited.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                return Err("HTTP запрос оборван".to_string());
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                let value = value.trim();
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "sec-websocket-key" => key = Some(value.to_string()),
+                    "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+                    "origin" => origin = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+        }
+        let Some(key) = key.filter(|_| upgrade) else {
+            let _ = self
+                .writer
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n");
+            return Err("HTTP запрос без WebSocket upgrade".to_string());
+        };
+        if let Some(origin) = origin.filter(|o| !token_required && !is_local_origin(o)) {
+            let _ = self
+                .writer
+                .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n");
+            return Err(format!(
+                "WebSocket со страницы {origin} отклонен — для нелокальных страниц задай network.token"
+            ));
+        }
+        let accept = base64_encode(&sha1(format!("{key}{WS_GUID}").as_bytes()));
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+        );
+        self.writer.write_all(response.as_bytes()).map_err(|e| e.to_string())
+    }
+
+    fn read_handshake(&mut self) -> Result<NetHandshake, String> {
+        let text = match self.transport {
+            Transport::Raw => {
+                let mut line = String::new();
+                (&mut self.reader)
+                    .take(MAX_HANDSHAKE_BYTES)
+                    .read_line(&mut line)
+                    .map_err(|e| format!("handshake: {e}"))?;
+                line
+            }
+            Transport::WebSocket => match self.read_ws_message().map_err(|e| format!("handshake: {e}"))? {
+                Some(WsMessage::Text(text)) => text,
+                Some(WsMessage::Binary(_)) => {
+                    return Err("первым сообщением ожидается текстовый handshake".to_string())
+                }
+                None => return Err("соединение закрыто до handshake".to_string()),
+            },
+        };
+        self.reader.get_mut().deadline = None;
+        serde_json::from_str(text.trim()).map_err(|e| format!("handshake JSON неверен: {e}"))
+    }
+
+    fn reply(&mut self, text: &str) -> io::Result<()> {
+        match self.transport {
+            Transport::Raw => self.writer.write_all(format!("{text}\n").as_bytes()),
+            Transport::WebSocket => write_ws_frame(&mut self.writer, 0x1, text.as_bytes()),
+        }
+    }
+
+    /// Next batch of samples; `None` once the peer closed the connection.
+    fn read_pcm(&mut self) -> io::Result<Option<Vec<i16>>> {
+        let bytes = match self.transport {
+            Transport::Raw => {
+                let mut buf = vec![0u8; 4096];
+                let n = self.reader.read(&mut buf)?;
+                if n == 0 {
+                    return Ok(None);
+                }
+                buf.truncate(n);
+                buf
+            }
+            Transport::WebSocket => loop {
+                match self.read_ws_message()? {
+                    Some(WsMessage::Binary(data)) => break data,
+                    // Text after the handshake is ignored so clients can send keep-alives.
+                    Some(WsMessage::Text(_)) => continue,
+                    None => return Ok(None),
+                }
+            },
+        };
+
+        let mut data = Vec::with_capacity(bytes.len() + 1);
+        data.extend(self.carry.take());
+        data.extend_from_slice(&bytes);
+        if data.len() % 2 == 1 {
+            self.carry = data.pop();
+        }
+        Ok(Some(
+            data.chunks_exact(2)
+                .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+                .collect(),
+        ))
+    }
+
+    /// Reassembles fragmented messages and answers ping/close; `None` after a close frame.
+    fn read_ws_message(&mut self) -> io::Result<Option<WsMessage>> {
+        let mut message: Option<(u8, Vec<u8>)> = None;
+        loop {
+            let mut header = [0u8; 2];
+            if let Err(err) = self.reader.read_exact(&mut header) {
+                return match err.kind() {
+                    ErrorKind::UnexpectedEof => Ok(None),
+                    _ => Err(err),
+                };
+            }
+            let fin = header[0] & 0x80 != 0;
+            let opcode = header[0] & 0x0f;
+            let masked = header[1] & 0x80 != 0;
+            let len = match header[1] & 0x7f {
+                126 => {
+                    let mut ext = [0u8; 2];
+                    self.reader.read_exact(&mut ext)?;
+                    u16::from_be_bytes(ext) as u64
+                }
+                127 => {
+                    let mut ext = [0u8; 8];
+                    self.reader.read_exact(&mut ext)?;
+                    u64::from_be_bytes(ext)
+                }
+                short => short as u64,
+            };
+            let buffered = message.as_ref().map(|(_, data)| data.len()).unwrap_or(0);
+            if len as usize > MAX_WS_MESSAGE_BYTES.saturating_sub(buffered) {
+                return Err(io::Error::new(ErrorKind::InvalidData, "WebSocket сообщение слишком большое"));
+            }
+            let mut mask = [0u8; 4];
+            if masked {
+                self.reader.read_exact(&mut mask)?;
+            }
+            let mut payload = vec![0u8; len as usize];
+            self.reader.read_exact(&mut payload)?;
+            if masked {
+                for (i, byte) in payload.iter_mut().enumerate() {
+                    *byte ^= mask[i % 4];
+                }
+            }
+
+            match opcode {
+                0x8 => {
+                    let _ = write_ws_frame(&mut self.writer, 0x8, &payload);
+                    return Ok(None);
+                }
+                0x9 => write_ws_frame(&mut self.writer, 0xA, &payload)?,
+                0xA => {}
+                0x0 => match message.as_mut() {
+                    Some((_, data)) => data.extend_from_slice(&payload),
+                    None => {
+                        return Err(io::Error::new(ErrorKind::InvalidData, "continuation без начала сообщения"))
+                    }
+                },
+                0x1 | 0x2 => message = Some((opcode, payload)),
+                other => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("неизвестный WebSocket opcode {other}"),
+                    ))
+                }
+            }
+
+            if fin && opcode < 0x8 {
+                if let Some((kind, data)) = message.take() {
+                    return Ok(Some(if kind == 0x1 {
+                        WsMessage::Text(String::from_utf8_lossy(&data).to_string())
+                    } else {
+                        WsMessage::Binary(data)
+                    }));
+                }
+            }
+        }
+    }
+}
+
+fn write_ws_frame(out: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    out.write_all(&frame)
+}
+
+/// `Origin` of a page on this machine: `http(s)://localhost`, `127.x.x.x` or `[::1]`, any port.
+fn is_local_origin(origin: &str) -> bool {
+    let Some((scheme, rest)) = origin.trim().split_once("://") else {
+        return false;
+    };
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return false;
+    }
+    let host = match rest.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => rest.split([':', '/']).next().unwrap_or_default(),
+    };
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Compares without stopping at the first differing byte, so response time does not tell
+/// how much of a guessed token was right.
+fn tokens_equal(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (word, bytes) in w.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (acc, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *acc = acc.wrapping_add(value);
+        }
+    }
+
+    let mut out = [0u8; 20];
+    for (chunk, value) in out.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    out
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (chunk.get(1).copied().unwrap_or(0) as u32) << 8
+            | chunk.get(2).copied().unwrap_or(0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// `--stream-to`: captures the configured input and sends it to a remote BlockDeletee
+/// instance as `speaker`. Logs go to stderr; stops on Ctrl+C or at the end of an input file.
+pub(crate) fn run_stream_client(
+    config: &AppConfig,
+    config_dir: &Path,
+    addr: &str,
+    speaker: &str,
+) -> Result<(), String> {
+    let ui: UiHandle = Arc::new(Mutex::new(UiState::new(
+        config.microphone.player_name.clone(),
+        config.minecraft.rcon_host.clone(),
+        config.minecraft.rcon_port,
+        config.minecraft.rcon_password.clone(),
+        UiMode::Tui,
+    )));
+
+    let mut stream =
+        TcpStream::connect(addr).map_err(|e| format!("Не удалось подключиться к `{addr}`: {e}"))?;
+    let _ = stream.set_nodelay(true);
+    let handshake = serde_json::json!({
+        "speaker": speaker,
+        "sample_rate": STREAM_SAMPLE_RATE,
+        "token": config.network.token,
+    });
+    stream
+        .write_all(format!("{handshake}\n").as_bytes())
+        .map_err(|e| format!("handshake: {e}"))?;
+    stream
+        .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    let mut reply = String::new();
+    BufReader::new(stream.try_clone().map_err(|e| e.to_string())?)
+        .take(MAX_HANDSHAKE_BYTES)
+        .read_line(&mut reply)
+        .map_err(|e| format!("Сервер не ответил на handshake: {e}"))?;
+    match reply.trim() {
+        "OK" => {}
+        "" => return Err("Сервер закрыл соединение во время handshake".to_string()),
+        other => {
+            return Err(format!(
+                "Сервер отклонил подключение: {}",
+                other.strip_prefix("ERR ").unwrap_or(other)
+            ))
+        }
+    }
+    eprintln!("[stream] подключено к {addr} как `{speaker}`, {STREAM_SAMPLE_RATE} Hz");
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    {
+        let shutdown = Arc::clone(&shutdown);
+        ctrlc::set_handler(move || {
+            shutdown.store(true, Ordering::SeqCst);
+        })
+        .map_err(|e| format!("Не удалось установить Ctrl+C handler: {e}"))?;
+    }
+
+    let (pcm_tx, pcm_rx) = bounded::<PcmBlock>(256);
+    let mut source = AudioSource::start_headless(config, config_dir, &ui, pcm_tx)?;
+
+    let mut resampler: Option<Resampler> = None;
+    let mut last_input_rate = 0u32;
+    let mut sent = 0u64;
+    let mut result = Ok(());
+    while !shutdown.load(Ordering::Relaxed) {
+        for line in ui_drain_logs(&ui) {
+            eprintln!("{line}");
+        }
+        let block = match pcm_rx.recv_timeout(POLL_INTERVAL) {
+            Ok(block) => block,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if block.sample_rate != last_input_rate {
+            last_input_rate = block.sample_rate;
+            resampler = (block.sample_rate != STREAM_SAMPLE_RATE)
+                .then(|| Resampler::new(block.sample_rate, STREAM_SAMPLE_RATE));
+        }
+        let pcm = match resampler.as_mut() {
+            Some(r) => r.process(&block.samples),
+            None => block.samples,
+        };
+        let bytes: Vec<u8> = pcm.iter().flat_map(|s| s.to_le_bytes()).collect();
+        if let Err(err) = stream.write_all(&bytes) {
+            result = Err(format!("Соединение с `{addr}` потеряно: {err}"));
+            break;
+        }
+        sent += pcm.len() as u64;
+    }
+
+    source.stop();
+    let _ = stream.shutdown(Shutdown::Write);
+    for line in ui_drain_logs(&ui) {
+        eprintln!("{line}");
+    }
+    eprintln!(
+        "[stream] отправлено {:.1}s аудио",
+        sent as f64 / STREAM_SAMPLE_RATE as f64
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn websocket_accept_key_matches_rfc_6455_sample() {
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        let accept = base64_encode(&sha1(format!("{key}{WS_GUID}").as_bytes()));
+        assert_eq!(accept, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn base64_pads_short_input() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+    }
+
+    #[test]
+    fn only_pages_on_this_machine_are_local() {
+        assert!(is_local_origin("http://localhost:8080"));
+        assert!(is_local_origin("https://127.0.0.1"));
+        assert!(is_local_origin("http://[::1]:3000"));
+        assert!(!is_local_origin("https://evil.example"));
+        assert!(!is_local_origin("http://localhost.evil.example"));
+        assert!(!is_local_origin("null"));
+        assert!(!is_local_origin("file://localhost"));
+    }
+
+    #[test]
+    fn token_comparison() {
+        assert!(tokens_equal("secret", "secret"));
+        assert!(!tokens_equal("secret", "secreT"));
+        assert!(!tokens_equal("secret", "secret2"));
+        assert!(!tokens_equal("", "secret"));
+    }
+}
@@ -0,0 +1,82 @@
+/// Sound-alike key of normalized text, word by word. Voiced and voiceless consonant pairs
+/// merge (г/к, д/т, з/с, b/p, …), vowels reduce to three classes, soft and hard signs and
+/// doubled sounds disappear, so «гамень» and «камень» both become `камин`.
+pub(crate) fn phonetic_key(normalized: &str) -> String {
+    normalized
+        .split_whitespace()
+        .map(word_key)
+        .filter(|key| !key.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub(crate) fn word_key(word: &str) -> String {
+    let chars: Vec<char> = word.chars().collect();
+    let mut key = String::with_capacity(word.len());
+    let mut last = None;
+    let mut i = 0;
+    while i < chars.len() {
+        let next = chars.get(i + 1).copied();
+        let (sound, used) = match (chars[i], next) {
+            ('p', Some('h')) => (Some('f'), 2),
+            ('c', Some('e' | 'i' | 'y')) => (Some('s'), 1),
+            (ch, _) => (sound(ch), 1),
+        };
+        i += used;
+        let Some(sound) = sound else {
+            continue;
+        };
+        if last != Some(sound) {
+            key.push(sound);
+        }
+        last = Some(sound);
+    }
+    key
+}
+
+fn sound(ch: char) -> Option<char> {
+    let sound = match ch {
+        'а' | 'о' | 'я' | 'ы' => 'а',
+        'е' | 'ё' | 'э' | 'и' | 'й' => 'и',
+        'у' | 'ю' => 'у',
+        'б' | 'п' => 'п',
+        'в' | 'ф' => 'ф',
+        'г' | 'к' => 'к',
+        'д' | 'т' => 'т',
+        'ж' | 'ш' | 'щ' => 'ш',
+        'з' | 'с' | 'ц' => 'с',
+        'ь' | 'ъ' => return None,
+        'a' | 'e' | 'i' | 'o' | 'u' | 'y' => 'a',
+        'b' | 'p' => 'p',
+        'd' | 't' => 't',
+        'g' | 'k' | 'q' | 'c' | 'x' => 'k',
+        'v' | 'f' => 'f',
+        'z' | 's' => 's',
+        'h' => return None,
+        other => other,
+    };
+    Some(sound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sound_alike_words_share_a_key() {
+        assert_eq!(phonetic_key("гамень"), "камин");
+        assert_eq!(phonetic_key("камень"), "камин");
+        assert_eq!(phonetic_key("лед"), phonetic_key("лет"));
+        assert_eq!(phonetic_key("земля"), phonetic_key("симля"));
+        assert_ne!(phonetic_key("камень"), phonetic_key("кабель"));
+    }
+
+    #[test]
+    fn keys_drop_signs_and_doubled_sounds() {
+        assert_eq!(word_key("подъезд"), "патист");
+        assert_eq!(word_key("касса"), "каса");
+        assert_eq!(phonetic_key("  алмазная   руда "), "алмасна рута");
+        assert_eq!(word_key("phone"), "fana");
+        assert_eq!(word_key("ice"), "asa");
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use crate::{ui_log, RecordingConfig, UiHandle};
+
+/// How often the WAV header is rewritten, so a crash still leaves a playable file.
+const HEADER_SYNC_SECONDS: u64 = 2;
+
+/// Writes the PCM fed to the recognizer into rotating WAV segments plus an `events.jsonl`
+/// sidecar. Every sidecar line carries the segment name and the offset into it, so a false
+/// trigger can be found and replayed with `--input-file`.
+pub(crate) struct SessionRecorder {
+    dir: PathBuf,
+    sample_rate: u32,
+    segment_samples: u64,
+    max_segments: usize,
+    started: Instant,
+    ui: UiHandle,
+    state: Mutex<RecorderState>,
+}
+
+struct RecorderState {
+    segment: Option<WavSegment>,
+    finished: VecDeque<PathBuf>,
+    next_index: u32,
+    total_samples: u64,
+    events: BufWriter<File>,
+    failed: bool,
+}
+
+struct WavSegment {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    sample_rate: u32,
+    samples: u64,
+    synced_samples: u64,
+}
+
+impl SessionRecorder {
+    pub(crate) fn start(
+        config: &RecordingConfig,
+        base_dir: &Path,
+        sample_rate: u32,
+        ui: UiHandle,
+    ) -> Result<Self, String> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let dir = base_dir.join(format!("session-{stamp}"));
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Не удалось создать каталог записи `{}`: {e}", dir.display()))?;
+        let events_path = dir.join("events.jsonl");
+        let events = File::create(&events_path)
+            .map_err(|e| format!("Не удалось создать `{}`: {e}", events_path.display()))?;
+
+        ui_log(
+            &ui,
+            format!(
+                "[recorder] запись сессии: {} | сегмент={}s | хранить={} файлов",
+                dir.display(),
+                config.segment_seconds,
+                config.max_segments
+            ),
+        );
+        Ok(Self {
+            dir,
+            sample_rate,
+            segment_samples: sample_rate as u64 * config.segment_seconds.max(1),
+            max_segments: config.max_segments.max(1),
+            started: Instant::now(),
+            ui,
+            state: Mutex::new(RecorderState {
+                segment: None,
+                finished: VecDeque::new(),
+                next_index: 1,
+                total_samples: 0,
+                events: BufWriter::new(events),
+                failed: false,
+            }),
+        })
+    }
+
+    pub(crate) fn write_pcm(&self, samples: &[i16]) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if state.failed || samples.is_empty() {
+            return;
+        }
+        if let Err(err) = self.write_locked(&mut state, samples) {
+            state.failed = true;
+            ui_log(&self.ui, format!("[recorder-error] запись остановлена: {err}"));
+        }
+    }
+
+    /// Appends one line to `events.jsonl`; `kind` is `partial`, `final` or `trigger`.
+    pub(crate) fn note_event(&self, kind: &str, fields: Value) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if state.failed {
+            return;
+        }
+        let (segment, offset) = match &state.segment {
+            Some(seg) => (
+                seg.path.file_name().map(|n| n.to_string_lossy().to_string()),
+                seg.samples as f64 / self.sample_rate as f64,
+            ),
+            None => (None, 0.0),
+        };
+        let wall_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let mut line = json!({
+            "kind": kind,
+            "wall_ms": wall_ms,
+            "session_s": round_ms(self.started.elapsed().as_secs_f64()),
+            "audio_s": round_ms(state.total_samples as f64 / self.sample_rate as f64),
+            "segment": segment,
+            "segment_offset_s": round_ms(offset),
+        });
+        if let (Some(line), Value::Object(extra)) = (line.as_object_mut(), fields) {
+            line.extend(extra);
+        }
+        let result = writeln!(state.events, "{line}").and_then(|_| state.events.flush());
+        if let Err(err) = result {
+            state.failed = true;
+            ui_log(&self.ui, format!("[recorder-error] events.jsonl: {err}"));
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        if let Some(segment) = state.segment.take() {
+            let _ = segment.close();
+        }
+        let _ = state.events.flush();
+        ui_log(
+            &self.ui,
+            format!(
+                "[recorder] сохранено {:.1}s аудио в {}",
+                state.total_samples as f64 / self.sample_rate as f64,
+                self.dir.display()
+            ),
+        );
+    }
+
+    fn write_locked(&self, state: &mut RecorderState, mut samples: &[i16]) -> Result<(), String> {
+        while !samples.is_empty() {
+            if state.segment.is_none() {
+                let path = self.dir.join(format!("segment-{:04}.wav", state.next_index));
+                state.next_index += 1;
+                state.segment = Some(WavSegment::create(path, self.sample_rate)?);
+            }
+            let segment = state.segment.as_mut().expect("segment just created");
+            let room = (self.segment_samples - segment.samples) as usize;
+            let (now, rest) = samples.split_at(room.min(samples.len()));
+            segment.append(now)?;
+            state.total_samples += now.len() as u64;
+            samples = rest;
+
+            if segment.samples >= self.segment_samples {
+                let segment = state.segment.take().expect("segment present");
+                state.finished.push_back(segment.close()?);
+                while state.finished.len() >= self.max_segments {
+                    if let Some(old) = state.finished.pop_front() {
+                        let _ = fs::remove_file(&old);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WavSegment {
+    fn create(path: PathBuf, sample_rate: u32) -> Result<Self, String> {
+        let file = File::create(&path)
+            .map_err(|e| format!("Не удалось создать `{}`: {e}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        write_wav_header(&mut writer, sample_rate, 0).map_err(|e| e.to_string())?;
+        Ok(Self {
+            path,
+            writer,
+            sample_rate,
+            samples: 0,
+            synced_samples: 0,
+        })
+    }
+
+    fn append(&mut self, samples: &[i16]) -> Result<(), String> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.writer.write_all(&bytes).map_err(|e| e.to_string())?;
+        self.samples += samples.len() as u64;
+        if self.samples - self.synced_samples >= self.sample_rate as u64 * HEADER_SYNC_SECONDS {
+            self.sync_header().map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn sync_header(&mut self) -> std::io::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        write_wav_header(&mut self.writer, self.sample_rate, self.samples)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        self.synced_samples = self.samples;
+        Ok(())
+    }
+
+    fn close(mut self) -> Result<PathBuf, String> {
+        self.sync_header().map_err(|e| e.to_string())?;
+        Ok(self.path)
+    }
+}
+
+pub(crate) fn write_wav_header(out: &mut impl Write, sample_rate: u32, samples: u64) -> std::io::Result<()> {
+    let data_len = (samples * 2).min(u32::MAX as u64 - 36) as u32;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?; // PCM
+    out.write_all(&1u16.to_le_bytes())?; // mono
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * 2).to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())
+}
+
+fn round_ms(seconds: f64) -> f64 {
+    (seconds * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{UiMode, UiState};
+
+    /// (RIFF size, data size, file size) of a finished segment.
+    fn wav_sizes(path: &Path) -> (u32, u32, u64) {
+        let bytes = fs::read(path).unwrap();
+        let field = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[36..40], b"data");
+        (field(4), field(40), bytes.len() as u64)
+    }
+
+    #[test]
+    fn rotation_keeps_the_newest_segments_and_events_point_into_them() {
+        let base = std::env::temp_dir().join(format!("blockdeletee-recorder-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let config = RecordingConfig {
+            enabled: true,
+            dir: base.display().to_string(),
+            segment_seconds: 1,
+            max_segments: 3,
+        };
+        let ui = Arc::new(Mutex::new(UiState::new(
+            "Steve".to_string(),
+            "127.0.0.1".to_string(),
+            25575,
+            String::new(),
+            UiMode::Tui,
+        )));
+        // 100 Hz keeps a segment at 100 samples.
+        let recorder = SessionRecorder::start(&config, &base, 100, ui).unwrap();
+
+        // 4.5 segments' worth, in blocks that straddle the segment boundaries.
+        let audio: Vec<i16> = (0..450).map(|i| i as i16).collect();
+        for (index, block) in audio.chunks(70).enumerate() {
+            recorder.write_pcm(block);
+            if index == 1 {
+                recorder.note_event("partial", json!({ "text": "камень" }));
+            }
+        }
+        recorder.note_event("trigger", json!({ "block": "minecraft:stone" }));
+        recorder.finish();
+
+        let mut names: Vec<String> = fs::read_dir(&recorder.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["events.jsonl", "segment-0003.wav", "segment-0004.wav", "segment-0005.wav"]
+        );
+        for (name, samples) in [("segment-0003.wav", 100), ("segment-0004.wav", 100), ("segment-0005.wav", 50)] {
+            assert_eq!(
+                wav_sizes(&recorder.dir.join(name)),
+                (36 + samples * 2, samples * 2, 44 + samples as u64 * 2),
+                "{name}"
+            );
+        }
+        let third = fs::read(recorder.dir.join("segment-0003.wav")).unwrap();
+        assert_eq!(i16::from_le_bytes([third[44], third[45]]), 200);
+
+        let events: Vec<Value> = fs::read_to_string(recorder.dir.join("events.jsonl"))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["kind"], "partial");
+        assert_eq!(events[0]["text"], "камень");
+        assert_eq!(events[0]["segment"], "segment-0002.wav");
+        assert_eq!(events[0]["segment_offset_s"], 0.4);
+        assert_eq!(events[0]["audio_s"], 1.4);
+        assert_eq!(events[1]["block"], "minecraft:stone");
+        assert_eq!(events[1]["segment"], "segment-0005.wav");
+        assert_eq!(events[1]["segment_offset_s"], 0.5);
+        assert_eq!(events[1]["audio_s"], 4.5);
+
+        let _ = fs::remove_dir_all(&base);
+    }
+}
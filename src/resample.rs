@@ -0,0 +1,173 @@
+use std::f64::consts::PI;
+
+/// Kernel half-width in input samples; 16 keeps aliasing well below what Vosk notices.
+const HALF_TAPS: usize = 16;
+/// Number of precomputed fractional phases of the interpolation kernel.
+const PHASES: usize = 256;
+
+/// Streaming windowed-sinc resampler for mono i16 PCM.
+///
+/// State is carried between calls, so blocks of any size can be fed in sequence
+/// without clicks at block boundaries.
+pub(crate) struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    step: f64,
+    table: Vec<f32>,
+    history: Vec<f32>,
+    position: f64,
+}
+
+impl Resampler {
+    pub(crate) fn new(input_rate: u32, output_rate: u32) -> Self {
+        let step = input_rate as f64 / output_rate as f64;
+        // When downsampling the kernel doubles as the anti-aliasing low-pass filter.
+        let cutoff = (output_rate as f64 / input_rate as f64).min(1.0) * 0.95;
+        let taps = 2 * HALF_TAPS;
+        let mut table = vec![0.0f32; (PHASES + 1) * taps];
+        for phase in 0..=PHASES {
+            let frac = phase as f64 / PHASES as f64;
+            for tap in 0..taps {
+                // Distance between the output instant and input sample `floor(t) - HALF_TAPS + 1 + tap`.
+                let x = frac + HALF_TAPS as f64 - 1.0 - tap as f64;
+                table[phase * taps + tap] = kernel(x, cutoff) as f32;
+            }
+        }
+
+        Self {
+            input_rate,
+            output_rate,
+            step,
+            table,
+            history: vec![0.0; HALF_TAPS],
+            position: HALF_TAPS as f64,
+        }
+    }
+
+    pub(crate) fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub(crate) fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    pub(crate) fn process(&mut self, input: &[i16]) -> Vec<i16> {
+        self.history.extend(input.iter().map(|v| *v as f32));
+        let taps = 2 * HALF_TAPS;
+        let mut out = Vec::with_capacity((input.len() as f64 / self.step) as usize + 1);
+
+        while (self.position.floor() as usize) + HALF_TAPS < self.history.len() {
+            let base = self.position.floor() as usize;
+            let phase = ((self.position - base as f64) * PHASES as f64).round() as usize;
+            let coeffs = &self.table[phase * taps..(phase + 1) * taps];
+            let window = &self.history[base + 1 - HALF_TAPS..=base + HALF_TAPS];
+            let acc: f32 = window.iter().zip(coeffs).map(|(s, c)| s * c).sum();
+            out.push(acc.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+            self.position += self.step;
+        }
+
+        let consumed = (self.position.floor() as usize).saturating_sub(HALF_TAPS);
+        if consumed > 0 {
+            self.history.drain(..consumed.min(self.history.len()));
+            self.position -= consumed as f64;
+        }
+        out
+    }
+}
+
+fn kernel(x: f64, cutoff: f64) -> f64 {
+    if x.abs() >= HALF_TAPS as f64 {
+        return 0.0;
+    }
+    let sinc = if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * cutoff * x).sin() / (PI * cutoff * x)
+    };
+    let window = 0.5 * (1.0 + (PI * x / HALF_TAPS as f64).cos());
+    cutoff * sinc * window
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Odd block sizes, so block edges fall on every kernel phase.
+    const BLOCKS: [usize; 5] = [1, 37, 160, 441, 1024];
+
+    fn tone(rate: u32, hz: f64, amplitude: f64, seconds: f64) -> Vec<i16> {
+        (0..(rate as f64 * seconds) as usize)
+            .map(|i| (amplitude * (i as f64 * hz * 2.0 * PI / rate as f64).sin()).round() as i16)
+            .collect()
+    }
+
+    fn in_blocks(resampler: &mut Resampler, input: &[i16]) -> Vec<i16> {
+        let mut out = Vec::new();
+        let mut start = 0;
+        for size in BLOCKS.iter().cycle() {
+            if start >= input.len() {
+                break;
+            }
+            let end = (start + size).min(input.len());
+            out.extend(resampler.process(&input[start..end]));
+            start = end;
+        }
+        out
+    }
+
+    #[test]
+    fn output_length_follows_the_rate_ratio() {
+        for input_rate in [44_100, 48_000] {
+            let input = tone(input_rate, 440.0, 10_000.0, 2.0);
+            let mut resampler = Resampler::new(input_rate, 16_000);
+            let out = in_blocks(&mut resampler, &input);
+            // Only the kernel's look-ahead is still held back.
+            let expected = input.len() as f64 * 16_000.0 / input_rate as f64;
+            let held = HALF_TAPS as f64 * 16_000.0 / input_rate as f64;
+            assert!(
+                (out.len() as f64 - expected).abs() <= held + 1.0,
+                "{input_rate}: {} vs {expected}",
+                out.len()
+            );
+        }
+    }
+
+    #[test]
+    fn block_edges_leave_no_trace() {
+        let input = tone(44_100, 440.0, 10_000.0, 1.0);
+        let whole = Resampler::new(44_100, 16_000).process(&input);
+        let blocked = in_blocks(&mut Resampler::new(44_100, 16_000), &input);
+        assert_eq!(blocked, whole);
+    }
+
+    #[test]
+    fn amplitude_survives() {
+        let mut resampler = Resampler::new(48_000, 16_000);
+        let dc = in_blocks(&mut resampler, &vec![10_000; 48_000]);
+        // Past the kernel's start-up the level is flat.
+        for sample in &dc[HALF_TAPS..] {
+            assert!((*sample - 10_000).abs() <= 50, "DC {sample}");
+        }
+
+        let mut resampler = Resampler::new(44_100, 16_000);
+        let sine = in_blocks(&mut resampler, &tone(44_100, 440.0, 10_000.0, 1.0));
+        let peak = sine[HALF_TAPS..].iter().map(|v| v.unsigned_abs()).max().unwrap();
+        assert!((9_800..=10_200).contains(&peak), "peak {peak}");
+        // The steepest step of a 440 Hz tone at 16 kHz is about 1730.
+        let max_step = sine[HALF_TAPS..].windows(2).map(|w| (w[1] - w[0]).unsigned_abs()).max().unwrap();
+        assert!(max_step <= 1_800, "step {max_step}");
+    }
+
+    #[test]
+    fn history_stays_bounded() {
+        let mut resampler = Resampler::new(44_100, 16_000);
+        let input = tone(44_100, 440.0, 10_000.0, 0.1);
+        for _ in 0..200 {
+            for size in BLOCKS {
+                resampler.process(&input[..size]);
+                assert!(resampler.history.len() <= 2 * HALF_TAPS + 4, "{}", resampler.history.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,494 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use vosk::{set_log_level, CompleteResult, DecodingState, LogLevel, Model, Recognizer, SpeakerModel, Word};
+
+use crate::{resolve_path, ui_log, SpeechConfig, SpeechEngineKind, UiHandle};
+
+/// One recognition hypothesis.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Hypothesis {
+    pub(crate) text: String,
+    /// 0..1 for the whole phrase when the engine reports it.
+    pub(crate) confidence: Option<f32>,
+    /// Per-word details; empty when the engine does not report them.
+    pub(crate) words: Vec<WordInfo>,
+    /// N-best list, most likely first (`text` is the first entry); empty unless the engine
+    /// was asked for alternatives.
+    pub(crate) alternatives: Vec<Alternative>,
+    /// Voice of a final result; only Vosk with `speech.speaker_model_path` and no N-best.
+    pub(crate) speaker: Option<SpeakerVector>,
+}
+
+/// Vosk x-vector of the voice in one utterance.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SpeakerVector {
+    pub(crate) vector: Vec<f32>,
+    /// Speech frames the vector was computed from; short phrases give unreliable vectors.
+    pub(crate) frames: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Alternative {
+    pub(crate) text: String,
+    /// Engine score, higher is better. Only comparable within one hypothesis.
+    pub(crate) score: f32,
+}
+
+impl Alternative {
+    /// Share of each alternative in `alternatives`, summing to 1. Vosk scores are lattice
+    /// log-likelihoods, so a softmax over them is the posterior of each path among the N-best.
+    pub(crate) fn weights(alternatives: &[Alternative]) -> Vec<f32> {
+        let best = alternatives.iter().map(|a| a.score).fold(f32::NEG_INFINITY, f32::max);
+        let exp: Vec<f32> = alternatives.iter().map(|a| (a.score - best).exp()).collect();
+        let total: f32 = exp.iter().sum();
+        exp.into_iter().map(|e| e / total).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct WordInfo {
+    pub(crate) word: String,
+    /// 0..1, `None` when only the phrase has a score.
+    pub(crate) confidence: Option<f32>,
+    /// Seconds from the start of the stream.
+    pub(crate) start: f32,
+    pub(crate) end: f32,
+}
+
+impl WordInfo {
+    fn from_vosk(word: &Word<'_>) -> Self {
+        Self {
+            word: word.word.to_string(),
+            confidence: Some(word.conf),
+            start: word.start,
+            end: word.end,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SpeechOutput {
+    /// Unstable text of the utterance in progress.
+    Partial(Hypothesis),
+    /// The utterance is over.
+    Final(Hypothesis),
+}
+
+/// A loaded recognizer backend. Shared between the microphone and network connections;
+/// every audio stream opens its own [`SpeechStream`].
+pub(crate) trait SpeechEngine: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// `grammar` limits recognition to these phrases where the engine supports it.
+    fn open_stream(
+        &self,
+        sample_rate: u32,
+        grammar: Option<&[String]>,
+        ui: &UiHandle,
+    ) -> Result<Box<dyn SpeechStream>, String>;
+}
+
+pub(crate) trait SpeechStream: Send {
+    /// Feeds mono PCM at the rate given to `open_stream`.
+    fn accept(&mut self, pcm: &[i16]) -> Result<Vec<SpeechOutput>, String>;
+    /// Finalizes whatever was said so far.
+    fn flush(&mut self) -> Option<Hypothesis>;
+    /// Drops the unfinished utterance.
+    fn reset(&mut self);
+}
+
+pub(crate) fn load_engine(config: &SpeechConfig, config_dir: &Path) -> Result<Arc<dyn SpeechEngine>, String> {
+    match config.engine {
+        SpeechEngineKind::Vosk => {
+            let speaker_model = config.speaker_model_path.as_deref().map(|p| resolve_path(config_dir, p));
+            let engine = VoskEngine::load(
+                &resolve_path(config_dir, &config.model_path),
+                config.max_alternatives,
+                speaker_model.as_deref(),
+            )?;
+            Ok(Arc::new(engine))
+        }
+        SpeechEngineKind::Scripted => {
+            let script = config
+                .script
+                .as_deref()
+                .ok_or_else(|| "speech.engine=scripted, но speech.script не задан".to_string())?;
+            let engine = ScriptedEngine::load(&resolve_path(config_dir, script))?;
+            Ok(Arc::new(engine))
+        }
+        #[cfg(feature = "whisper")]
+        SpeechEngineKind::Whisper => {
+            let engine = crate::whisper_engine::WhisperEngine::new(&config.whisper, config_dir)?;
+            Ok(Arc::new(engine))
+        }
+        #[cfg(not(feature = "whisper"))]
+        SpeechEngineKind::Whisper => Err(
+            "speech.engine=whisper, но сборка без него: cargo build --features whisper".to_string(),
+        ),
+    }
+}
+
+pub(crate) struct VoskEngine {
+    model: Model,
+    max_alternatives: u16,
+    /// Adds an x-vector to every final result.
+    speaker_model: Option<SpeakerModel>,
+}
+
+impl VoskEngine {
+    pub(crate) fn load(path: &Path, max_alternatives: u16, speaker_model: Option<&Path>) -> Result<Self, String> {
+        set_log_level(LogLevel::Warn);
+        let model = Model::new(path.to_string_lossy().to_string())
+            .ok_or_else(|| format!("Не удалось загрузить Vosk model из `{}`", path.display()))?;
+        let speaker_model = speaker_model
+            .map(|path| {
+                SpeakerModel::new(path.to_string_lossy().to_string())
+                    .ok_or_else(|| format!("Не удалось загрузить Vosk speaker model из `{}`", path.display()))
+            })
+            .transpose()?;
+        Ok(Self {
+            model,
+            max_alternatives,
+            speaker_model,
+        })
+    }
+}
+
+impl SpeechEngine for VoskEngine {
+    fn name(&self) -> &'static str {
+        "vosk"
+    }
+
+    fn open_stream(
+        &self,
+        sample_rate: u32,
+        grammar: Option<&[String]>,
+        ui: &UiHandle,
+    ) -> Result<Box<dyn SpeechStream>, String> {
+        let mut recognizer = match grammar.filter(|p| !p.is_empty()) {
+            Some(phrases) => {
+                let refs: Vec<&str> = phrases.iter().map(|s| s.as_str()).collect();
+                match Recognizer::new_with_grammar(&self.model, sample_rate as f32, &refs) {
+                    Some(r) => Some(r),
+                    None => {
+                        ui_log(
+                            ui,
+                            "[recognizer-warning] grammar mode unavailable for current model, fallback to default"
+                                .to_string(),
+                        );
+                        Recognizer::new(&self.model, sample_rate as f32)
+                    }
+                }
+            }
+            None => Recognizer::new(&self.model, sample_rate as f32),
+        }
+        .ok_or_else(|| "Не удалось создать Vosk recognizer".to_string())?;
+        recognizer.set_words(true);
+        recognizer.set_partial_words(true);
+        recognizer.set_max_alternatives(self.max_alternatives);
+        if let Some(speaker_model) = &self.speaker_model {
+            recognizer.set_speaker_model(speaker_model);
+        }
+        Ok(Box::new(VoskStream { recognizer }))
+    }
+}
+
+struct VoskStream {
+    recognizer: Recognizer,
+}
+
+impl SpeechStream for VoskStream {
+    fn accept(&mut self, pcm: &[i16]) -> Result<Vec<SpeechOutput>, String> {
+        match self.recognizer.accept_waveform(pcm) {
+            Ok(DecodingState::Finalized) => Ok(hypothesis_from_result(self.recognizer.result())
+                .map(SpeechOutput::Final)
+                .into_iter()
+                .collect()),
+            Ok(DecodingState::Running) => {
+                let result = self.recognizer.partial_result();
+                let partial = result.partial.trim().to_string();
+                Ok(if partial.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![SpeechOutput::Partial(Hypothesis {
+                        text: partial,
+                        confidence: None,
+                        words: result.partial_result.iter().map(WordInfo::from_vosk).collect(),
+                        alternatives: Vec::new(),
+                        speaker: None,
+                    })]
+                })
+            }
+            Ok(DecodingState::Failed) => Err("Vosk decoding failed".to_string()),
+            Err(err) => Err(format!("accept_waveform: {err}")),
+        }
+    }
+
+    fn flush(&mut self) -> Option<Hypothesis> {
+        hypothesis_from_result(self.recognizer.final_result())
+    }
+
+    fn reset(&mut self) {
+        self.recognizer.reset();
+    }
+}
+
+fn hypothesis_from_result(result: CompleteResult<'_>) -> Option<Hypothesis> {
+    let hypothesis = match result {
+        CompleteResult::Single(single) => Hypothesis {
+            text: single.text.trim().to_string(),
+            confidence: None,
+            words: single.result.iter().map(WordInfo::from_vosk).collect(),
+            alternatives: Vec::new(),
+            speaker: single.speaker_info.map(|info| SpeakerVector {
+                vector: info.vector,
+                frames: info.frames,
+            }),
+        },
+        // Alternative scores are lattice log-likelihoods, not 0..1, so they are not reported
+        // as confidence; see `Alternative::weights`.
+        CompleteResult::Multiple(multi) => {
+            let best = multi.alternatives.first()?;
+            Hypothesis {
+                text: best.text.trim().to_string(),
+                confidence: None,
+                words: best
+                    .result
+                    .iter()
+                    .map(|w| WordInfo {
+                        word: w.word.to_string(),
+                        confidence: None,
+                        start: w.start,
+                        end: w.end,
+                    })
+                    .collect(),
+                alternatives: multi
+                    .alternatives
+                    .iter()
+                    .map(|a| Alternative {
+                        text: a.text.trim().to_string(),
+                        score: a.confidence,
+                    })
+                    .filter(|a| !a.text.is_empty())
+                    .collect(),
+                speaker: None,
+            }
+        }
+    };
+    (!hypothesis.text.is_empty()).then_some(hypothesis)
+}
+
+/// One line of a script: at `at_seconds` of stream audio, emit `output`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ScriptedLine {
+    pub(crate) at_seconds: f64,
+    pub(crate) output: SpeechOutput,
+}
+
+/// Replays a fixed list of hypotheses against the audio clock of each stream, so the event
+/// pipeline can be driven without a model on disk.
+///
+/// Script file: one `<seconds> <partial|final>[:confidence] <text>` per line, `#` starts a comment.
+/// `a | b | c` in the text is an N-best list, scored as if each were `e` times less likely
+/// than the previous one.
+/// ```text
+/// 1.0 partial кам
+/// 1.4 final:0.92 камень
+/// 3.0 final кабель | камень
+/// ```
+pub(crate) struct ScriptedEngine {
+    pub(crate) lines: Vec<ScriptedLine>,
+}
+
+impl ScriptedEngine {
+    pub(crate) fn new(mut lines: Vec<ScriptedLine>) -> Self {
+        lines.sort_by(|a, b| a.at_seconds.total_cmp(&b.at_seconds));
+        Self { lines }
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let raw = fs::read_to_string(path)
+            .map_err(|e| format!("Не удалось прочитать speech.script `{}`: {e}", path.display()))?;
+        Self::parse(&raw).map_err(|e| format!("speech.script `{}`: {e}", path.display()))
+    }
+
+    pub(crate) fn parse(script: &str) -> Result<Self, String> {
+        let mut lines = Vec::new();
+        for (number, line) in script.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let at_seconds = parts
+                .next()
+                .and_then(|s| s.parse::<f64>().ok())
+                .filter(|s| *s >= 0.0)
+                .ok_or_else(|| format!("строка {}: ожидалось время в секундах", number + 1))?;
+            let kind = parts.next().unwrap_or_default();
+            let (kind, confidence) = match kind.split_once(':') {
+                Some((kind, confidence)) => {
+                    let confidence = confidence
+                        .parse::<f32>()
+                        .map_err(|_| format!("строка {}: неверная confidence `{confidence}`", number + 1))?;
+                    (kind, Some(confidence.clamp(0.0, 1.0)))
+                }
+                None => (kind, None),
+            };
+            let texts: Vec<String> = parts
+                .next()
+                .unwrap_or_default()
+                .split('|')
+                .map(|text| text.trim().to_string())
+                .collect();
+            let alternatives = if texts.len() > 1 {
+                texts
+                    .iter()
+                    .enumerate()
+                    .map(|(rank, text)| Alternative {
+                        text: text.clone(),
+                        score: -(rank as f32),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let hypothesis = Hypothesis {
+                text: texts[0].clone(),
+                confidence,
+                words: Vec::new(),
+                alternatives,
+                speaker: None,
+            };
+            let output = match kind {
+                "partial" => SpeechOutput::Partial(hypothesis),
+                "final" => SpeechOutput::Final(hypothesis),
+                other => return Err(format!("строка {}: `{other}` — ожидалось partial или final", number + 1)),
+            };
+            lines.push(ScriptedLine { at_seconds, output });
+        }
+        Ok(Self::new(lines))
+    }
+}
+
+impl SpeechEngine for ScriptedEngine {
+    fn name(&self) -> &'static str {
+        "scripted"
+    }
+
+    fn open_stream(
+        &self,
+        sample_rate: u32,
+        _grammar: Option<&[String]>,
+        _ui: &UiHandle,
+    ) -> Result<Box<dyn SpeechStream>, String> {
+        Ok(Box::new(ScriptedStream {
+            lines: self.lines.clone(),
+            next: 0,
+            sample_rate: sample_rate.max(1),
+            samples: 0,
+            pending: None,
+        }))
+    }
+}
+
+struct ScriptedStream {
+    lines: Vec<ScriptedLine>,
+    next: usize,
+    sample_rate: u32,
+    samples: u64,
+    /// Last partial not yet followed by a final; `flush` turns it into one.
+    pending: Option<Hypothesis>,
+}
+
+impl SpeechStream for ScriptedStream {
+    fn accept(&mut self, pcm: &[i16]) -> Result<Vec<SpeechOutput>, String> {
+        self.samples += pcm.len() as u64;
+        let now = self.samples as f64 / self.sample_rate as f64;
+        let mut out = Vec::new();
+        while let Some(line) = self.lines.get(self.next).filter(|l| l.at_seconds <= now) {
+            self.pending = match &line.output {
+                SpeechOutput::Partial(h) => Some(h.clone()),
+                SpeechOutput::Final(_) => None,
+            };
+            out.push(line.output.clone());
+            self.next += 1;
+        }
+        Ok(out)
+    }
+
+    fn flush(&mut self) -> Option<Hypothesis> {
+        self.pending.take()
+    }
+
+    fn reset(&mut self) {
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hypothesis(output: &SpeechOutput) -> (&'static str, &Hypothesis) {
+        match output {
+            SpeechOutput::Partial(hypothesis) => ("partial", hypothesis),
+            SpeechOutput::Final(hypothesis) => ("final", hypothesis),
+        }
+    }
+
+    #[test]
+    fn script_lines_are_parsed_and_sorted() {
+        let engine = ScriptedEngine::parse(
+            "# заголовок\n\
+             3.0 final кабель | камень | камни\n\
+             \n\
+             1.0 partial кам   # еще не слово\n\
+             1.4 final:0.92 алмазная руда\n\
+             2.0 final:7 песок\n",
+        )
+        .unwrap();
+        let lines: Vec<(f64, &str, &Hypothesis)> = engine
+            .lines
+            .iter()
+            .map(|line| {
+                let (kind, hypothesis) = hypothesis(&line.output);
+                (line.at_seconds, kind, hypothesis)
+            })
+            .collect();
+        assert_eq!(lines.len(), 4);
+
+        let (at, kind, partial) = lines[0];
+        assert_eq!((at, kind, partial.text.as_str(), partial.confidence), (1.0, "partial", "кам", None));
+
+        let (at, kind, scored) = lines[1];
+        assert_eq!((at, kind, scored.text.as_str()), (1.4, "final", "алмазная руда"));
+        assert_eq!(scored.confidence, Some(0.92));
+        assert!(scored.alternatives.is_empty());
+
+        // Confidence is clamped to 0..1.
+        assert_eq!(lines[2].2.confidence, Some(1.0));
+
+        let (at, _, nbest) = lines[3];
+        assert_eq!((at, nbest.text.as_str()), (3.0, "кабель"));
+        let alternatives: Vec<(&str, f32)> = nbest
+            .alternatives
+            .iter()
+            .map(|a| (a.text.as_str(), a.score))
+            .collect();
+        assert_eq!(alternatives, [("кабель", 0.0), ("камень", -1.0), ("камни", -2.0)]);
+    }
+
+    #[test]
+    fn malformed_script_lines_name_the_line() {
+        let error = |script: &str| ScriptedEngine::parse(script).err().unwrap();
+        assert_eq!(error("1.0 final камень\nсейчас final камень"), "строка 2: ожидалось время в секундах");
+        assert_eq!(error("-1 final камень"), "строка 1: ожидалось время в секундах");
+        assert_eq!(error("# ok\n1.0 final:высокая камень"), "строка 2: неверная confidence `высокая`");
+        assert_eq!(error("1.0 result камень"), "строка 1: `result` — ожидалось partial или final");
+        assert_eq!(error("1.0"), "строка 1: `` — ожидалось partial или final");
+        assert!(ScriptedEngine::parse("# только комментарий\n\n").unwrap().lines.is_empty());
+    }
+}
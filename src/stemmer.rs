@@ -0,0 +1,177 @@
+use crate::models::base_language;
+
+/// Strips inflection so «камня», «камнем» and «камень» compare equal. Words in another
+/// script are left alone, so every stemmer can run over mixed-language text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Stemmer {
+    /// Snowball Russian plus folding of fleeting vowels («камен» -> «камн»).
+    Russian,
+}
+
+impl Stemmer {
+    pub(crate) fn for_locale(locale: &str) -> Option<Self> {
+        match base_language(locale) {
+            "ru" => Some(Self::Russian),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn stem(self, word: &str) -> String {
+        match self {
+            Self::Russian => stem_russian(word),
+        }
+    }
+}
+
+/// Every word of normalized `text` through each stemmer.
+pub(crate) fn stem_text(text: &str, stemmers: &[Stemmer]) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            stemmers
+                .iter()
+                .fold(word.to_string(), |word, stemmer| stemmer.stem(&word))
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+const VOWELS: [char; 9] = ['а', 'е', 'и', 'о', 'у', 'ы', 'э', 'ю', 'я'];
+
+const PERFECTIVE_GERUND_1: &[&str] = &["в", "вши", "вшись"];
+const PERFECTIVE_GERUND_2: &[&str] = &["ив", "ивши", "ившись", "ыв", "ывши", "ывшись"];
+const ADJECTIVE: &[&str] = &[
+    "ее", "ие", "ые", "ое", "ими", "ыми", "ей", "ий", "ый", "ой", "ем", "им", "ым", "ом", "его", "ого", "ему",
+    "ому", "их", "ых", "ую", "юю", "ая", "яя", "ою", "ею",
+];
+const PARTICIPLE_1: &[&str] = &["ем", "нн", "вш", "ющ", "щ"];
+const PARTICIPLE_2: &[&str] = &["ивш", "ывш", "ующ"];
+const REFLEXIVE: &[&str] = &["ся", "сь"];
+const VERB_1: &[&str] = &[
+    "ла", "на", "ете", "йте", "ли", "й", "л", "ем", "н", "ло", "но", "ет", "ют", "ны", "ть", "ешь", "нно",
+];
+const VERB_2: &[&str] = &[
+    "ила", "ыла", "ена", "ейте", "уйте", "ите", "или", "ыли", "ей", "уй", "ил", "ыл", "им", "ым", "ен", "ило",
+    "ыло", "ено", "ят", "ует", "уют", "ит", "ыт", "ены", "ить", "ыть", "ишь", "ую", "ю",
+];
+const NOUN: &[&str] = &[
+    "а", "ев", "ов", "ие", "ье", "е", "иями", "ями", "ами", "еи", "ии", "и", "ией", "ей", "ой", "ий", "й", "иям",
+    "ям", "ием", "ем", "ам", "ом", "о", "у", "ах", "иях", "ях", "ы", "ь", "ию", "ью", "ю", "ия", "ья", "я",
+];
+const DERIVATIONAL: &[&str] = &["ост", "ость"];
+const SUPERLATIVE: &[&str] = &["ейш", "ейше"];
+
+fn stem_russian(word: &str) -> String {
+    let mut w: Vec<char> = word.chars().collect();
+    if w.is_empty() || !w.iter().all(|c| ('а'..='я').contains(c) || *c == 'ё') {
+        return word.to_string();
+    }
+    for c in &mut w {
+        if *c == 'ё' {
+            *c = 'е';
+        }
+    }
+    let is_vowel = |c: &char| VOWELS.contains(c);
+    let rv = w.iter().position(is_vowel).map_or(w.len(), |i| i + 1);
+    let r1 = next_region(&w, 0);
+    let r2 = next_region(&w, r1);
+
+    // Step 1
+    if !strip_after_a(&mut w, rv, PERFECTIVE_GERUND_1) && !strip(&mut w, rv, PERFECTIVE_GERUND_2) {
+        strip(&mut w, rv, REFLEXIVE);
+        if strip(&mut w, rv, ADJECTIVE) {
+            if !strip_after_a(&mut w, rv, PARTICIPLE_1) {
+                strip(&mut w, rv, PARTICIPLE_2);
+            }
+        } else if !strip_after_a(&mut w, rv, VERB_1) && !strip(&mut w, rv, VERB_2) {
+            strip(&mut w, rv, NOUN);
+        }
+    }
+    // Step 2
+    strip(&mut w, rv, &["и"]);
+    // Step 3
+    strip(&mut w, r2, DERIVATIONAL);
+    // Step 4
+    if !strip(&mut w, rv, &["ь"]) {
+        strip(&mut w, rv, SUPERLATIVE);
+        if w.len() > rv && w.ends_with(&['н', 'н']) {
+            w.pop();
+        }
+    }
+
+    // Fleeting vowels: «камень»/«камня», «песок»/«песка», «уголь»/«угля».
+    let n = w.len();
+    if n >= 4
+        && matches!(w[n - 2], 'е' | 'о')
+        && matches!(w[n - 1], 'н' | 'к' | 'ц' | 'л' | 'р')
+        && !is_vowel(&w[n - 3])
+    {
+        w.remove(n - 2);
+    }
+    w.into_iter().collect()
+}
+
+/// Start of the region after the first non-vowel that follows a vowel, from `from` on.
+fn next_region(w: &[char], from: usize) -> usize {
+    (from + 1..w.len())
+        .find(|&i| !VOWELS.contains(&w[i]) && VOWELS.contains(&w[i - 1]))
+        .map_or(w.len(), |i| i + 1)
+}
+
+/// Removes the longest of `suffixes` that lies within `w[limit..]`.
+fn strip(w: &mut Vec<char>, limit: usize, suffixes: &[&str]) -> bool {
+    strip_where(w, limit, suffixes, |_| true)
+}
+
+/// Like `strip`, for endings that only count after а or я (which stays).
+fn strip_after_a(w: &mut Vec<char>, limit: usize, suffixes: &[&str]) -> bool {
+    strip_where(w, limit, suffixes, |before| {
+        before.last().is_some_and(|c| matches!(c, 'а' | 'я')) && before.len() > limit
+    })
+}
+
+fn strip_where(w: &mut Vec<char>, limit: usize, suffixes: &[&str], condition: impl Fn(&[char]) -> bool) -> bool {
+    let mut candidates: Vec<Vec<char>> = suffixes.iter().map(|s| s.chars().collect()).collect();
+    candidates.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    for suffix in candidates {
+        if w.len() < suffix.len() || w.len() - suffix.len() < limit || !w.ends_with(&suffix) {
+            continue;
+        }
+        let cut = w.len() - suffix.len();
+        if condition(&w[..cut]) {
+            w.truncate(cut);
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stem(text: &str) -> String {
+        stem_text(text, &[Stemmer::Russian])
+    }
+
+    #[test]
+    fn inflected_forms_share_a_stem() {
+        for (forms, expected) in [
+            (&["камень", "камня", "камнем", "камни"][..], "камн"),
+            (&["песок", "песка", "песком"], "песк"),
+            (&["уголь", "угля", "углем"], "угл"),
+            (&["алмазная руда", "алмазную руду", "алмазной руды"], "алмазн руд"),
+        ] {
+            for form in forms {
+                assert_eq!(stem(form), expected, "{form}");
+            }
+        }
+    }
+
+    #[test]
+    fn other_scripts_and_short_words_are_kept() {
+        assert_eq!(stem("stone камень"), "stone камн");
+        assert_eq!(stem("лед"), "лед");
+        assert_eq!(Stemmer::for_locale("en_us"), None);
+        assert_eq!(Stemmer::for_locale("ru_ru"), Some(Stemmer::Russian));
+    }
+}
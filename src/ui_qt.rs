@@ -2,11 +2,14 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, OnceLock};
 use std::thread;
 
+use crossterm::event::KeyCode;
 use qmetaobject::prelude::*;
+use qmetaobject::QVariantList;
 
 use crate::backend_bootstrap::BackendBootstrap;
 use crate::{
-    restart_current_process, ui_log, ui_snapshot, BlockDeleteController, UiHandle, UiMode, UiState,
+    restart_current_process, ui_log, ui_snapshot, BlockDeleteController, InputDeviceInfo, ModelInfo,
+    UiHandle, UiMode, UiState,
 };
 
 static QT_CTX: OnceLock<QtFrontendContext> = OnceLock::new();
@@ -33,6 +36,21 @@ struct QtBackendBridge {
     rcon_ok_changed: qt_signal!(),
     player_online: qt_property!(bool; NOTIFY player_online_changed),
     player_online_changed: qt_signal!(),
+    vad_enabled: qt_property!(bool; NOTIFY vad_enabled_changed),
+    vad_enabled_changed: qt_signal!(),
+    listening: qt_property!(bool; NOTIFY listening_changed),
+    listening_changed: qt_signal!(),
+    input_level_active: qt_property!(bool; NOTIFY input_level_changed),
+    input_level_db: qt_property!(f64; NOTIFY input_level_changed),
+    input_peak_db: qt_property!(f64; NOTIFY input_level_changed),
+    input_clipping: qt_property!(bool; NOTIFY input_level_changed),
+    input_level_changed: qt_signal!(),
+    activation_mode: qt_property!(QString; NOTIFY activation_mode_changed),
+    activation_mode_changed: qt_signal!(),
+    activation_key_code: qt_property!(i32; NOTIFY activation_key_code_changed),
+    activation_key_code_changed: qt_signal!(),
+    talk_open: qt_property!(bool; NOTIFY talk_open_changed),
+    talk_open_changed: qt_signal!(),
 
     player_name: qt_property!(QString; NOTIFY player_name_changed),
     player_name_changed: qt_signal!(),
@@ -44,6 +62,11 @@ struct QtBackendBridge {
     logs_border_color_changed: qt_signal!(),
     overlay_error: qt_property!(QString; NOTIFY overlay_error_changed),
     overlay_error_changed: qt_signal!(),
+    // Debug panel: recent block matches, newest last.
+    matches_text: qt_property!(QString; NOTIFY matches_text_changed),
+    matches_text_changed: qt_signal!(),
+    matches_open: qt_property!(bool; NOTIFY matches_open_changed),
+    matches_open_changed: qt_signal!(),
 
     settings_open: qt_property!(bool; NOTIFY settings_open_changed),
     settings_open_changed: qt_signal!(),
@@ -57,6 +80,18 @@ struct QtBackendBridge {
     settings_player_name_changed: qt_signal!(),
     settings_ui_mode: qt_property!(QString; NOTIFY settings_ui_mode_changed),
     settings_ui_mode_changed: qt_signal!(),
+    // Labels for the Microphone tab; row 0 is the system default device.
+    input_devices: qt_property!(QVariantList; NOTIFY input_devices_changed),
+    input_devices_changed: qt_signal!(),
+    settings_mic_index: qt_property!(i32; NOTIFY settings_mic_index_changed),
+    settings_mic_index_changed: qt_signal!(),
+    device_list: Vec<InputDeviceInfo>,
+    // Labels for the Model tab; -1 when the model in use is not in `speech.models_dir`.
+    models: qt_property!(QVariantList; NOTIFY models_changed),
+    models_changed: qt_signal!(),
+    settings_model_index: qt_property!(i32; NOTIFY settings_model_index_changed),
+    settings_model_index_changed: qt_signal!(),
+    model_list: Vec<ModelInfo>,
 
     tick: qt_method!(fn tick(&mut self) {
         let snap = ui_snapshot(&qt_ctx().ui);
@@ -76,6 +111,42 @@ struct QtBackendBridge {
             self.player_online = snap.player_online;
             self.player_online_changed();
         }
+        if self.vad_enabled != snap.listening.is_some() {
+            self.vad_enabled = snap.listening.is_some();
+            self.vad_enabled_changed();
+        }
+        let listening = snap.listening.unwrap_or(false);
+        if self.listening != listening {
+            self.listening = listening;
+            self.listening_changed();
+        }
+        let (active, level_db, peak_db, clipping) = match snap.input_level {
+            Some(level) => (true, level.rms_db as f64, level.peak_db as f64, level.clipping),
+            None => (false, -90.0, -90.0, false),
+        };
+        if self.input_level_active != active
+            || self.input_level_db != level_db
+            || self.input_peak_db != peak_db
+            || self.input_clipping != clipping
+        {
+            self.input_level_active = active;
+            self.input_level_db = level_db;
+            self.input_peak_db = peak_db;
+            self.input_clipping = clipping;
+            self.input_level_changed();
+        }
+        let talk_open = snap.voice_gate.unwrap_or(true);
+        if self.talk_open != talk_open {
+            self.talk_open = talk_open;
+            self.talk_open_changed();
+        }
+        if self.activation_mode.to_string().is_empty() {
+            let (mode, key) = qt_ctx().controller.mic_activation();
+            self.activation_mode = mode.as_config_str().to_string().into();
+            self.activation_key_code = qt_key_code(key);
+            self.activation_mode_changed();
+            self.activation_key_code_changed();
+        }
 
         let player_name: QString = snap.player_name.into();
         if self.player_name != player_name {
@@ -114,6 +185,17 @@ struct QtBackendBridge {
             self.overlay_error = overlay_qs;
             self.overlay_error_changed();
         }
+
+        let matches_qs: QString = snap.matches.join("\n").into();
+        if self.matches_text != matches_qs {
+            self.matches_text = matches_qs;
+            self.matches_text_changed();
+        }
+    }),
+
+    toggle_matches: qt_method!(fn toggle_matches(&mut self) {
+        self.matches_open = !self.matches_open;
+        self.matches_open_changed();
     }),
 
     open_settings: qt_method!(fn open_settings(&mut self) {
@@ -130,6 +212,68 @@ struct QtBackendBridge {
         self.settings_player_name_changed();
         self.settings_ui_mode_changed();
         self.settings_open_changed();
+        self.refresh_input_devices();
+        self.refresh_models();
+    }),
+
+    refresh_input_devices: qt_method!(fn refresh_input_devices(&mut self) {
+        let (devices, selected) = match qt_ctx().controller.input_device_choices() {
+            Ok(choices) => choices,
+            Err(err) => {
+                ui_log(&qt_ctx().ui, format!("[settings-error] {err}"));
+                (Vec::new(), None)
+            }
+        };
+        self.input_devices = std::iter::once(QString::from("По умолчанию (система)"))
+            .chain(devices.iter().map(|d| QString::from(d.label())))
+            .collect();
+        self.settings_mic_index = selected.map(|i| i as i32 + 1).unwrap_or(0);
+        self.device_list = devices;
+        self.input_devices_changed();
+        self.settings_mic_index_changed();
+    }),
+
+    select_input_device: qt_method!(fn select_input_device(&mut self, index: i32) {
+        let device = if index <= 0 {
+            None
+        } else {
+            self.device_list.get(index as usize - 1)
+        };
+        match qt_ctx().controller.select_input_device(device) {
+            Ok(()) => {
+                self.settings_mic_index = index.max(0);
+                self.settings_mic_index_changed();
+            }
+            Err(err) => ui_log(&qt_ctx().ui, format!("[settings-error] {err}")),
+        }
+    }),
+
+    refresh_models: qt_method!(fn refresh_models(&mut self) {
+        let (models, selected) = match qt_ctx().controller.model_choices() {
+            Ok(choices) => choices,
+            Err(err) => {
+                ui_log(&qt_ctx().ui, format!("[settings-error] {err}"));
+                (Vec::new(), None)
+            }
+        };
+        self.models = models.iter().map(|m| QString::from(m.label())).collect();
+        self.settings_model_index = selected.map(|i| i as i32).unwrap_or(-1);
+        self.model_list = models;
+        self.models_changed();
+        self.settings_model_index_changed();
+    }),
+
+    select_model: qt_method!(fn select_model(&mut self, index: i32) {
+        let Some(model) = usize::try_from(index).ok().and_then(|i| self.model_list.get(i)) else {
+            return;
+        };
+        match qt_ctx().controller.select_model(model) {
+            Ok(()) => {
+                self.settings_model_index = index;
+                self.settings_model_index_changed();
+            }
+            Err(err) => ui_log(&qt_ctx().ui, format!("[settings-error] {err}")),
+        }
     }),
 
     close_settings: qt_method!(fn close_settings(&mut self) {
@@ -199,6 +343,14 @@ struct QtBackendBridge {
         }
     }),
 
+    set_talk: qt_method!(fn set_talk(&mut self, open: bool) {
+        qt_ctx().controller.set_voice_gate(open);
+    }),
+
+    toggle_talk: qt_method!(fn toggle_talk(&mut self) {
+        qt_ctx().controller.toggle_voice_gate();
+    }),
+
     request_exit: qt_method!(fn request_exit(&mut self) {
         qt_ctx().shutdown.store(true, Ordering::SeqCst);
     }),
@@ -206,7 +358,7 @@ struct QtBackendBridge {
 
 pub(crate) fn run_qt_mode(bootstrap: BackendBootstrap) -> Result<(), String> {
     let ui: UiHandle = Arc::new(std::sync::Mutex::new(UiState::new(
-        bootstrap.config.microphone.player_name.clone(),
+        bootstrap.config.mic_player_name().unwrap_or_default(),
         bootstrap.config.minecraft.rcon_host.clone(),
         bootstrap.config.minecraft.rcon_port,
         bootstrap.config.minecraft.rcon_password.clone(),
@@ -244,6 +396,17 @@ pub(crate) fn run_qt_mode(bootstrap: BackendBootstrap) -> Result<(), String> {
     Ok(())
 }
 
+/// Maps the configured activation key to a `Qt::Key` value for QML key handlers.
+fn qt_key_code(key: KeyCode) -> i32 {
+    match key {
+        KeyCode::Char(' ') => 0x20,
+        KeyCode::Tab => 0x0100_0001,
+        KeyCode::F(n) => 0x0100_0030 + (n as i32 - 1),
+        KeyCode::Char(c) => c.to_uppercase().next().unwrap_or(c) as i32,
+        _ => 0,
+    }
+}
+
 fn logs_to_html(lines: &[String]) -> String {
     if lines.is_empty() {
         return "<span style=\"color:#9aa3b2;\">Ожидание событий...</span>".to_string();
@@ -337,6 +500,27 @@ ApplicationWindow {
         backend.request_exit()
     }
 
+    Item {
+        id: keyCatcher
+        focus: true
+        Keys.onPressed: (event) => {
+            if (event.key !== backend.activation_key_code || event.isAutoRepeat || backend.settings_open)
+                return
+            if (backend.activation_mode === "push_to_talk")
+                backend.set_talk(true)
+            else if (backend.activation_mode === "toggle")
+                backend.toggle_talk()
+            event.accepted = true
+        }
+        Keys.onReleased: (event) => {
+            if (event.key !== backend.activation_key_code || event.isAutoRepeat)
+                return
+            if (backend.activation_mode === "push_to_talk")
+                backend.set_talk(false)
+            event.accepted = true
+        }
+    }
+
     ColumnLayout {
         anchors.fill: parent
         anchors.margins: 14
@@ -368,12 +552,63 @@ ApplicationWindow {
                     Label { text: "󰋎 REC " + (backend.rec_ok ? "●" : "●" + " !"); color: root.badgeColor(backend.rec_ok); font.bold: !backend.rec_ok }
                     Label { text: "󰒓 RCON " + (backend.rcon_ok ? "●" : "●" + " !"); color: root.badgeColor(backend.rcon_ok); font.bold: !backend.rcon_ok }
                     Label { text: "󰀄 PLAYER " + (backend.player_online ? "●" : "●" + " !"); color: root.badgeColor(backend.player_online); font.bold: !backend.player_online }
+                    Label { visible: backend.activation_mode !== "always"; text: backend.talk_open ? "󰍬 TALK ●" : "󰍭 MUTED ●"; color: backend.talk_open ? "#26c281" : "#f2c14e"; font.bold: true }
+                    Label { visible: backend.vad_enabled; text: "󰍬 VAD ●"; color: backend.listening ? "#26c281" : "#7c8698"; font.bold: backend.listening }
                 }
 
-                Label {
-                    text: "Игрок: " + backend.player_name
-                    color: "#b9c0cc"
-                    font.pixelSize: 14
+                RowLayout {
+                    spacing: 18
+
+                    Label {
+                        text: "Игрок: " + backend.player_name
+                        color: "#b9c0cc"
+                        font.pixelSize: 14
+                    }
+
+                    RowLayout {
+                        spacing: 8
+
+                        Label { text: "󰕾 LVL"; color: backend.input_level_active ? "#b9c0cc" : "#7c8698" }
+
+                        Rectangle {
+                            id: levelMeter
+                            function fraction(db) { return Math.max(0, Math.min(1, (db + 60) / 60)) }
+                            Layout.preferredWidth: 180
+                            Layout.preferredHeight: 10
+                            radius: 3
+                            color: "#0f1115"
+                            border.width: 1
+                            border.color: "#2a2f38"
+
+                            Rectangle {
+                                x: 1
+                                y: 1
+                                height: parent.height - 2
+                                width: (parent.width - 2) * levelMeter.fraction(backend.input_level_db)
+                                radius: 2
+                                visible: backend.input_level_active
+                                color: backend.input_clipping ? "#e25555" : (backend.input_level_db > -18 ? "#f2c14e" : "#26c281")
+                            }
+
+                            Rectangle {
+                                visible: backend.input_level_active && backend.input_peak_db > -60
+                                x: 1 + (parent.width - 4) * levelMeter.fraction(backend.input_peak_db)
+                                y: 1
+                                width: 2
+                                height: parent.height - 2
+                                color: backend.input_peak_db > -3 ? "#e25555" : "#e6e6e6"
+                            }
+                        }
+
+                        Label {
+                            text: !backend.input_level_active
+                                ? "нет сигнала"
+                                : (backend.input_level_db <= -90 ? "-inf dB" : Math.round(backend.input_level_db) + " dB")
+                            color: backend.input_level_active ? "#b9c0cc" : "#e25555"
+                        }
+
+                        Label { visible: backend.input_clipping; text: "CLIP"; color: "#e25555"; font.bold: true }
+                    }
                 }
             }
         }
@@ -416,6 +651,44 @@ ApplicationWindow {
             }
         }
 
+        Rectangle {
+            visible: backend.matches_open
+            Layout.fillWidth: true
+            Layout.preferredHeight: 180
+            radius: 14
+            color: "#171a1f"
+            border.width: 2
+            border.color: "#49a7ff"
+
+            ColumnLayout {
+                anchors.fill: parent
+                anchors.margins: 10
+                spacing: 8
+
+                Label {
+                    text: "󰍉 Matches"
+                    color: "white"
+                    font.bold: true
+                }
+
+                ScrollView {
+                    Layout.fillWidth: true
+                    Layout.fillHeight: true
+                    clip: true
+
+                    TextArea {
+                        text: backend.matches_text.length > 0 ? backend.matches_text : "Совпадений еще не было"
+                        readOnly: true
+                        selectByMouse: true
+                        wrapMode: TextEdit.Wrap
+                        color: "#d7dbe2"
+                        background: null
+                        font.family: "monospace"
+                    }
+                }
+            }
+        }
+
         Rectangle {
             Layout.fillWidth: true
             Layout.preferredHeight: 64
@@ -446,6 +719,28 @@ ApplicationWindow {
                     }
                 }
 
+                FrameButton {
+                    text: "Совпадения"
+                    frameColor: backend.matches_open ? "#49a7ff" : "#3a404b"
+                    accentColor: "#49a7ff"
+                    onClicked: backend.toggle_matches()
+                }
+
+                FrameButton {
+                    visible: backend.activation_mode === "push_to_talk" || backend.activation_mode === "toggle"
+                    implicitWidth: 170
+                    focusPolicy: Qt.NoFocus
+                    text: backend.activation_mode === "push_to_talk"
+                        ? "Говорить (удерж.)"
+                        : (backend.talk_open ? "Выкл. микрофон" : "Вкл. микрофон")
+                    frameColor: backend.talk_open ? "#26c281" : "#f2c14e"
+                    accentColor: frameColor
+                    onPressed: if (backend.activation_mode === "push_to_talk") backend.set_talk(true)
+                    onReleased: if (backend.activation_mode === "push_to_talk") backend.set_talk(false)
+                    onCanceled: if (backend.activation_mode === "push_to_talk") backend.set_talk(false)
+                    onClicked: if (backend.activation_mode === "toggle") backend.toggle_talk()
+                }
+
                 Item { Layout.fillWidth: true }
             }
         }
@@ -496,7 +791,10 @@ ApplicationWindow {
         height: Math.min(root.height - 24, 430)
         visible: backend.settings_open
 
-        onVisibleChanged: if (!visible) backend.close_settings()
+        onVisibleChanged: if (!visible) {
+            backend.close_settings()
+            keyCatcher.forceActiveFocus()
+        }
         background: Rectangle {
             radius: 14
             color: "#171a1f"
@@ -555,6 +853,42 @@ ApplicationWindow {
                         border.color: "#2f3540"
                     }
                 }
+                TabButton {
+                    text: "Microphone"
+                    font.bold: true
+                    onClicked: backend.refresh_input_devices()
+                    contentItem: Label {
+                        text: parent.text
+                        color: parent.checked ? "#101214" : "#d7dbe2"
+                        horizontalAlignment: Text.AlignHCenter
+                        verticalAlignment: Text.AlignVCenter
+                        font.bold: true
+                    }
+                    background: Rectangle {
+                        radius: 8
+                        color: parent.checked ? "#49a7ff" : "transparent"
+                        border.width: parent.checked ? 0 : 1
+                        border.color: "#2f3540"
+                    }
+                }
+                TabButton {
+                    text: "Model"
+                    font.bold: true
+                    onClicked: backend.refresh_models()
+                    contentItem: Label {
+                        text: parent.text
+                        color: parent.checked ? "#101214" : "#d7dbe2"
+                        horizontalAlignment: Text.AlignHCenter
+                        verticalAlignment: Text.AlignVCenter
+                        font.bold: true
+                    }
+                    background: Rectangle {
+                        radius: 8
+                        color: parent.checked ? "#49a7ff" : "transparent"
+                        border.width: parent.checked ? 0 : 1
+                        border.color: "#2f3540"
+                    }
+                }
             }
 
             StackLayout {
@@ -680,6 +1014,110 @@ ApplicationWindow {
                     }
                     Item { Layout.fillHeight: true; Layout.minimumHeight: 0 }
                 }
+
+                ColumnLayout {
+                    spacing: 8
+                    RowLayout {
+                        Layout.fillWidth: true
+                        Label { text: "Устройство ввода"; color: "#9ecfff"; Layout.fillWidth: true }
+                        FrameButton {
+                            text: "Обновить"
+                            frameColor: "#8b93a1"
+                            accentColor: "#8b93a1"
+                            onClicked: backend.refresh_input_devices()
+                        }
+                    }
+
+                    ListView {
+                        id: deviceList
+                        Layout.fillWidth: true
+                        Layout.fillHeight: true
+                        Layout.minimumHeight: 0
+                        clip: true
+                        spacing: 4
+                        model: backend.input_devices
+                        delegate: ItemDelegate {
+                            width: deviceList.width
+                            onClicked: backend.select_input_device(index)
+                            contentItem: Label {
+                                text: (index === backend.settings_mic_index ? "● " : "○ ") + modelData
+                                color: index === backend.settings_mic_index ? "#26c281" : "#ffffff"
+                                font.bold: index === backend.settings_mic_index
+                                elide: Text.ElideRight
+                                verticalAlignment: Text.AlignVCenter
+                                leftPadding: 10
+                            }
+                            background: Rectangle {
+                                radius: 8
+                                color: parent.hovered ? "#232a33" : "transparent"
+                                border.width: index === backend.settings_mic_index ? 1 : 0
+                                border.color: "#26c281"
+                            }
+                        }
+                    }
+
+                    Label {
+                        text: "Микрофон переключается сразу и сохраняется в config.json"
+                        color: "#f2c14e"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
+                }
+
+                ColumnLayout {
+                    spacing: 8
+                    RowLayout {
+                        Layout.fillWidth: true
+                        Label { text: "Модель Vosk"; color: "#9ecfff"; Layout.fillWidth: true }
+                        FrameButton {
+                            text: "Обновить"
+                            frameColor: "#8b93a1"
+                            accentColor: "#8b93a1"
+                            onClicked: backend.refresh_models()
+                        }
+                    }
+
+                    Label {
+                        visible: backend.models.length === 0
+                        text: "Моделей не найдено (speech.models_dir)"
+                        color: "#8b93a1"
+                    }
+
+                    ListView {
+                        id: modelList
+                        Layout.fillWidth: true
+                        Layout.fillHeight: true
+                        Layout.minimumHeight: 0
+                        clip: true
+                        spacing: 4
+                        model: backend.models
+                        delegate: ItemDelegate {
+                            width: modelList.width
+                            onClicked: backend.select_model(index)
+                            contentItem: Label {
+                                text: (index === backend.settings_model_index ? "● " : "○ ") + modelData
+                                color: index === backend.settings_model_index ? "#26c281" : "#ffffff"
+                                font.bold: index === backend.settings_model_index
+                                elide: Text.ElideRight
+                                verticalAlignment: Text.AlignVCenter
+                                leftPadding: 10
+                            }
+                            background: Rectangle {
+                                radius: 8
+                                color: parent.hovered ? "#232a33" : "transparent"
+                                border.width: index === backend.settings_model_index ? 1 : 0
+                                border.color: "#26c281"
+                            }
+                        }
+                    }
+
+                    Label {
+                        text: "Модель загружается в фоне, распознавание переключается без перезапуска; выбор сохраняется в config.json"
+                        color: "#f2c14e"
+                        wrapMode: Text.Wrap
+                        Layout.fillWidth: true
+                    }
+                }
             }
 
             RowLayout {
@@ -5,7 +5,7 @@ use crate::{UiState, UiHandle};
 
 pub(crate) fn run_tui_mode(bootstrap: BackendBootstrap) -> Result<(), String> {
     let ui: UiHandle = Arc::new(Mutex::new(UiState::new(
-        bootstrap.config.microphone.player_name.clone(),
+        bootstrap.config.mic_player_name().unwrap_or_default(),
         bootstrap.config.minecraft.rcon_host.clone(),
         bootstrap.config.minecraft.rcon_port,
         bootstrap.config.minecraft.rcon_password.clone(),
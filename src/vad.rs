@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+
+use crate::VadConfig;
+
+/// Analysis frame length; 20 ms is short enough for a responsive gate and long enough for a stable ZCR.
+const FRAME_MS: u32 = 20;
+
+/// Result of feeding one block of PCM through [`VoiceActivityGate`].
+#[derive(Debug, Default)]
+pub(crate) struct GateOutput {
+    /// Samples that should reach the recognizer (pre-roll included when the gate just opened).
+    pub(crate) forwarded: Vec<i16>,
+    pub(crate) opened: bool,
+    pub(crate) closed: bool,
+}
+
+/// Energy + zero-crossing voice activity gate.
+///
+/// A frame counts as speech when its RMS level is above `threshold_db` (dBFS) and its
+/// zero-crossing rate is below `max_zcr`, which filters out most clicks and hiss.
+/// The gate stays open for `hangover_ms` after the last speech frame and replays
+/// `preroll_ms` of audio from before the opening so word onsets are not cut.
+pub(crate) struct VoiceActivityGate {
+    frame_len: usize,
+    threshold: f64,
+    max_zcr: f64,
+    hangover_frames: usize,
+    preroll_capacity: usize,
+    preroll: VecDeque<i16>,
+    pending: Vec<i16>,
+    open: bool,
+    silent_frames: usize,
+}
+
+impl VoiceActivityGate {
+    pub(crate) fn new(config: &VadConfig, sample_rate: u32) -> Self {
+        let frame_len = (sample_rate * FRAME_MS / 1000).max(1) as usize;
+        Self {
+            frame_len,
+            threshold: 10f64.powf(config.threshold_db / 20.0) * i16::MAX as f64,
+            max_zcr: config.max_zcr,
+            hangover_frames: (config.hangover_ms / FRAME_MS) as usize,
+            preroll_capacity: (sample_rate as u64 * config.preroll_ms as u64 / 1000) as usize,
+            preroll: VecDeque::new(),
+            pending: Vec::new(),
+            open: false,
+            silent_frames: 0,
+        }
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.open = false;
+        self.silent_frames = 0;
+        self.pending.clear();
+        self.preroll.clear();
+    }
+
+    pub(crate) fn process(&mut self, pcm: &[i16]) -> GateOutput {
+        let mut out = GateOutput::default();
+        self.pending.extend_from_slice(pcm);
+        let whole = self.pending.len() - self.pending.len() % self.frame_len;
+        let frames: Vec<i16> = self.pending.drain(..whole).collect();
+
+        for frame in frames.chunks(self.frame_len) {
+            let speech = self.is_speech(frame);
+            if self.open {
+                out.forwarded.extend_from_slice(frame);
+                if speech {
+                    self.silent_frames = 0;
+                } else {
+                    self.silent_frames += 1;
+                    if self.silent_frames > self.hangover_frames {
+                        self.open = false;
+                        self.silent_frames = 0;
+                        out.closed = true;
+                    }
+                }
+            } else if speech {
+                self.open = true;
+                self.silent_frames = 0;
+                out.opened = true;
+                out.forwarded.extend(self.preroll.drain(..));
+                out.forwarded.extend_from_slice(frame);
+            } else {
+                self.preroll.extend(frame.iter().copied());
+                let excess = self.preroll.len().saturating_sub(self.preroll_capacity);
+                self.preroll.drain(..excess);
+            }
+        }
+        out
+    }
+
+    fn is_speech(&self, frame: &[i16]) -> bool {
+        let energy: f64 = frame.iter().map(|v| (*v as f64) * (*v as f64)).sum();
+        let rms = (energy / frame.len() as f64).sqrt();
+        if rms < self.threshold {
+            return false;
+        }
+        let crossings = frame
+            .windows(2)
+            .filter(|w| (w[0] >= 0) != (w[1] >= 0))
+            .count();
+        let zcr = crossings as f64 / frame.len().saturating_sub(1).max(1) as f64;
+        zcr <= self.max_zcr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+    /// 20 ms at 16 kHz.
+    const FRAME: usize = 320;
+
+    fn gate() -> VoiceActivityGate {
+        let config = VadConfig {
+            enabled: true,
+            threshold_db: -40.0,
+            hangover_ms: 100,
+            preroll_ms: 60,
+            max_zcr: 0.3,
+        };
+        VoiceActivityGate::new(&config, RATE)
+    }
+
+    /// 200 Hz tone, far above -40 dBFS with a ZCR of 0.025.
+    fn voice() -> Vec<i16> {
+        (0..FRAME)
+            .map(|i| (10_000.0 * (i as f64 * 200.0 * std::f64::consts::TAU / RATE as f64).sin()) as i16)
+            .collect()
+    }
+
+    /// A constant far below the threshold; the value tells frames apart in the pre-roll.
+    fn quiet(level: i16) -> Vec<i16> {
+        vec![level; FRAME]
+    }
+
+    #[test]
+    fn loud_low_zcr_frame_opens_with_preroll() {
+        let mut gate = gate();
+        for level in 1..=5 {
+            let out = gate.process(&quiet(level));
+            assert!(!out.opened && out.forwarded.is_empty());
+        }
+        let out = gate.process(&voice());
+        assert!(out.opened && gate.is_open());
+        // 60 ms of pre-roll: the last three quiet frames, then the frame that opened it.
+        let expected: Vec<i16> = [quiet(3), quiet(4), quiet(5), voice()].concat();
+        assert_eq!(out.forwarded, expected);
+    }
+
+    #[test]
+    fn gate_holds_for_hangover_then_closes() {
+        let mut gate = gate();
+        assert!(gate.process(&voice()).opened);
+        // 100 ms = 5 silent frames still go through.
+        for _ in 0..5 {
+            let out = gate.process(&quiet(0));
+            assert!(!out.closed && gate.is_open());
+            assert_eq!(out.forwarded.len(), FRAME);
+        }
+        let out = gate.process(&quiet(0));
+        assert!(out.closed && !gate.is_open());
+        assert_eq!(out.forwarded.len(), FRAME);
+        assert!(gate.process(&quiet(0)).forwarded.is_empty());
+    }
+
+    #[test]
+    fn speech_resets_the_hangover() {
+        let mut gate = gate();
+        gate.process(&voice());
+        let mut audio = Vec::new();
+        for _ in 0..4 {
+            audio.extend(quiet(0));
+        }
+        audio.extend(voice());
+        for _ in 0..5 {
+            audio.extend(quiet(0));
+        }
+        let out = gate.process(&audio);
+        assert!(!out.closed && gate.is_open());
+        assert_eq!(out.forwarded.len(), audio.len());
+    }
+
+    #[test]
+    fn loud_high_zcr_noise_stays_out() {
+        let mut gate = gate();
+        let hiss: Vec<i16> = (0..FRAME).map(|i| if i % 2 == 0 { 10_000 } else { -10_000 }).collect();
+        let out = gate.process(&[hiss.clone(), hiss].concat());
+        assert!(!out.opened && out.forwarded.is_empty() && !gate.is_open());
+    }
+
+    #[test]
+    fn partial_frames_carry_over_between_calls() {
+        let mut gate = gate();
+        let voice = voice();
+        let out = gate.process(&voice[..100]);
+        assert!(!out.opened && out.forwarded.is_empty());
+        let out = gate.process(&voice[100..]);
+        assert!(out.opened);
+        assert_eq!(out.forwarded, voice);
+
+        // A reset drops the carried samples along with the pre-roll.
+        gate.reset();
+        gate.process(&voice[..100]);
+        gate.reset();
+        assert!(!gate.process(&voice[..FRAME - 100]).opened);
+    }
+}
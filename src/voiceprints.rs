@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crossbeam_channel::{bounded, RecvTimeoutError};
+use serde::{Deserialize, Serialize};
+
+use crate::resample::Resampler;
+use crate::speech::{Hypothesis, SpeakerVector, SpeechEngine, SpeechOutput, VoskEngine};
+use crate::{resolve_path, ui_drain_logs, AppConfig, AudioSource, PcmBlock, SpeechConfig, UiHandle, UiMode, UiState};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Averaged x-vector of one enrolled speaker.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Voiceprint {
+    pub(crate) vector: Vec<f32>,
+    /// Speech frames behind the average; new samples are merged in proportion to it.
+    pub(crate) frames: u32,
+}
+
+impl Voiceprint {
+    /// Frame-weighted mean of the unit-length vectors; `None` without any usable vector.
+    pub(crate) fn average(voices: &[SpeakerVector]) -> Option<Self> {
+        let mut sum: Vec<f32> = Vec::new();
+        let mut frames = 0u32;
+        for voice in voices.iter().filter(|v| v.frames > 0 && !v.vector.is_empty()) {
+            if sum.is_empty() {
+                sum = vec![0.0; voice.vector.len()];
+            }
+            if voice.vector.len() != sum.len() {
+                continue;
+            }
+            let weight = voice.frames as f32;
+            for (acc, value) in sum.iter_mut().zip(unit(&voice.vector)) {
+                *acc += value * weight;
+            }
+            frames += voice.frames as u32;
+        }
+        (frames > 0).then(|| Self {
+            vector: unit(&sum),
+            frames,
+        })
+    }
+
+    fn merge(&mut self, other: Voiceprint) {
+        if self.vector.len() != other.vector.len() {
+            *self = other;
+            return;
+        }
+        let (a, b) = (self.frames as f32, other.frames as f32);
+        let mixed: Vec<f32> = self
+            .vector
+            .iter()
+            .zip(&other.vector)
+            .map(|(x, y)| x * a + y * b)
+            .collect();
+        self.vector = unit(&mixed);
+        self.frames += other.frames;
+    }
+}
+
+/// `speech.voiceprints_file`: speaker id -> voiceprint.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Voiceprints {
+    path: PathBuf,
+    entries: BTreeMap<String, Voiceprint>,
+}
+
+impl Voiceprints {
+    /// A missing file is an empty set.
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let entries = if path.exists() {
+            let raw = fs::read_to_string(path)
+                .map_err(|e| format!("Не удалось прочитать voiceprints `{}`: {e}", path.display()))?;
+            serde_json::from_str(&raw).map_err(|e| format!("voiceprints `{}`: {e}", path.display()))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub(crate) fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.entries).map_err(|e| e.to_string())?;
+        fs::write(&self.path, json + "\n")
+            .map_err(|e| format!("Не удалось записать voiceprints `{}`: {e}", self.path.display()))
+    }
+
+    /// Adds a sample to the speaker's voiceprint, or starts one.
+    pub(crate) fn enroll(&mut self, speaker_id: &str, voiceprint: Voiceprint) {
+        match self.entries.get_mut(speaker_id) {
+            Some(existing) => existing.merge(voiceprint),
+            None => {
+                self.entries.insert(speaker_id.to_string(), voiceprint);
+            }
+        }
+    }
+
+    pub(crate) fn speaker_ids(&self) -> Vec<&str> {
+        self.entries.keys().map(String::as_str).collect()
+    }
+
+    /// Enrolled speaker nearest to `vector` and the cosine distance to them.
+    pub(crate) fn closest(&self, vector: &[f32]) -> Option<(&str, f32)> {
+        self.entries
+            .iter()
+            .filter(|(_, print)| print.vector.len() == vector.len())
+            .map(|(id, print)| (id.as_str(), cosine_distance(&print.vector, vector)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+/// Tags final results of the shared microphone with the closest enrolled voice.
+pub(crate) struct SpeakerIdentifier {
+    pub(crate) voiceprints: Voiceprints,
+    pub(crate) max_distance: f32,
+}
+
+impl SpeakerIdentifier {
+    /// `None` when `speech.speaker_model_path` is not set.
+    pub(crate) fn load(config: &SpeechConfig, config_dir: &Path) -> Result<Option<Self>, String> {
+        if config.speaker_model_path.is_none() {
+            return Ok(None);
+        }
+        let path = resolve_path(config_dir, &config.voiceprints_file);
+        let voiceprints = Voiceprints::load(&path)?;
+        if voiceprints.entries.is_empty() {
+            return Err(format!(
+                "в `{}` нет голосов — запиши их: blockdeletee enroll <speaker>",
+                path.display()
+            ));
+        }
+        Ok(Some(Self {
+            voiceprints,
+            max_distance: config.speaker_max_distance,
+        }))
+    }
+}
+
+/// `1 - cos`: 0 for the same direction, 1 for unrelated vectors, up to 2.
+pub(crate) fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator <= f32::EPSILON {
+        return 1.0;
+    }
+    1.0 - dot / denominator
+}
+
+fn unit(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm <= f32::EPSILON {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+/// `blockdeletee enroll <speaker>`: listens to the configured microphone (or `--input-file`)
+/// for `seconds`, averages the x-vectors of the recognized phrases and merges them into
+/// `speech.voiceprints_file`.
+pub(crate) fn run_enrollment(config: &AppConfig, config_dir: &Path, speaker: &str, seconds: f64) -> Result<(), String> {
+    let speaker = speaker.trim();
+    if speaker.is_empty() {
+        return Err("enroll: пустой speaker id".to_string());
+    }
+    let speaker_model = config.speech.speaker_model_path.as_deref().ok_or_else(|| {
+        "enroll: speech.speaker_model_path не задан — нужна Vosk speaker model (vosk-model-spk-0.4)".to_string()
+    })?;
+    if !config.speakers.contains_key(speaker) {
+        eprintln!("[enroll-warning] `{speaker}` нет в speakers — голос узнается, но удалять блоки будет не у кого");
+    }
+
+    let ui: UiHandle = Arc::new(Mutex::new(UiState::new(
+        config.microphone.player_name.clone(),
+        config.minecraft.rcon_host.clone(),
+        config.minecraft.rcon_port,
+        config.minecraft.rcon_password.clone(),
+        UiMode::Tui,
+    )));
+    // N-best results carry no speaker vector, so the engine is always asked for one result.
+    let engine = VoskEngine::load(
+        &resolve_path(config_dir, &config.speech.model_path),
+        0,
+        Some(&resolve_path(config_dir, speaker_model)),
+    )?;
+    let sample_rate = config.speech.sample_rate;
+    let mut stream = engine.open_stream(sample_rate, None, &ui)?;
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    {
+        let shutdown = Arc::clone(&shutdown);
+        ctrlc::set_handler(move || {
+            shutdown.store(true, Ordering::SeqCst);
+        })
+        .map_err(|e| format!("Не удалось установить Ctrl+C handler: {e}"))?;
+    }
+
+    let (pcm_tx, pcm_rx) = bounded::<PcmBlock>(256);
+    let mut source = AudioSource::start_headless(config, config_dir, &ui, pcm_tx)?;
+    eprintln!("[enroll] {speaker}: говори обычным голосом {seconds:.0} с (Ctrl+C — закончить раньше)");
+
+    let mut voices: Vec<SpeakerVector> = Vec::new();
+    let mut collect = |hypothesis: Hypothesis| match hypothesis.speaker {
+        Some(voice) => {
+            eprintln!("[enroll] «{}» ({} кадров)", hypothesis.text, voice.frames);
+            voices.push(voice);
+        }
+        None => eprintln!("[enroll] «{}» — без голоса, пропущено", hypothesis.text),
+    };
+
+    let target = (seconds.max(1.0) * sample_rate as f64) as u64;
+    let mut heard = 0u64;
+    let mut resampler: Option<Resampler> = None;
+    let mut last_input_rate = 0u32;
+    while !shutdown.load(Ordering::Relaxed) && heard < target {
+        for line in ui_drain_logs(&ui) {
+            eprintln!("{line}");
+        }
+        let block = match pcm_rx.recv_timeout(POLL_INTERVAL) {
+            Ok(block) => block,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if block.sample_rate != last_input_rate {
+            last_input_rate = block.sample_rate;
+            resampler =
+                (block.sample_rate != sample_rate).then(|| Resampler::new(block.sample_rate, sample_rate));
+        }
+        let pcm = match resampler.as_mut() {
+            Some(r) => r.process(&block.samples),
+            None => block.samples,
+        };
+        heard += pcm.len() as u64;
+        match stream.accept(&pcm) {
+            Ok(outputs) => {
+                for output in outputs {
+                    if let SpeechOutput::Final(hypothesis) = output {
+                        collect(hypothesis);
+                    }
+                }
+            }
+            Err(err) => eprintln!("[recognizer-error] {err}"),
+        }
+    }
+    source.stop();
+    if let Some(hypothesis) = stream.flush() {
+        collect(hypothesis);
+    }
+    for line in ui_drain_logs(&ui) {
+        eprintln!("{line}");
+    }
+
+    let phrases = voices.len();
+    let voiceprint = Voiceprint::average(&voices)
+        .ok_or_else(|| "enroll: голос не услышан ни в одной фразе — говори громче или дольше".to_string())?;
+    let frames = voiceprint.frames;
+    let path = resolve_path(config_dir, &config.speech.voiceprints_file);
+    let mut voiceprints = Voiceprints::load(&path)?;
+    voiceprints.enroll(speaker, voiceprint);
+    voiceprints.save()?;
+    eprintln!(
+        "[enroll] {speaker}: {phrases} фраз, {frames} кадров речи — сохранено в `{}`",
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voice(vector: &[f32], frames: u16) -> SpeakerVector {
+        SpeakerVector {
+            vector: vector.to_vec(),
+            frames,
+        }
+    }
+
+    fn print(vector: &[f32], frames: u32) -> Voiceprint {
+        Voiceprint {
+            vector: vector.to_vec(),
+            frames,
+        }
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn average_weights_unit_vectors_by_frames() {
+        // Lengths do not matter, frames do: 3 parts «x» to 1 part «y».
+        let voices = [
+            voice(&[10.0, 0.0], 3),
+            voice(&[0.0, 0.5], 1),
+            voice(&[1.0, 1.0], 0),
+            voice(&[1.0, 1.0, 1.0], 50),
+            voice(&[], 7),
+        ];
+        let average = Voiceprint::average(&voices).unwrap();
+        let norm = 10f32.sqrt();
+        assert_close(&average.vector, &[3.0 / norm, 1.0 / norm]);
+        assert_eq!(average.frames, 4);
+
+        assert_eq!(Voiceprint::average(&[]), None);
+        assert_eq!(Voiceprint::average(&[voice(&[1.0, 0.0], 0)]), None);
+    }
+
+    #[test]
+    fn enrolling_again_merges_by_frames() {
+        let mut prints = Voiceprints::default();
+        prints.enroll("alex", print(&[1.0, 0.0], 3));
+        prints.enroll("alex", print(&[0.0, 1.0], 1));
+        let norm = 10f32.sqrt();
+        assert_close(&prints.entries["alex"].vector, &[3.0 / norm, 1.0 / norm]);
+        assert_eq!(prints.entries["alex"].frames, 4);
+
+        // A print from a different speaker model replaces the old one.
+        prints.enroll("alex", print(&[0.0, 0.0, 1.0], 2));
+        assert_eq!(prints.entries["alex"], print(&[0.0, 0.0, 1.0], 2));
+        prints.enroll("bob", print(&[0.0, 1.0], 5));
+        assert_eq!(prints.speaker_ids(), ["alex", "bob"]);
+    }
+
+    #[test]
+    fn closest_voice_is_accepted_within_max_distance() {
+        let mut prints = Voiceprints::default();
+        prints.enroll("alex", print(&[1.0, 0.0], 10));
+        prints.enroll("bob", print(&[0.0, 1.0], 10));
+        let max_distance = 0.5;
+
+        let (id, distance) = prints.closest(&[0.9, 0.2]).unwrap();
+        assert_eq!(id, "alex");
+        assert!(distance <= max_distance, "{distance}");
+
+        // Nearest is bob, but too far to be anyone enrolled.
+        let (id, distance) = prints.closest(&[-1.0, 0.1]).unwrap();
+        assert_eq!(id, "bob");
+        assert!(distance > max_distance, "{distance}");
+
+        assert_eq!(prints.closest(&[1.0, 0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn cosine_distance_ignores_length() {
+        assert!(cosine_distance(&[2.0, 0.0], &[5.0, 0.0]).abs() < 1e-6);
+        assert!((cosine_distance(&[1.0, 0.0], &[0.0, 3.0]) - 1.0).abs() < 1e-6);
+        assert!((cosine_distance(&[1.0, 1.0], &[-2.0, -2.0]) - 2.0).abs() < 1e-6);
+        assert_eq!(cosine_distance(&[0.0, 0.0], &[1.0, 0.0]), 1.0);
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::recorder::write_wav_header;
+use crate::resample::Resampler;
+use crate::speech::{Hypothesis, SpeechEngine, SpeechOutput, SpeechStream};
+use crate::{resolve_path, ui_log, UiHandle, WhisperConfig};
+
+/// whisper.cpp models are trained on 16 kHz audio.
+const WHISPER_SAMPLE_RATE: u32 = 16_000;
+/// Blocks quieter than this (RMS, dBFS) count as a pause between phrases.
+const SILENCE_DB: f64 = -45.0;
+/// Audio kept before the first loud block so word onsets are not cut off.
+const LEAD_IN_MS: usize = 300;
+
+static NEXT_SEGMENT: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone)]
+struct WhisperSettings {
+    binary: PathBuf,
+    model: PathBuf,
+    language: String,
+    max_segment_samples: usize,
+    silence_samples: usize,
+}
+
+/// Runs the whisper.cpp command line tool on each finished phrase. Whisper is not a
+/// streaming recognizer: there are no partials, and the recognizer thread waits while a
+/// phrase is transcribed, so this engine is meant for comparing results, not for latency.
+pub(crate) struct WhisperEngine {
+    settings: WhisperSettings,
+}
+
+impl WhisperEngine {
+    pub(crate) fn new(config: &WhisperConfig, config_dir: &Path) -> Result<Self, String> {
+        // A bare program name is looked up in PATH, anything with a separator is a path.
+        let binary = if config.binary.contains(['/', '\\']) {
+            resolve_path(config_dir, &config.binary)
+        } else {
+            PathBuf::from(&config.binary)
+        };
+        let model = resolve_path(config_dir, &config.model);
+        if !model.is_file() {
+            return Err(format!("whisper model `{}` не найден", model.display()));
+        }
+        let rate = WHISPER_SAMPLE_RATE as f64;
+        Ok(Self {
+            settings: WhisperSettings {
+                binary,
+                model,
+                language: config.language.clone(),
+                max_segment_samples: (config.max_segment_seconds * rate) as usize,
+                silence_samples: (config.silence_ms as f64 / 1000.0 * rate) as usize,
+            },
+        })
+    }
+}
+
+impl SpeechEngine for WhisperEngine {
+    fn name(&self) -> &'static str {
+        "whisper"
+    }
+
+    fn open_stream(
+        &self,
+        sample_rate: u32,
+        _grammar: Option<&[String]>,
+        ui: &UiHandle,
+    ) -> Result<Box<dyn SpeechStream>, String> {
+        Ok(Box::new(WhisperStream {
+            settings: self.settings.clone(),
+            resampler: (sample_rate != WHISPER_SAMPLE_RATE)
+                .then(|| Resampler::new(sample_rate, WHISPER_SAMPLE_RATE)),
+            buffer: Vec::new(),
+            heard_speech: false,
+            quiet_samples: 0,
+            ui: ui.clone(),
+        }))
+    }
+}
+
+struct WhisperStream {
+    settings: WhisperSettings,
+    resampler: Option<Resampler>,
+    buffer: Vec<i16>,
+    heard_speech: bool,
+    quiet_samples: usize,
+    ui: UiHandle,
+}
+
+impl WhisperStream {
+    fn transcribe(&mut self) -> Result<Option<Hypothesis>, String> {
+        let samples = std::mem::take(&mut self.buffer);
+        self.heard_speech = false;
+        self.quiet_samples = 0;
+
+        let path = std::env::temp_dir().join(format!(
+            "blockdeletee-whisper-{}-{}.wav",
+            std::process::id(),
+            NEXT_SEGMENT.fetch_add(1, Ordering::Relaxed)
+        ));
+        write_wav(&path, &samples).map_err(|e| format!("whisper: временный WAV: {e}"))?;
+        let output = Command::new(&self.settings.binary)
+            .arg("-m")
+            .arg(&self.settings.model)
+            .arg("-f")
+            .arg(&path)
+            .args(["-l", &self.settings.language, "-nt", "-np"])
+            .output();
+        let _ = fs::remove_file(&path);
+
+        let binary = self.settings.binary.display();
+        let output = output.map_err(|e| format!("whisper: не удалось запустить `{binary}`: {e}"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "whisper: `{binary}` завершился с {}: {}",
+                output.status,
+                stderr.lines().last().unwrap_or_default().trim()
+            ));
+        }
+        let text = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            // Drops markers such as `[BLANK_AUDIO]` or `(музыка)`.
+            .filter(|line| !line.is_empty() && !line.starts_with(['[', '(']))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok((!text.is_empty()).then_some(Hypothesis {
+            text,
+            confidence: None,
+            words: Vec::new(),
+            alternatives: Vec::new(),
+            speaker: None,
+        }))
+    }
+}
+
+impl SpeechStream for WhisperStream {
+    fn accept(&mut self, pcm: &[i16]) -> Result<Vec<SpeechOutput>, String> {
+        let pcm = match self.resampler.as_mut() {
+            Some(r) => r.process(pcm),
+            None => pcm.to_vec(),
+        };
+        if pcm.is_empty() {
+            return Ok(Vec::new());
+        }
+        let loud = rms_db(&pcm) >= SILENCE_DB;
+        self.buffer.extend_from_slice(&pcm);
+        if loud {
+            self.heard_speech = true;
+            self.quiet_samples = 0;
+        } else {
+            self.quiet_samples += pcm.len();
+        }
+
+        if !self.heard_speech {
+            let lead_in = WHISPER_SAMPLE_RATE as usize * LEAD_IN_MS / 1000;
+            let excess = self.buffer.len().saturating_sub(lead_in);
+            self.buffer.drain(..excess);
+            return Ok(Vec::new());
+        }
+        if self.quiet_samples >= self.settings.silence_samples
+            || self.buffer.len() >= self.settings.max_segment_samples
+        {
+            return Ok(self.transcribe()?.map(SpeechOutput::Final).into_iter().collect());
+        }
+        Ok(Vec::new())
+    }
+
+    fn flush(&mut self) -> Option<Hypothesis> {
+        if !self.heard_speech {
+            self.reset();
+            return None;
+        }
+        self.transcribe().unwrap_or_else(|err| {
+            ui_log(&self.ui, format!("[recognizer-error] {err}"));
+            None
+        })
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.heard_speech = false;
+        self.quiet_samples = 0;
+    }
+}
+
+fn write_wav(path: &Path, samples: &[i16]) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_wav_header(&mut out, WHISPER_SAMPLE_RATE, samples.len() as u64)?;
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    out.write_all(&bytes)?;
+    out.flush()
+}
+
+fn rms_db(samples: &[i16]) -> f64 {
+    let energy: f64 = samples.iter().map(|v| (*v as f64) * (*v as f64)).sum();
+    let rms = (energy / samples.len() as f64).sqrt();
+    if rms <= 0.0 {
+        return -90.0;
+    }
+    20.0 * (rms / i16::MAX as f64).log10()
+}